@@ -0,0 +1,105 @@
+//! Who is making a request, as recorded in the task history.
+//!
+//! There are no user accounts yet, so callers with a bearer token are told
+//! apart by a hash of it, the same key the rate limiter uses. Personal
+//! access tokens act as the user who issued them; see [`crate::tokens`].
+//! Everyone else is `anonymous`, and can't use the admin routes.
+//!
+//! Each route names the scope it needs in its guard, as `Actor<ReadScope>`,
+//! `Actor<WriteScope>` or `Actor<AdminScope>`. Only personal access tokens
+//! carry scopes. Any other bearer token is its own user, with nothing to
+//! check it against, so it has every scope: a caller who wants a script
+//! limited to reading has to give it a personal access token, not the
+//! bearer token they sign in with.
+
+use std::marker::PhantomData;
+
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    Request,
+};
+
+use crate::db::DB;
+use crate::rate_limit::{bearer, user_key};
+use crate::tokens::{Scope, ANONYMOUS_SCOPE, TOKEN_PREFIX};
+
+pub const ANONYMOUS: &str = "anonymous";
+
+/// The caller of a route that needs the scope `S` stands for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Actor<S>(pub String, PhantomData<S>);
+
+impl<S> Actor<S> {
+    fn new(user: String) -> Self {
+        Actor(user, PhantomData)
+    }
+}
+
+/// The scope a route's [`Actor`] guard holds callers to.
+pub trait RouteScope: Send + Sync + 'static {
+    const SCOPE: Scope;
+}
+
+/// For reading tasks and what belongs to them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadScope;
+
+/// For changing tasks and what belongs to them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteScope;
+
+/// For managing the API itself, such as tokens, webhooks and workflows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdminScope;
+
+impl RouteScope for ReadScope {
+    const SCOPE: Scope = Scope::Read;
+}
+
+impl RouteScope for WriteScope {
+    const SCOPE: Scope = Scope::Write;
+}
+
+impl RouteScope for AdminScope {
+    const SCOPE: Scope = Scope::Admin;
+}
+
+/// Fails with `401 Unauthorized` for personal access tokens that were never
+/// issued or have been revoked, and `403 Forbidden` for callers without the
+/// scope the route needs: tokens lacking it, and anonymous callers of the
+/// admin routes.
+#[rocket::async_trait]
+impl<'r, S: RouteScope> FromRequest<'r> for Actor<S> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let token = match bearer(request) {
+            Some(token) if token.starts_with(TOKEN_PREFIX) => token,
+            _ => {
+                let Some(key) = user_key(request) else {
+                    return match ANONYMOUS_SCOPE.allows(S::SCOPE) {
+                        true => Outcome::Success(Actor::new(ANONYMOUS.to_string())),
+                        false => Outcome::Failure((Status::Forbidden, ())),
+                    };
+                };
+                // Plain bearer tokens have every scope; see the module docs.
+                return Outcome::Success(Actor::new(format!("user:{key}")));
+            }
+        };
+        let Some(db) = request.rocket().state::<DB>() else {
+            return Outcome::Failure((Status::InternalServerError, ()));
+        };
+        match db.use_token(token).await {
+            Ok(Some(api_token)) if api_token.allows(S::SCOPE) => {
+                Outcome::Success(Actor::new(api_token.user))
+            }
+            Ok(Some(_)) => Outcome::Failure((Status::Forbidden, ())),
+            Ok(None) => Outcome::Failure((Status::Unauthorized, ())),
+            Err(e) => {
+                warn!("failed to check token: {}", e);
+                Outcome::Failure((Status::InternalServerError, ()))
+            }
+        }
+    }
+}
@@ -0,0 +1,287 @@
+//! Files attached to tasks. The database keeps each attachment's metadata,
+//! and the [`Storage`] its contents, under the id part of its record id.
+
+use chrono::Utc;
+use rocket::{
+    fs::TempFile,
+    http::{ContentType, Header},
+};
+
+use crate::db::{AffectedRows, DB};
+use crate::error::Error;
+use crate::query::{Order, Query, Target};
+use crate::storage::Storage;
+pub use todo_models::{attachment_name, Attachment};
+
+/// A file read from an upload, before it is stored.
+pub struct NewAttachment {
+    pub name: String,
+    pub content_type: ContentType,
+    pub bytes: Vec<u8>,
+}
+
+impl NewAttachment {
+    /// Reads an uploaded file. Its type is the one sent with it, or guessed
+    /// from its name when the client sent none or a generic one.
+    pub async fn read(file: &TempFile<'_>) -> std::io::Result<NewAttachment> {
+        let name = file
+            .raw_name()
+            .map(|name| name.dangerous_unsafe_unsanitized_raw().as_str().to_string())
+            .unwrap_or_default();
+        let guessed = name
+            .rsplit_once('.')
+            .and_then(|(_, extension)| ContentType::from_extension(extension));
+        let content_type = match file.content_type() {
+            Some(sent) if *sent != ContentType::Binary && *sent != ContentType::Any => sent.clone(),
+            _ => guessed.unwrap_or(ContentType::Binary),
+        };
+        let bytes = match file {
+            TempFile::Buffered { content } => AsRef::<[u8]>::as_ref(content).to_vec(),
+            file => match file.path() {
+                Some(path) => tokio::fs::read(path).await?,
+                None => vec![],
+            },
+        };
+        Ok(NewAttachment {
+            name,
+            content_type,
+            bytes,
+        })
+    }
+}
+
+/// An attachment's contents, served with the type it was uploaded as. Only
+/// images are shown inline. Anything else is downloaded, so an uploaded
+/// HTML page never runs on the API's origin.
+#[derive(Responder)]
+pub struct Download {
+    bytes: Vec<u8>,
+    content_type: ContentType,
+    disposition: Header<'static>,
+    nosniff: Header<'static>,
+}
+
+impl Download {
+    pub fn new(attachment: &Attachment, bytes: Vec<u8>) -> Self {
+        let content_type =
+            ContentType::parse_flexible(&attachment.content_type).unwrap_or(ContentType::Binary);
+        // SVG images can run scripts, so they are downloaded too.
+        let disposition = match content_type.top() == "image" && content_type.sub() != "svg+xml" {
+            true => "inline",
+            false => "attachment",
+        };
+        // Quoted names must be ASCII, so the exact name goes in `filename*`.
+        let ascii: String = attachment
+            .name
+            .chars()
+            .map(|c| if c.is_ascii() { c } else { '_' })
+            .collect();
+        let disposition = format!(
+            "{disposition}; filename=\"{ascii}\"; filename*=UTF-8''{}",
+            percent_encode(&attachment.name)
+        );
+        Download {
+            bytes,
+            content_type,
+            disposition: Header::new("Content-Disposition", disposition),
+            nosniff: Header::new("X-Content-Type-Options", "nosniff"),
+        }
+    }
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// The key an attachment's contents are stored under.
+fn storage_key(id: &str) -> &str {
+    id.split_once(':').map_or(id, |(_, key)| key)
+}
+
+impl DB {
+    /// The task's attachments, oldest first.
+    pub async fn get_attachments(&self, task: String) -> Result<Vec<Attachment>, Error> {
+        self.get_task(task.clone()).await?;
+        let query = Query::select("attachments")
+            .where_eq("task", task)
+            .order_by("created_at", Order::Asc);
+        self.fetch(query).await
+    }
+
+    pub async fn get_attachment(&self, id: String) -> Result<Attachment, Error> {
+        // Any record id parses, so other tables are turned away here.
+        if !id.starts_with("attachments:") {
+            return Err(Error::XRecordNotFound(id));
+        }
+        let query = Query::select(Target::record(&id)?);
+        self.fetch_one(query)
+            .await?
+            .ok_or(Error::XRecordNotFound(id))
+    }
+
+    /// Records the attachment, then stores its contents. If storing fails,
+    /// the record is removed again.
+    pub async fn add_attachment(
+        &self,
+        task: String,
+        file: NewAttachment,
+        actor: &str,
+        storage: &dyn Storage,
+    ) -> Result<Attachment, Error> {
+        self.get_task(task.clone()).await?;
+        if file.bytes.is_empty() {
+            return Err(todo_models::Error::AttachmentEmpty.into());
+        }
+
+        let query = Query::create("attachments")
+            .set("task", task)
+            .set("name", attachment_name(&file.name))
+            .set("content_type", file.content_type.to_string())
+            .set("size", file.bytes.len() as i64)
+            .set("author", actor)
+            .set("created_at", Utc::now());
+        let attachment: Attachment = self.fetch_one(query).await?.ok_or(Error::XNoResponse)?;
+
+        if let Err(e) = storage.put(storage_key(&attachment.id), file.bytes).await {
+            let _ = self
+                .run(Query::delete(Target::record(&attachment.id)?))
+                .await;
+            return Err(e);
+        }
+        Ok(attachment)
+    }
+
+    /// The attachment and its contents.
+    pub async fn download_attachment(
+        &self,
+        id: String,
+        storage: &dyn Storage,
+    ) -> Result<Download, Error> {
+        let attachment = self.get_attachment(id.clone()).await?;
+        match storage.get(storage_key(&attachment.id)).await? {
+            Some(bytes) => Ok(Download::new(&attachment, bytes)),
+            None => Err(Error::XRecordNotFound(id)),
+        }
+    }
+
+    /// Deletes the contents first, so a failure leaves the attachment
+    /// listed and the delete can be retried.
+    pub async fn delete_attachment(
+        &self,
+        id: String,
+        storage: &dyn Storage,
+    ) -> Result<AffectedRows, Error> {
+        let attachment = match self.get_attachment(id.clone()).await {
+            Ok(attachment) => attachment,
+            Err(Error::XRecordNotFound(_)) => return Ok(AffectedRows { affected_rows: 0 }),
+            Err(e) => return Err(e),
+        };
+        storage.delete(storage_key(&attachment.id)).await?;
+        let _ = self.run(Query::delete(Target::record(&id)?)).await?;
+        Ok(AffectedRows { affected_rows: 1 })
+    }
+
+    /// Deletes every attachment of a task, for when the task is deleted.
+    pub async fn delete_attachments(
+        &self,
+        task: String,
+        storage: &dyn Storage,
+    ) -> Result<(), Error> {
+        let query = Query::select("attachments").where_eq("task", task);
+        let attachments: Vec<Attachment> = self.fetch(query).await?;
+        for attachment in attachments {
+            self.delete_attachment(attachment.id, storage).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::ANONYMOUS;
+    use crate::storage::MemoryStorage;
+    use std::sync::Arc;
+    use surrealdb::{dbs::Session, kvs::Datastore};
+
+    async fn db() -> DB {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        DB { ds, sesh }
+    }
+
+    fn file(name: &str, bytes: &[u8]) -> NewAttachment {
+        NewAttachment {
+            name: name.to_string(),
+            content_type: ContentType::PNG,
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_attachments() {
+        let db = db().await;
+        let storage = MemoryStorage::default();
+        let task = db.add_task("test".to_string(), ANONYMOUS).await.unwrap();
+
+        let attachment = db
+            .add_attachment(
+                task.id.clone(),
+                file("../shot.png", b"png"),
+                ANONYMOUS,
+                &storage,
+            )
+            .await
+            .unwrap();
+        assert_eq!(attachment.name, "shot.png");
+        assert_eq!(attachment.content_type, "image/png");
+        assert_eq!(attachment.size, 3);
+        assert_eq!(
+            storage.get(storage_key(&attachment.id)).await.unwrap(),
+            Some(b"png".to_vec())
+        );
+
+        let res = db
+            .add_attachment(task.id.clone(), file("empty", b""), ANONYMOUS, &storage)
+            .await;
+        assert!(matches!(res, Err(Error::Invalid(_))));
+
+        let attachments = db.get_attachments(task.id.clone()).await.unwrap();
+        assert_eq!(attachments, std::slice::from_ref(&attachment));
+
+        db.delete_attachments(task.id.clone(), &storage)
+            .await
+            .unwrap();
+        assert!(db.get_attachments(task.id).await.unwrap().is_empty());
+        assert_eq!(
+            storage.get(storage_key(&attachment.id)).await.unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_download_headers() {
+        let attachment = Attachment {
+            id: "attachments:abc".to_string(),
+            task: "tasks:abc".to_string(),
+            name: "résumé.html".to_string(),
+            content_type: "text/html".to_string(),
+            size: 4,
+            author: ANONYMOUS.to_string(),
+            created_at: Utc::now(),
+        };
+        let download = Download::new(&attachment, b"<p/>".to_vec());
+        assert_eq!(download.content_type.to_string(), "text/html");
+        assert_eq!(
+            download.disposition.value(),
+            "attachment; filename=\"r_sum_.html\"; filename*=UTF-8''r%C3%A9sum%C3%A9.html"
+        );
+    }
+}
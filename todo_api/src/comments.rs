@@ -0,0 +1,212 @@
+//! Threaded comments on tasks. Only a comment's author may edit or delete
+//! it, where the author is the [`Actor`](crate::actor::Actor) that wrote it.
+
+use chrono::Utc;
+use surrealdb::sql::Value;
+
+use crate::db::{AffectedRows, DB};
+use crate::error::Error;
+use crate::query::{Order, Query, Return, Target};
+pub use todo_models::{Comment, EditComment, NewComment};
+
+impl DB {
+    /// The task's comments, oldest first.
+    pub async fn get_comments(&self, task: String) -> Result<Vec<Comment>, Error> {
+        self.get_task(task.clone()).await?;
+        let query = Query::select("comments")
+            .where_eq("task", task)
+            .order_by("created_at", Order::Asc);
+        self.fetch(query).await
+    }
+
+    pub async fn get_comment(&self, id: String) -> Result<Comment, Error> {
+        // Any record id parses, so other tables are turned away here.
+        if !id.starts_with("comments:") {
+            return Err(Error::XRecordNotFound(id));
+        }
+        let query = Query::select(Target::record(&id)?);
+        self.fetch_one(query)
+            .await?
+            .ok_or(Error::XRecordNotFound(id))
+    }
+
+    /// Comments on a task, or replies to a comment on the same task.
+    pub async fn add_comment(
+        &self,
+        task: String,
+        comment: NewComment,
+        actor: &str,
+    ) -> Result<Comment, Error> {
+        self.get_task(task.clone()).await?;
+        if let Some(parent) = &comment.parent {
+            let invalid = || todo_models::Error::CommentParentInvalid(parent.clone());
+            match self.get_comment(parent.clone()).await {
+                Ok(parent) if parent.task == task => {}
+                Ok(_) | Err(Error::XRecordNotFound(_)) => return Err(invalid().into()),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let query = Query::create("comments")
+            .set("task", task)
+            .set("parent", comment.parent.map_or(Value::None, Value::from))
+            .set("author", actor)
+            .set("body", comment.body)
+            .set("created_at", Utc::now())
+            .set("deleted", false);
+        self.fetch_one(query).await?.ok_or(Error::XNoResponse)
+    }
+
+    pub async fn edit_comment(
+        &self,
+        id: String,
+        edit: EditComment,
+        actor: &str,
+    ) -> Result<Comment, Error> {
+        let comment = self.own_comment(id.clone(), actor).await?;
+        if comment.deleted {
+            return Err(Error::XRecordNotFound(id));
+        }
+
+        let query = Query::update(Target::record(&id)?)
+            .set("body", edit.body)
+            .set("edited_at", Utc::now())
+            .returning(Return::After);
+        self.fetch_one(query)
+            .await?
+            .ok_or(Error::XRecordNotFound(id))
+    }
+
+    /// Deletes a comment. One with replies keeps its place in the thread,
+    /// marked deleted and without its body. Missing comments affect no rows.
+    pub async fn delete_comment(&self, id: String, actor: &str) -> Result<AffectedRows, Error> {
+        match self.own_comment(id.clone(), actor).await {
+            Ok(comment) if !comment.deleted => {}
+            Ok(_) | Err(Error::XRecordNotFound(_)) => return Ok(AffectedRows { affected_rows: 0 }),
+            Err(e) => return Err(e),
+        }
+
+        let replies: Vec<Comment> = self
+            .fetch(
+                Query::select("comments")
+                    .where_eq("parent", id.clone())
+                    .limit(1),
+            )
+            .await?;
+        let query = match replies.is_empty() {
+            true => Query::delete(Target::record(&id)?),
+            false => Query::update(Target::record(&id)?)
+                .set("body", "")
+                .set("deleted", true)
+                .returning(Return::None),
+        };
+        let _ = self.run(query).await?;
+        Ok(AffectedRows { affected_rows: 1 })
+    }
+
+    async fn own_comment(&self, id: String, actor: &str) -> Result<Comment, Error> {
+        let comment = self.get_comment(id).await?;
+        match comment.author == actor {
+            true => Ok(comment),
+            false => Err(Error::XForbidden(comment.id)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::ANONYMOUS;
+    use std::sync::Arc;
+    use surrealdb::{dbs::Session, kvs::Datastore};
+
+    async fn db() -> DB {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        DB { ds, sesh }
+    }
+
+    fn new_comment(body: &str, parent: Option<&Comment>) -> NewComment {
+        NewComment {
+            body: body.to_string(),
+            parent: parent.map(|comment| comment.id.clone()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_comments() {
+        let db = db().await;
+        let task = db.add_task("test".to_string(), ANONYMOUS).await.unwrap();
+
+        let first = db
+            .add_comment(task.id.clone(), new_comment("first", None), "user:ada")
+            .await
+            .unwrap();
+        assert_eq!(first.author, "user:ada");
+        let reply = db
+            .add_comment(
+                task.id.clone(),
+                new_comment("reply", Some(&first)),
+                ANONYMOUS,
+            )
+            .await
+            .unwrap();
+        assert_eq!(reply.parent.as_deref(), Some(first.id.as_str()));
+
+        let edit = EditComment {
+            body: "edited".to_string(),
+        };
+        let res = db
+            .edit_comment(first.id.clone(), edit.clone(), ANONYMOUS)
+            .await;
+        assert!(matches!(res, Err(Error::XForbidden(_))));
+        let edited = db
+            .edit_comment(first.id.clone(), edit, "user:ada")
+            .await
+            .unwrap();
+        assert_eq!(edited.body, "edited");
+        assert!(edited.edited_at.is_some());
+
+        // The first comment has a reply, so it stays as a placeholder.
+        let res = db
+            .delete_comment(first.id.clone(), "user:ada")
+            .await
+            .unwrap();
+        assert_eq!(res.affected_rows, 1);
+        let res = db
+            .delete_comment(reply.id.clone(), ANONYMOUS)
+            .await
+            .unwrap();
+        assert_eq!(res.affected_rows, 1);
+
+        let comments = db.get_comments(task.id).await.unwrap();
+        assert_eq!(comments.len(), 1);
+        assert!(comments[0].deleted);
+        assert_eq!(comments[0].body, "");
+    }
+
+    #[tokio::test]
+    async fn test_reply_must_be_on_the_same_task() {
+        let db = db().await;
+        let a = db.add_task("a".to_string(), ANONYMOUS).await.unwrap();
+        let b = db.add_task("b".to_string(), ANONYMOUS).await.unwrap();
+        let on_a = db
+            .add_comment(a.id, new_comment("on a", None), ANONYMOUS)
+            .await
+            .unwrap();
+
+        let res = db
+            .add_comment(b.id.clone(), new_comment("reply", Some(&on_a)), ANONYMOUS)
+            .await;
+        assert!(matches!(res, Err(Error::Invalid(_))));
+
+        let res = db
+            .add_comment(
+                "tasks:missing".to_string(),
+                new_comment("hi", None),
+                ANONYMOUS,
+            )
+            .await;
+        assert!(matches!(res, Err(Error::XRecordNotFound(_))));
+    }
+}
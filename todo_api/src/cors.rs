@@ -1,9 +1,112 @@
 use rocket::{
-    fairing::{Fairing, Info, Kind},
-    http::{ContentType, Header, Method, Status},
-    Request, Response,
+    fairing::{self, Fairing, Info, Kind},
+    http::{Header, Method, Status},
+    route::{Handler, Outcome},
+    serde::{Deserialize, Serialize},
+    Build, Data, Request, Response, Rocket, Route,
 };
 
+/// CORS policy, read from the `cors` table of the Rocket config.
+///
+/// Origins are matched exactly (`https://app.example.com`), by subdomain
+/// (`https://*.example.com`, which does not match the apex domain), or with
+/// `*` for any origin. `*` cannot be combined with credentials.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    /// `*` echoes whatever headers a preflight asks for.
+    pub allowed_headers: Vec<String>,
+    pub allow_credentials: bool,
+    /// Seconds a browser may cache a preflight response.
+    pub max_age: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        CorsConfig {
+            allowed_origins: vec![],
+            allowed_methods: ["GET", "POST", "PUT", "PATCH", "DELETE"]
+                .map(String::from)
+                .to_vec(),
+            allowed_headers: ["Content-Type", "Authorization"].map(String::from).to_vec(),
+            allow_credentials: false,
+            max_age: 86400,
+        }
+    }
+}
+
+impl CorsConfig {
+    fn validate(&self) -> Result<(), String> {
+        for origin in &self.allowed_origins {
+            if origin == "*" {
+                if self.allow_credentials {
+                    return Err("the `*` origin cannot be used with credentials".into());
+                }
+            } else if !origin.contains("://") {
+                return Err(format!("origin '{origin}' is missing a scheme"));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn allows(&self, origin: &str) -> bool {
+        self.allowed_origins
+            .iter()
+            .any(|pattern| origin_matches(pattern, origin))
+    }
+}
+
+fn origin_matches(pattern: &str, origin: &str) -> bool {
+    if pattern == "*" {
+        return true;
+    }
+
+    let (Some((pattern_scheme, pattern_host)), Some((scheme, host))) =
+        (pattern.split_once("://"), origin.split_once("://"))
+    else {
+        return false;
+    };
+    if !pattern_scheme.eq_ignore_ascii_case(scheme) {
+        return false;
+    }
+
+    match pattern_host.strip_prefix("*.") {
+        Some(domain) => {
+            let host = host.to_ascii_lowercase();
+            let suffix = format!(".{}", domain.to_ascii_lowercase());
+            host.len() > suffix.len() && host.ends_with(&suffix)
+        }
+        None => pattern_host.eq_ignore_ascii_case(host),
+    }
+}
+
+/// Replaces parameter names so routes that only differ by them, like
+/// `/task/<id>` and `/task/<title>`, share one preflight route.
+fn preflight_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment {
+            s if s.starts_with('<') && s.ends_with("..>") => "<_..>",
+            s if s.starts_with('<') => "<_>",
+            s => s,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Answers preflight requests. The CORS headers are added by the fairing's
+/// response hook like on every other response.
+#[derive(Clone)]
+struct Preflight;
+
+#[rocket::async_trait]
+impl Handler for Preflight {
+    async fn handle<'r>(&self, request: &'r Request<'_>, _: Data<'r>) -> Outcome<'r> {
+        Outcome::from(request, Status::NoContent)
+    }
+}
+
 #[allow(clippy::upper_case_acronyms)]
 pub struct CORS;
 
@@ -11,27 +114,271 @@ pub struct CORS;
 impl Fairing for CORS {
     fn info(&self) -> Info {
         Info {
-            name: "Add CORS headers to responses",
-            kind: Kind::Response,
+            name: "CORS policy",
+            kind: Kind::Ignite | Kind::Response,
         }
     }
 
-    // source: https://stackoverflow.com/questions/62412361/how-to-set-up-cors-or-options-for-rocket-rs
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let config = match rocket.figment().find_value("cors") {
+            Ok(_) => rocket.figment().extract_inner::<CorsConfig>("cors"),
+            Err(_) => Ok(CorsConfig::default()),
+        };
+        let config = match config.map_err(|e| e.to_string()).and_then(|config| {
+            config.validate()?;
+            Ok(config)
+        }) {
+            Ok(config) => config,
+            Err(e) => {
+                error!("invalid cors config: {}", e);
+                return Err(rocket);
+            }
+        };
+
+        // Only mounted paths get a preflight route, so OPTIONS requests to
+        // unknown paths are answered with 404 like any other method.
+        let mut paths: Vec<String> = vec![];
+        for route in rocket.routes().filter(|r| r.method != Method::Options) {
+            let path = preflight_path(route.uri.path());
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        // Preflight routes can overlap, e.g. `/a/<_>` and `/<_>/b`, so each
+        // gets its own rank to avoid collisions.
+        let routes: Vec<Route> = paths
+            .iter()
+            .enumerate()
+            .map(|(rank, path)| Route::ranked(rank as isize, Method::Options, path, Preflight))
+            .collect();
+
+        Ok(rocket.manage(config).mount("/", routes))
+    }
+
     async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
-        response.set_header(Header::new("Access-Control-Allow-Origin", "*"));
+        response.adjoin_header(Header::new("Vary", "Origin"));
+
+        let config = match request.rocket().state::<CorsConfig>() {
+            Some(config) => config,
+            None => return,
+        };
+        let origin = match request.headers().get_one("Origin") {
+            Some(origin) if config.allows(origin) => origin,
+            _ => return,
+        };
+
         response.set_header(Header::new(
-            "Access-Control-Allow-Methods",
-            "POST, GET, PATCH, DELETE, OPTIONS",
-        ));
-        response.set_header(Header::new("Access-Control-Allow-Headers", "*"));
-        response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
-
-        // source: https://webprogramming.ninja/2022/08/25/handling-options-requests-in-rust-using-rocket-with-cors/
-        if request.method() == Method::Options {
-            let body = "";
-            response.set_header(ContentType::Plain);
-            response.set_sized_body(body.len(), std::io::Cursor::new(body));
-            response.set_status(Status::Ok);
+            "Access-Control-Allow-Origin",
+            origin.to_string(),
+        ));
+        if config.allow_credentials {
+            response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+        }
+
+        let requested_headers = request.headers().get_one("Access-Control-Request-Headers");
+        if request.method() == Method::Options
+            && request.headers().contains("Access-Control-Request-Method")
+        {
+            let allowed_headers = match (config.allowed_headers.as_slice(), requested_headers) {
+                ([wildcard], Some(requested)) if wildcard == "*" => requested.to_string(),
+                (headers, _) => headers.join(", "),
+            };
+            response.set_header(Header::new(
+                "Access-Control-Allow-Methods",
+                config.allowed_methods.join(", "),
+            ));
+            response.set_header(Header::new("Access-Control-Allow-Headers", allowed_headers));
+            response.set_header(Header::new(
+                "Access-Control-Max-Age",
+                config.max_age.to_string(),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+// The route macros export `uri!` helpers that these tests don't use.
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use rocket::{error::ErrorKind, local::blocking::Client};
+
+    #[get("/task/<_id>")]
+    fn task(_id: String) -> &'static str {
+        "task"
+    }
+
+    #[post("/task/<_title>")]
+    fn add(_title: String) -> &'static str {
+        "added"
+    }
+
+    fn client(config: CorsConfig) -> Client {
+        let figment = rocket::Config::figment().merge(("cors", config));
+        let rocket = rocket::custom(figment)
+            .mount("/", routes![task, add])
+            .attach(CORS);
+        Client::tracked(rocket).expect("valid rocket instance")
+    }
+
+    fn config(origins: &[&str]) -> CorsConfig {
+        CorsConfig {
+            allowed_origins: origins.iter().map(|o| o.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_origin_matching() {
+        assert!(origin_matches(
+            "https://app.example.com",
+            "https://APP.example.com"
+        ));
+        assert!(!origin_matches(
+            "https://app.example.com",
+            "http://app.example.com"
+        ));
+        assert!(origin_matches(
+            "https://*.example.com",
+            "https://a.b.example.com"
+        ));
+        assert!(!origin_matches(
+            "https://*.example.com",
+            "https://example.com"
+        ));
+        assert!(!origin_matches(
+            "https://*.example.com",
+            "https://example.com.evil.com"
+        ));
+        assert!(!origin_matches(
+            "https://*.example.com",
+            "https://a.example.com:8443"
+        ));
+        assert!(origin_matches("*", "null"));
+    }
+
+    #[test]
+    fn test_allowed_origin_is_echoed() {
+        let client = client(config(&["http://app.test"]));
+        let response = client
+            .get("/task/tasks:1")
+            .header(Header::new("Origin", "http://app.test"))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        let headers = response.headers();
+        assert_eq!(
+            headers.get_one("Access-Control-Allow-Origin"),
+            Some("http://app.test")
+        );
+        assert_eq!(headers.get_one("Vary"), Some("Origin"));
+        assert_eq!(headers.get_one("Access-Control-Allow-Credentials"), None);
+    }
+
+    #[test]
+    fn test_other_origin_gets_no_cors_headers() {
+        let client = client(config(&["http://app.test"]));
+        let response = client
+            .get("/task/tasks:1")
+            .header(Header::new("Origin", "http://evil.test"))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            None
+        );
+        assert_eq!(response.headers().get_one("Vary"), Some("Origin"));
+    }
+
+    #[test]
+    fn test_credentials() {
+        let client = client(CorsConfig {
+            allow_credentials: true,
+            ..config(&["https://*.app.test"])
+        });
+        let response = client
+            .get("/task/tasks:1")
+            .header(Header::new("Origin", "https://web.app.test"))
+            .dispatch();
+
+        assert_eq!(
+            response
+                .headers()
+                .get_one("Access-Control-Allow-Credentials"),
+            Some("true")
+        );
+    }
+
+    #[test]
+    fn test_preflight() {
+        let client = client(CorsConfig {
+            max_age: 60,
+            ..config(&["http://app.test"])
+        });
+        let response = client
+            .options("/task/tasks:1")
+            .header(Header::new("Origin", "http://app.test"))
+            .header(Header::new("Access-Control-Request-Method", "DELETE"))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::NoContent);
+        let headers = response.headers();
+        assert_eq!(
+            headers.get_one("Access-Control-Allow-Methods"),
+            Some("GET, POST, PUT, PATCH, DELETE")
+        );
+        assert_eq!(
+            headers.get_one("Access-Control-Allow-Headers"),
+            Some("Content-Type, Authorization")
+        );
+        assert_eq!(headers.get_one("Access-Control-Max-Age"), Some("60"));
+    }
+
+    #[test]
+    fn test_preflight_echoes_wildcard_headers() {
+        let client = client(CorsConfig {
+            allowed_headers: vec!["*".to_string()],
+            ..config(&["http://app.test"])
+        });
+        let response = client
+            .options("/task/tasks:1")
+            .header(Header::new("Origin", "http://app.test"))
+            .header(Header::new("Access-Control-Request-Method", "PUT"))
+            .header(Header::new("Access-Control-Request-Headers", "x-custom"))
+            .dispatch();
+
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Headers"),
+            Some("x-custom")
+        );
+    }
+
+    #[test]
+    fn test_options_on_unmounted_path_is_not_found() {
+        let client = client(config(&["http://app.test"]));
+        let response = client
+            .options("/nowhere")
+            .header(Header::new("Origin", "http://app.test"))
+            .header(Header::new("Access-Control-Request-Method", "GET"))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_wildcard_with_credentials_fails_to_launch() {
+        let figment = rocket::Config::figment().merge((
+            "cors",
+            CorsConfig {
+                allow_credentials: true,
+                ..config(&["*"])
+            },
+        ));
+        let rocket = rocket::custom(figment).attach(CORS);
+        match Client::tracked(rocket) {
+            Err(e) => assert!(matches!(e.kind(), ErrorKind::FailedFairings(_))),
+            Ok(_) => panic!("rocket launched with an invalid cors config"),
         }
     }
 }
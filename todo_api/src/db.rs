@@ -1,56 +1,58 @@
-use std::{collections::BTreeMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+};
 
-use crate::{prelude::W, utils::macros::map};
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use crate::{
+    actor::ANONYMOUS,
+    prelude::W,
+    query::{Order, Query, Return, Target},
+    rate_limit::Bucket,
+    utils::{
+        macros::map,
+        value::{from_value, to_value},
+    },
+    webhooks::{delivery_query, Subscription},
+    workspaces::Role,
+};
+use chrono::Utc;
+use serde::{de::DeserializeOwned, Deserialize};
 use surrealdb::{
     dbs::Session,
     kvs::Datastore,
-    sql::{thing, Array, Object, Value},
-    Response,
+    sql::{Array, Id, Thing, Value},
+};
+use todo_models::rank;
+pub use todo_models::{
+    diff, AffectedRows, Assignment, Member, MoveTask, NewTask, Status, Task, TaskAction, TaskEvent,
+    TaskRecord, UpdateTask, WebhookPayload, Workflow,
 };
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Task {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub id: Option<String>,
-    pub title: String,
-    pub completed: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub created_at: Option<DateTime<Utc>>,
-}
-
-impl From<Task> for Value {
-    fn from(val: Task) -> Self {
-        match val.id {
-            Some(v) => map![
-                "id".into() => v.into(),
-                "title".into() => val.title.into(),
-                "completed".into() => val.completed.into(),
-            ]
-            .into(),
-            None => map![
-                "title".into() => val.title.into(),
-                "completed".into() => val.completed.into(),
-            ]
-            .into(),
-        }
+impl TryFrom<W<Task>> for Value {
+    type Error = crate::error::Error;
+
+    fn try_from(val: W<Task>) -> Result<Self, Self::Error> {
+        to_value(&val.0)
     }
 }
 
-impl Creatable for Task {}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct AffectedRows {
-    pub affected_rows: u64,
+impl From<W<NewTask>> for Value {
+    fn from(val: W<NewTask>) -> Self {
+        map![
+            "title".into() => val.0.title.into(),
+            "completed".into() => false.into(),
+        ]
+        .into()
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct RowId {
-    pub id: String,
-}
+impl TryFrom<W<UpdateTask>> for Value {
+    type Error = crate::error::Error;
 
-pub trait Creatable: Into<Value> {}
+    fn try_from(val: W<UpdateTask>) -> Result<Self, Self::Error> {
+        to_value(&val.0)
+    }
+}
 
 #[derive(Clone)]
 pub struct DB {
@@ -73,104 +75,730 @@ impl DB {
         Ok(res)
     }
 
-    pub async fn add_task(&self, title: String) -> Result<Object, crate::error::Error> {
-        let sql = "CREATE tasks SET title = $title, completed = false, created_at = time.now()";
-        let vars: BTreeMap<String, Value> = map!["title".into() => Value::Strand(title.into())];
-        let res = self.execute(sql, Some(vars)).await?;
+    /// Runs a query and returns the result of its statement.
+    pub async fn run(&self, query: Query) -> Result<Value, crate::error::Error> {
+        let (sql, vars) = query.build();
+        let res = self.execute(&sql, Some(vars)).await?;
 
-        let first_res = res.into_iter().next().expect("no response");
-        let response = match first_res.result {
-            Ok(v) => v.first(),
-            Err(e) => {
-                return Err(crate::error::Error::Surreal(surrealdb::Error::Db(e)));
+        let first_res = res
+            .into_iter()
+            .next()
+            .ok_or(crate::error::Error::XNoResponse)?;
+        first_res
+            .result
+            .map_err(|e| crate::error::Error::Surreal(surrealdb::Error::Db(e)))
+    }
+
+    /// Runs a query and returns every record it produced.
+    pub async fn fetch<T: DeserializeOwned>(
+        &self,
+        query: Query,
+    ) -> Result<Vec<T>, crate::error::Error> {
+        let array: Array = W(self.run(query).await?).try_into()?;
+        array.into_iter().map(from_value).collect()
+    }
+
+    /// Runs a query and returns its first record, if there is one.
+    pub async fn fetch_one<T: DeserializeOwned>(
+        &self,
+        query: Query,
+    ) -> Result<Option<T>, crate::error::Error> {
+        match self.run(query).await?.first() {
+            Value::None => Ok(None),
+            value => from_value(value).map(Some),
+        }
+    }
+
+    /// Runs `queries` in one transaction.
+    async fn run_all(&self, queries: Vec<Query>) -> Result<Vec<Value>, crate::error::Error> {
+        self.transaction(|tx| async move {
+            for query in queries {
+                tx.add(query);
             }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Adds a task outside any workspace. The API always goes through
+    /// [`DB::add_task_in`].
+    #[cfg(test)]
+    pub async fn add_task(&self, title: String, actor: &str) -> Result<Task, crate::error::Error> {
+        self.add_task_in(title, None, actor).await
+    }
+
+    /// Adds a task to a workspace, or outside any with `None`.
+    pub async fn add_task_in(
+        &self,
+        title: String,
+        workspace: Option<String>,
+        actor: &str,
+    ) -> Result<Task, crate::error::Error> {
+        let rank = self.ranks_after_last(1).await?.remove(0);
+        let workflow = self.get_workflow(workspace.as_deref()).await?;
+        let task = Task {
+            id: new_task_id(),
+            title,
+            completed: false,
+            created_at: Utc::now(),
+            due: None,
+            priority: None,
+            remind_at: None,
+            rank,
+            status: workflow.first(false).id.clone(),
+            workspace,
+            assignee: None,
         };
-        W(response).try_into()
+        let log = self
+            .change_log(task.workspace.as_deref(), TaskAction::Created)
+            .await?;
+        let mut queries = vec![insert_query(&task)?];
+        queries.extend(log.record(actor, TaskAction::Created, None, Some(&task))?);
+        let results = self.run_all(queries).await?;
+        let created = results.into_iter().next().map(|value| value.first());
+        from_value(created.ok_or(crate::error::Error::XNoResponse)?)
     }
 
-    pub async fn get_task(&self, id: String) -> Result<Object, crate::error::Error> {
-        let sql = "SELECT * FROM $th";
-        let tid = format!("{}", id);
-        let thing = match thing(&tid) {
-            Ok(v) => v,
-            Err(e) => {
-                return Err(crate::error::Error::Surreal(surrealdb::Error::Db(e)));
-            }
+    /// Creates tasks with every field given, as when restoring a backup, in
+    /// one transaction, in a workspace or outside any with `None`. Records
+    /// without a creation time are stamped with the current time.
+    pub async fn insert_tasks(
+        &self,
+        records: Vec<TaskRecord>,
+        workspace: Option<&str>,
+        actor: &str,
+    ) -> Result<Vec<Task>, crate::error::Error> {
+        let ranks = self.ranks_after_last(records.len()).await?;
+        let workflow = self.get_workflow(workspace).await?;
+        let log = self.change_log(workspace, TaskAction::Created).await?;
+        let mut queries = vec![];
+        let mut inserts = vec![];
+        for (record, rank) in records.into_iter().zip(ranks) {
+            let task = Task {
+                id: new_task_id(),
+                title: record.title,
+                completed: record.completed,
+                created_at: record.created_at.unwrap_or_else(Utc::now),
+                due: record.due,
+                priority: record.priority,
+                remind_at: None,
+                rank,
+                status: workflow.first(record.completed).id.clone(),
+                workspace: workspace.map(String::from),
+                assignee: None,
+            };
+            inserts.push(queries.len());
+            queries.push(insert_query(&task)?);
+            queries.extend(log.record(actor, TaskAction::Created, None, Some(&task))?);
+        }
+
+        let results = self.run_all(queries).await?;
+        inserts
+            .into_iter()
+            .map(|i| from_value(results[i].first()))
+            .collect()
+    }
+
+    pub async fn get_task(&self, id: String) -> Result<Task, crate::error::Error> {
+        // Any record id parses, so other tables are turned away here.
+        if !id.starts_with("tasks:") {
+            return Err(crate::error::Error::XRecordNotFound(id));
+        }
+        let query = Query::select(Target::record(&id)?);
+        self.fetch_one(query)
+            .await?
+            .ok_or(crate::error::Error::XRecordNotFound(id))
+    }
+
+    /// Every task in every workspace. The API always lists one workspace
+    /// with [`DB::get_tasks_in`].
+    #[cfg(test)]
+    pub async fn get_all_tasks(&self) -> Result<Vec<Task>, crate::error::Error> {
+        let query = Query::select("tasks")
+            .order_by("rank", Order::Asc)
+            .order_by("created_at", Order::Asc);
+        self.fetch(query).await
+    }
+
+    /// The tasks of a workspace, or those outside any with `None`, in list
+    /// order: by rank, then by when they were made.
+    pub async fn get_tasks_in(
+        &self,
+        workspace: Option<&str>,
+    ) -> Result<Vec<Task>, crate::error::Error> {
+        self.fetch(tasks_query(workspace)).await
+    }
+
+    /// Up to `limit` of the tasks [`DB::get_tasks_in`] lists, from the
+    /// `start`th on.
+    pub async fn get_tasks_page(
+        &self,
+        workspace: Option<&str>,
+        start: u64,
+        limit: u64,
+    ) -> Result<Vec<Task>, crate::error::Error> {
+        self.fetch(tasks_query(workspace).limit(limit).start(start))
+            .await
+    }
+
+    /// Like [`DB::get_tasks_in`], keeping only the tasks assigned to `user`.
+    pub async fn get_assigned_tasks(
+        &self,
+        workspace: Option<&str>,
+        user: &str,
+    ) -> Result<Vec<Task>, crate::error::Error> {
+        self.fetch(tasks_query(workspace).where_eq("assignee", user))
+            .await
+    }
+
+    /// `count` ranks that sort after every task, for adding tasks at the end.
+    async fn ranks_after_last(&self, count: usize) -> Result<Vec<String>, crate::error::Error> {
+        let query = Query::select("tasks")
+            .order_by("rank", Order::Desc)
+            .limit(1);
+        let last: Option<Task> = self.fetch_one(query).await?;
+
+        let mut ranks: Vec<String> = Vec::with_capacity(count);
+        let mut prev = last.map(|task| task.rank);
+        for _ in 0..count {
+            let rank = rank::between(prev.as_deref(), None).ok_or_else(|| {
+                crate::error::Error::XConversion(format!("invalid task rank {prev:?}"))
+            })?;
+            prev = Some(rank.clone());
+            ranks.push(rank);
+        }
+        Ok(ranks)
+    }
+
+    /// Moves a task just before or after another one in the same list: its
+    /// workspace, or the tasks outside any. Targets in other lists are not
+    /// found. Usually only the moved task is ranked again, but when its new
+    /// neighbours leave no room, as with tasks made before ranks existed,
+    /// the list is ranked afresh in the same transaction.
+    pub async fn move_task(
+        &self,
+        id: String,
+        to: MoveTask,
+        actor: &str,
+    ) -> Result<Task, crate::error::Error> {
+        let before = self.get_task(id.clone()).await?;
+        if to.target() == id {
+            return Ok(before);
+        }
+        let mut tasks = self.get_tasks_in(before.workspace.as_deref()).await?;
+        let log = self
+            .change_log(before.workspace.as_deref(), TaskAction::Moved)
+            .await?;
+        tasks.retain(|task| task.id != id);
+
+        let target = tasks
+            .iter()
+            .position(|task| task.id == to.target())
+            .ok_or_else(|| crate::error::Error::XRecordNotFound(to.target().to_string()))?;
+        let index = match to {
+            MoveTask::Before(_) => target,
+            MoveTask::After(_) => target + 1,
         };
-        let vars: BTreeMap<String, Value> = map!["th".into() => thing.into()];
-        let ress = self.execute(sql, Some(vars)).await?;
+        let prev = index.checked_sub(1).map(|i| tasks[i].rank.as_str());
+        let next = tasks.get(index).map(|task| task.rank.as_str());
 
-        let first_res = ress.into_iter().next().expect("no response");
-        let response = match first_res.result {
-            Ok(v) => v.first(),
-            Err(e) => {
-                return Err(crate::error::Error::Surreal(surrealdb::Error::Db(e)));
+        let mut queries = vec![];
+        let after = match rank::between(prev, next) {
+            Some(rank) => {
+                let after = Task {
+                    rank,
+                    ..before.clone()
+                };
+                queries.push(rank_query(&after)?);
+                after
+            }
+            None => {
+                tasks.insert(index, before.clone());
+                let ranks = rank::sequence(tasks.len());
+                for (task, rank) in tasks.iter_mut().zip(ranks) {
+                    if task.rank != rank {
+                        task.rank = rank;
+                        queries.push(rank_query(task)?);
+                    }
+                }
+                tasks.remove(index)
             }
         };
+        queries.extend(log.record(actor, TaskAction::Moved, Some(&before), Some(&after))?);
+        self.run_all(queries).await?;
 
-        W(response).try_into()
+        Ok(after)
     }
 
-    pub async fn get_all_tasks(&self) -> Result<Vec<Object>, crate::error::Error> {
-        let sql = "SELECT * FROM tasks ORDER BY created_at ASC;";
+    /// Flips whether a task is completed. Missing tasks affect no rows.
+    pub async fn toggle_task(
+        &self,
+        id: String,
+        actor: &str,
+    ) -> Result<AffectedRows, crate::error::Error> {
+        let toggle = |task: &Task| UpdateTask {
+            completed: Some(!task.completed),
+            ..Default::default()
+        };
+        match self
+            .change_task(id, TaskAction::Toggled, actor, toggle)
+            .await
+        {
+            Ok(_) => Ok(AffectedRows { affected_rows: 1 }),
+            Err(crate::error::Error::XRecordNotFound(_)) => Ok(AffectedRows { affected_rows: 0 }),
+            Err(e) => Err(e),
+        }
+    }
 
-        let ress = self.execute(sql, None).await?;
+    /// Updates a task. A new status must be one of the workflow's, and sets
+    /// whether the task is completed; see [`Workflow::settle`].
+    pub async fn update_task(
+        &self,
+        id: String,
+        update: UpdateTask,
+        actor: &str,
+    ) -> Result<Task, crate::error::Error> {
+        self.change_task(id, TaskAction::Updated, actor, |_| update.clone())
+            .await
+    }
 
-        let first_res = ress.into_iter().next().expect("no response");
+    /// Reads a task, works out the update with `change`, and writes it with
+    /// its history in one transaction. Toggling and the workflow decide from
+    /// whether the task is completed, so the write only goes ahead if that
+    /// is still as it was read; otherwise the change starts over from a
+    /// fresh read, up to [`CHANGE_ATTEMPTS`] times.
+    async fn change_task(
+        &self,
+        id: String,
+        action: TaskAction,
+        actor: &str,
+        change: impl Fn(&Task) -> UpdateTask,
+    ) -> Result<Task, crate::error::Error> {
+        for _ in 0..CHANGE_ATTEMPTS {
+            let before = self.get_task(id.clone()).await?;
+            let update = self
+                .get_workflow(before.workspace.as_deref())
+                .await?
+                .settle(&before, change(&before))?;
+            let after = update.apply(&before);
+            let log = self.change_log(before.workspace.as_deref(), action).await?;
 
-        let response = match first_res.result {
-            Ok(v) => v.first(),
-            Err(e) => {
-                return Err(crate::error::Error::Surreal(surrealdb::Error::Db(e)));
+            let target = Target::record(&before.id)?;
+            let unchanged = Query::select(target.clone()).where_eq("completed", before.completed);
+            let write = Query::update(target)
+                .merge(Value::try_from(W(update))?)
+                .returning(Return::After);
+            let record = log.record(actor, action, Some(&before), Some(&after))?;
+            let results = self
+                .transaction(|tx| async move {
+                    tx.bind("unchanged", unchanged);
+                    tx.add_if(UNCHANGED, write);
+                    for query in record {
+                        tx.add_if(UNCHANGED, query);
+                    }
+                    Ok(())
+                })
+                .await?;
+
+            // The write's result is NONE when the task changed, or was
+            // deleted, since it was read.
+            match results.get(1).map(Value::first) {
+                Some(Value::None) | None => continue,
+                Some(value) => return from_value(value),
             }
+        }
+        // Either the task is gone by now or it kept changing.
+        self.get_task(id.clone()).await?;
+        Err(crate::error::Error::XConflict(id))
+    }
+
+    /// Assigns a task to a user, or unassigns it with `None`. Tasks in a
+    /// workspace are only assigned to its members.
+    pub async fn assign_task(
+        &self,
+        id: String,
+        assignee: Option<String>,
+        actor: &str,
+    ) -> Result<Task, crate::error::Error> {
+        let before = self.get_task(id).await?;
+        if let (Some(workspace), Some(assignee)) = (&before.workspace, &assignee) {
+            if self.get_role(workspace, assignee).await?.is_none() {
+                return Err(todo_models::Error::AssigneeNotMember(assignee.clone()).into());
+            }
+        }
+        let after = Task {
+            assignee: assignee.clone(),
+            ..before.clone()
         };
+        let log = self
+            .change_log(before.workspace.as_deref(), TaskAction::Updated)
+            .await?;
 
-        let array: Array = W(response).try_into()?;
+        let mut queries = vec![Query::update(Target::record(&before.id)?)
+            .set("assignee", assignee.map_or(Value::None, Value::from))
+            .returning(Return::After)];
+        queries.extend(log.record(actor, TaskAction::Updated, Some(&before), Some(&after))?);
+        let results = self.run_all(queries).await?;
 
-        array.into_iter().map(|v| W(v).try_into()).collect()
+        match results.into_iter().next().map(|value| value.first()) {
+            Some(Value::None) | None => Err(crate::error::Error::XRecordNotFound(before.id)),
+            Some(value) => from_value(value),
+        }
     }
 
-    pub async fn toggle_task(&self, id: String) -> Result<AffectedRows, crate::error::Error> {
-        let sql = "UPDATE $th SET completed = function() { return !this.completed; }";
-        let tid = format!("{}", id);
-        let thing = match thing(&tid) {
-            Ok(v) => v,
-            Err(e) => {
-                return Err(crate::error::Error::Surreal(surrealdb::Error::Db(e)));
+    /// Who a task can be assigned to: the members of its workspace, or for
+    /// tasks outside any, the signed-in users who changed it. The current
+    /// assignee is always included.
+    pub async fn get_assignees(&self, id: String) -> Result<Vec<String>, crate::error::Error> {
+        let task = self.get_task(id.clone()).await?;
+        let mut users: Vec<String> = match &task.workspace {
+            Some(workspace) => {
+                let query = Query::select("members")
+                    .where_eq("workspace", workspace.clone())
+                    .order_by("joined_at", Order::Asc);
+                let members: Vec<Member> = self.fetch(query).await?;
+                members.into_iter().map(|member| member.user).collect()
             }
+            None => self
+                .get_task_history(id)
+                .await?
+                .into_iter()
+                .map(|event| event.actor)
+                .filter(|actor| actor != ANONYMOUS)
+                .collect(),
         };
-        let vars: BTreeMap<String, Value> = map!["th".into() => thing.into()];
-        let _ = self.execute(sql, Some(vars)).await?;
+        users.extend(task.assignee);
+        let mut seen = HashSet::new();
+        users.retain(|user| seen.insert(user.clone()));
+        Ok(users)
+    }
 
-        Ok(AffectedRows { affected_rows: 1 })
+    /// The statuses the tasks of a workspace, or those outside any with
+    /// `None`, move through, or the default ones if none were set.
+    pub async fn get_workflow(
+        &self,
+        workspace: Option<&str>,
+    ) -> Result<Workflow, crate::error::Error> {
+        let query = Query::select(workflow_target(workspace));
+        let record: Option<WorkflowRecord> = self.fetch_one(query).await?;
+        Ok(record.map_or_else(Workflow::default, |record| Workflow {
+            statuses: record.statuses,
+        }))
     }
 
-    pub async fn delete_task(&self, id: String) -> Result<AffectedRows, crate::error::Error> {
-        let sql = "DELETE FROM $th";
-        let tid = format!("{}", id);
-        let thing = match thing(&tid) {
-            Ok(v) => v,
-            Err(e) => {
-                return Err(crate::error::Error::Surreal(surrealdb::Error::Db(e)));
+    /// Replaces the statuses of a workspace, which only its owners may, or
+    /// those of the tasks outside any, which any signed-in user may. Tasks
+    /// in a status that was removed are shown in the first open or done one
+    /// until they are moved.
+    pub async fn set_workflow(
+        &self,
+        workflow: Workflow,
+        workspace: Option<String>,
+        user: &str,
+    ) -> Result<Workflow, crate::error::Error> {
+        if user == ANONYMOUS {
+            return Err(crate::error::Error::XAnonymous);
+        }
+        if let Some(workspace) = &workspace {
+            self.get_workspace(workspace.clone()).await?;
+        }
+        self.authorize(workspace.as_deref(), user, Role::Owner)
+            .await?;
+        let query = Query::update(workflow_target(workspace.as_deref()))
+            .set("statuses", to_value(&workflow.statuses)?)
+            .returning(Return::None);
+        let _ = self.run(query).await?;
+        Ok(workflow)
+    }
+
+    /// Deletes a task and its comments. Missing tasks affect no rows.
+    pub async fn delete_task(
+        &self,
+        id: String,
+        actor: &str,
+    ) -> Result<AffectedRows, crate::error::Error> {
+        let before = match self.get_task(id.clone()).await {
+            Ok(task) => task,
+            Err(crate::error::Error::XRecordNotFound(_)) => {
+                return Ok(AffectedRows { affected_rows: 0 })
             }
+            Err(e) => return Err(e),
         };
-        let vars: BTreeMap<String, Value> = map!["th".into() => thing.into()];
-        let _ = self.execute(sql, Some(vars)).await?;
+
+        let log = self
+            .change_log(before.workspace.as_deref(), TaskAction::Deleted)
+            .await?;
+
+        let mut queries = vec![
+            Query::delete(Target::record(&id)?),
+            Query::delete("comments").where_eq("task", id.clone()),
+        ];
+        queries.extend(log.record(actor, TaskAction::Deleted, Some(&before), None)?);
+        self.run_all(queries).await?;
 
         Ok(AffectedRows { affected_rows: 1 })
     }
+
+    /// What to record a kind of change to the tasks of a workspace, or
+    /// those outside any, with.
+    async fn change_log(
+        &self,
+        workspace: Option<&str>,
+        action: TaskAction,
+    ) -> Result<ChangeLog, crate::error::Error> {
+        let webhooks = self.get_subscriptions_to(workspace, action).await?;
+        Ok(ChangeLog { webhooks })
+    }
+
+    /// Every change made to a task, oldest first. Deleted tasks keep their
+    /// history.
+    pub async fn get_task_history(
+        &self,
+        id: String,
+    ) -> Result<Vec<TaskEvent>, crate::error::Error> {
+        let query = Query::select("task_events")
+            .where_eq("task", id)
+            .order_by("at", Order::Asc);
+        self.fetch(query).await
+    }
+
+    /// Creates a calendar feed of a workspace's tasks, or those outside any
+    /// with `None`, owned by `owner`, and returns its secret token.
+    pub async fn create_calendar_feed(
+        &self,
+        workspace: Option<String>,
+        owner: &str,
+    ) -> Result<String, crate::error::Error> {
+        if owner == ANONYMOUS {
+            return Err(crate::error::Error::XAnonymous);
+        }
+        let query = Query::create("calendar_feeds")
+            .set_expr("token", "rand::string(40)")
+            .set("owner", owner)
+            .set("workspace", workspace.map_or(Value::None, Value::from))
+            .set_expr("created_at", "time.now()");
+        let feed: FeedRecord = self
+            .fetch_one(query)
+            .await?
+            .ok_or(crate::error::Error::XNoResponse)?;
+        Ok(feed.token)
+    }
+
+    pub async fn get_calendar_feed(
+        &self,
+        token: String,
+    ) -> Result<Option<FeedRecord>, crate::error::Error> {
+        let query = Query::select("calendar_feeds")
+            .where_eq("token", token)
+            .limit(1);
+        self.fetch_one(query).await
+    }
+
+    /// Deletes one of `owner`'s calendar feeds. Missing feeds affect no
+    /// rows.
+    pub async fn delete_calendar_feed(
+        &self,
+        token: String,
+        owner: &str,
+    ) -> Result<AffectedRows, crate::error::Error> {
+        let Some(feed) = self.get_calendar_feed(token.clone()).await? else {
+            return Ok(AffectedRows { affected_rows: 0 });
+        };
+        if feed.owner != owner {
+            return Err(crate::error::Error::XForbidden(token));
+        }
+        let query = Query::delete("calendar_feeds")
+            .where_eq("token", token)
+            .returning(Return::Before);
+        let feeds: Vec<FeedRecord> = self.fetch(query).await?;
+        Ok(AffectedRows {
+            affected_rows: feeds.len() as u64,
+        })
+    }
+
+    /// Reads the bucket for a rate limit key, if one was stored.
+    pub async fn get_rate_limit(&self, key: String) -> Result<Option<Bucket>, crate::error::Error> {
+        let query = Query::select(Target::Key("rate_limits", key));
+        self.fetch_one(query).await
+    }
+
+    pub async fn set_rate_limit(
+        &self,
+        key: String,
+        bucket: Bucket,
+    ) -> Result<(), crate::error::Error> {
+        let query = Query::update(Target::Key("rate_limits", key))
+            .set("tokens", bucket.tokens)
+            .set("updated_at", bucket.updated_at)
+            .returning(Return::None);
+        let _ = self.run(query).await?;
+        Ok(())
+    }
+}
+
+/// How many times [`DB::change_task`] reads a task again after another
+/// change got in first.
+const CHANGE_ATTEMPTS: usize = 3;
+
+/// Holds while the task a change was read from is still as complete as it
+/// was; see [`DB::change_task`].
+const UNCHANGED: &str = "array::len($unchanged) > 0";
+
+/// A new record id for a task, so the task and its first event can be
+/// written in one transaction.
+fn new_task_id() -> String {
+    new_record_id("tasks")
 }
 
+/// A new random record id in `table`, for records written together with
+/// others that refer to them.
+pub fn new_record_id(table: &str) -> String {
+    Thing {
+        tb: table.to_string(),
+        id: Id::rand(),
+    }
+    .to_string()
+}
+
+fn insert_query(task: &Task) -> Result<Query, crate::error::Error> {
+    Ok(Query::create(Target::record(&task.id)?)
+        .set("title", task.title.clone())
+        .set("completed", task.completed)
+        .set("created_at", task.created_at)
+        .set("due", task.due.map_or(Value::None, Value::from))
+        .set(
+            "priority",
+            task.priority.map_or(Value::None, |p| i64::from(p).into()),
+        )
+        .set("rank", task.rank.clone())
+        .set("status", task.status.clone())
+        .set(
+            "workspace",
+            task.workspace.clone().map_or(Value::None, Value::from),
+        )
+        .set(
+            "assignee",
+            task.assignee.clone().map_or(Value::None, Value::from),
+        ))
+}
+
+/// The tasks of a workspace, or those outside any with `None`, in list
+/// order.
+fn tasks_query(workspace: Option<&str>) -> Query {
+    Query::select("tasks")
+        .where_eq("workspace", workspace.map_or(Value::None, Value::from))
+        .order_by("rank", Order::Asc)
+        .order_by("created_at", Order::Asc)
+}
+
+fn rank_query(task: &Task) -> Result<Query, crate::error::Error> {
+    Ok(Query::update(Target::record(&task.id)?)
+        .set("rank", task.rank.clone())
+        .returning(Return::None))
+}
+
+/// Records changes to tasks, in the transaction making them: each as a
+/// [`TaskEvent`], and as a delivery queued for every webhook of the task's
+/// workspace subscribed to it. A log is made for one kind of change in one
+/// workspace by [`DB::change_log`], which only loads those webhooks.
+pub struct ChangeLog {
+    webhooks: Vec<Subscription>,
+}
+
+impl ChangeLog {
+    /// The queries recording a change. `before` and `after` are as in
+    /// [`diff`].
+    fn record(
+        &self,
+        actor: &str,
+        action: TaskAction,
+        before: Option<&Task>,
+        after: Option<&Task>,
+    ) -> Result<Vec<Query>, crate::error::Error> {
+        let mut queries = vec![event_query(actor, action, before, after)?];
+        let Some(task) = after.or(before) else {
+            return Ok(queries);
+        };
+        if self.webhooks.is_empty() {
+            return Ok(queries);
+        }
+        let payload = WebhookPayload {
+            event: action,
+            actor: actor.to_string(),
+            at: Utc::now(),
+            task: task.clone(),
+            changes: diff(before, after),
+        };
+        let payload = rocket::serde::json::to_string(&payload)
+            .map_err(|e| crate::error::Error::XConversion(e.to_string()))?;
+        for webhook in &self.webhooks {
+            queries.push(delivery_query(&webhook.id, action, &payload)?);
+        }
+        Ok(queries)
+    }
+}
+
+/// Records a change to a task. `before` and `after` are as in [`diff`].
+fn event_query(
+    actor: &str,
+    action: TaskAction,
+    before: Option<&Task>,
+    after: Option<&Task>,
+) -> Result<Query, crate::error::Error> {
+    let task = before.or(after);
+    // NULL rather than NONE for tasks outside any workspace, so events made
+    // before the workspace was recorded can be told apart.
+    let workspace = task.and_then(|t| t.workspace.clone());
+    Ok(Query::create("task_events")
+        .set("task", task.map_or_else(String::new, |t| t.id.clone()))
+        .set("workspace", workspace.map_or(Value::Null, Value::from))
+        .set("actor", actor)
+        .set("action", to_value(&action)?)
+        .set("at", Utc::now())
+        .set("changes", to_value(&diff(before, after))?))
+}
+
+/// Where a workspace's workflow is stored. The one for tasks outside any
+/// workspace stays where the single workflow was kept before workspaces had
+/// their own.
+fn workflow_target(workspace: Option<&str>) -> Target {
+    match workspace {
+        Some(workspace) => Target::Key("workflows", workspace.to_string()),
+        None => Target::Key("settings", "workflow".to_string()),
+    }
+}
+
+/// A stored workflow.
+#[derive(Deserialize)]
+struct WorkflowRecord {
+    statuses: Vec<Status>,
+}
+
+/// The stored fields of a calendar feed that the API reads back.
+#[derive(Deserialize)]
+pub struct FeedRecord {
+    pub token: String,
+    /// Who made the feed. Feeds made before feeds had owners were made
+    /// anonymously.
+    #[serde(default = "anonymous")]
+    pub owner: String,
+    /// The workspace whose tasks are served, or `None` for those outside
+    /// any.
+    #[serde(default)]
+    pub workspace: Option<String>,
+}
+
+fn anonymous() -> String {
+    ANONYMOUS.to_string()
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use surrealdb::kvs::Datastore;
-    use surrealdb::sql::Value;
+    use std::sync::Arc;
     use surrealdb::dbs::Session;
     use surrealdb::err::Error;
-    use std::sync::Arc;
-
+    use surrealdb::kvs::Datastore;
 
     #[tokio::test]
     async fn test_add_task() -> Result<(), Error> {
@@ -179,12 +807,10 @@ mod tests {
         let db = DB { ds, sesh };
 
         let title = "test".to_string();
-        let res = db.add_task(title).await.unwrap();
-        let id = res.get("id").unwrap();
-        let id = id.to_string();
-        let res = db.get_task(id).await.unwrap();
-        let title = res.get("title").unwrap().to_string();
-        assert_eq!(title, "test");
+        let res = db.add_task(title, ANONYMOUS).await.unwrap();
+        let res = db.get_task(res.id).await.unwrap();
+        assert_eq!(res.title, "test");
+        assert!(!res.completed);
         Ok(())
     }
 
@@ -195,39 +821,402 @@ mod tests {
         let db = DB { ds, sesh };
 
         let title = "test".to_string();
-        let res = db.add_task(title).await.unwrap();
-        let id = res.get("id").unwrap();
-        let id = id.to_string();
+        let res = db.add_task(title, ANONYMOUS).await.unwrap();
+        let id = res.id;
         let res = db.get_all_tasks().await.unwrap();
-        let title = res[0].get("title").unwrap().to_string();
-        assert_eq!(title, "test");
-    }
-
-    // #[tokio::test]
-    // async fn test_toggle_task() {
-    //     let ds = Arc::new(Datastore::new());
-    //     let sesh = Session::new();
-    //     let db = DB { ds, sesh };
-
-    //     let title = "test".to_string();
-    //     let res = db.add_task(title).await.unwrap();
-    //     let id = res.get("id").unwrap().as_str().unwrap();
-    //     let id = id.to_string();
-    //     let res = db.toggle_task(id).await.unwrap();
-    //     assert_eq!(res.affected_rows, 1);
-    // }
-
-    // #[tokio::test]
-    // async fn test_delete_task() {
-    //     let ds = Arc::new(Datastore::new());
-    //     let sesh = Session::new();
-    //     let db = DB { ds, sesh };
-
-    //     let title = "test".to_string();
-    //     let res = db.add_task(title).await.unwrap();
-    //     let id = res.get("id").unwrap().as_str().unwrap();
-    //     let id = id.to_string();
-    //     let res = db.delete_task(id).await.unwrap();
-    //     assert_eq!(res.affected_rows, 1);
-    // }
-}
\ No newline at end of file
+        assert_eq!(res[0].id, id);
+        assert_eq!(res[0].title, "test");
+    }
+
+    #[tokio::test]
+    async fn test_update_task() {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        let db = DB { ds, sesh };
+
+        let res = db.add_task("test".to_string(), ANONYMOUS).await.unwrap();
+        let update = UpdateTask {
+            title: Some("renamed".to_string()),
+            completed: Some(true),
+            ..Default::default()
+        };
+        let res = db.update_task(res.id, update, ANONYMOUS).await.unwrap();
+        assert_eq!(res.title, "renamed");
+        assert!(res.completed);
+
+        let res = db.get_task(res.id).await.unwrap();
+        assert_eq!(res.title, "renamed");
+
+        let res = db
+            .update_task(
+                "tasks:missing".to_string(),
+                UpdateTask::default(),
+                ANONYMOUS,
+            )
+            .await;
+        assert!(matches!(res, Err(crate::error::Error::XRecordNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_calendar_feed() {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        let db = DB { ds, sesh };
+
+        let res = db.create_calendar_feed(None, ANONYMOUS).await;
+        assert!(matches!(res, Err(crate::error::Error::XAnonymous)));
+        let workspace = Some("workspaces:a".to_string());
+        let token = db
+            .create_calendar_feed(workspace.clone(), "user:a")
+            .await
+            .unwrap();
+        assert_eq!(token.len(), 40);
+        let feed = db.get_calendar_feed(token.clone()).await.unwrap().unwrap();
+        assert_eq!(feed.owner, "user:a");
+        assert_eq!(feed.workspace, workspace);
+        let res = db.get_calendar_feed("guess".to_string()).await.unwrap();
+        assert!(res.is_none());
+
+        let res = db.delete_calendar_feed(token.clone(), "user:b").await;
+        assert!(matches!(res, Err(crate::error::Error::XForbidden(_))));
+        let res = db
+            .delete_calendar_feed(token.clone(), "user:a")
+            .await
+            .unwrap();
+        assert_eq!(res.affected_rows, 1);
+        let res = db.get_calendar_feed(token).await.unwrap();
+        assert!(res.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        let db = DB { ds, sesh };
+
+        let key = "write:ip:127.0.0.1".to_string();
+        assert_eq!(db.get_rate_limit(key.clone()).await.unwrap(), None);
+
+        let bucket = Bucket {
+            tokens: 2.5,
+            updated_at: 1_680_000_000_000,
+        };
+        db.set_rate_limit(key.clone(), bucket).await.unwrap();
+        assert_eq!(db.get_rate_limit(key).await.unwrap(), Some(bucket));
+    }
+
+    #[tokio::test]
+    async fn test_task_history() {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        let db = DB { ds, sesh };
+
+        let task = db.add_task("test".to_string(), "user:ada").await.unwrap();
+        let update = UpdateTask {
+            title: Some("renamed".to_string()),
+            ..Default::default()
+        };
+        db.update_task(task.id.clone(), update, ANONYMOUS)
+            .await
+            .unwrap();
+        db.toggle_task(task.id.clone(), ANONYMOUS).await.unwrap();
+        db.delete_task(task.id.clone(), ANONYMOUS).await.unwrap();
+
+        let history = db.get_task_history(task.id.clone()).await.unwrap();
+        let actions: Vec<TaskAction> = history.iter().map(|e| e.action).collect();
+        assert_eq!(
+            actions,
+            [
+                TaskAction::Created,
+                TaskAction::Updated,
+                TaskAction::Toggled,
+                TaskAction::Deleted
+            ]
+        );
+        assert!(history.iter().all(|e| e.task == task.id));
+        assert_eq!(history[0].actor, "user:ada");
+        assert_eq!(
+            history[1].changes,
+            [todo_models::FieldChange {
+                field: "title".to_string(),
+                from: Some("test".to_string()),
+                to: Some("renamed".to_string()),
+            }]
+        );
+
+        let res = db.toggle_task(task.id.clone(), ANONYMOUS).await.unwrap();
+        assert_eq!(res.affected_rows, 0);
+        assert_eq!(db.get_task_history(task.id).await.unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_assign_task() {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        let db = DB { ds, sesh };
+
+        let task = db.add_task("test".to_string(), "user:ada").await.unwrap();
+        db.add_task("other".to_string(), ANONYMOUS).await.unwrap();
+        let assigned = db
+            .assign_task(task.id.clone(), Some("user:bob".to_string()), ANONYMOUS)
+            .await
+            .unwrap();
+        assert_eq!(assigned.assignee.as_deref(), Some("user:bob"));
+
+        let mine = db.get_assigned_tasks(None, "user:bob").await.unwrap();
+        assert_eq!(mine, [assigned]);
+        assert!(db
+            .get_assigned_tasks(None, "user:ada")
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            db.get_assignees(task.id.clone()).await.unwrap(),
+            ["user:ada", "user:bob"]
+        );
+
+        let unassigned = db
+            .assign_task(task.id.clone(), None, "user:bob")
+            .await
+            .unwrap();
+        assert_eq!(unassigned.assignee, None);
+        let history = db.get_task_history(task.id.clone()).await.unwrap();
+        assert_eq!(
+            history[2].changes,
+            [todo_models::FieldChange {
+                field: "assignee".to_string(),
+                from: Some("user:bob".to_string()),
+                to: None,
+            }]
+        );
+
+        let res = db
+            .assign_task("tasks:missing".to_string(), None, ANONYMOUS)
+            .await;
+        assert!(matches!(res, Err(crate::error::Error::XRecordNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_task_status() {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        let db = DB { ds, sesh };
+
+        let task = db.add_task("test".to_string(), ANONYMOUS).await.unwrap();
+        assert_eq!(task.status, "todo");
+
+        let update = UpdateTask {
+            status: Some("review".to_string()),
+            ..Default::default()
+        };
+        let task = db.update_task(task.id, update, ANONYMOUS).await.unwrap();
+        assert_eq!(task.status, "review");
+        assert!(!task.completed);
+
+        // Toggling still completes and reopens the task.
+        db.toggle_task(task.id.clone(), ANONYMOUS).await.unwrap();
+        let task = db.get_task(task.id).await.unwrap();
+        assert_eq!(task.status, "done");
+        assert!(task.completed);
+        db.toggle_task(task.id.clone(), ANONYMOUS).await.unwrap();
+        let task = db.get_task(task.id).await.unwrap();
+        assert_eq!(task.status, "todo");
+
+        let update = UpdateTask {
+            status: Some("archived".to_string()),
+            ..Default::default()
+        };
+        let res = db.update_task(task.id, update, ANONYMOUS).await;
+        assert!(matches!(res, Err(crate::error::Error::Invalid(_))));
+    }
+
+    #[tokio::test]
+    async fn test_workflow() {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        let db = DB { ds, sesh };
+
+        assert_eq!(db.get_workflow(None).await.unwrap(), Workflow::default());
+
+        let mut workflow = Workflow::default();
+        workflow.statuses.remove(2);
+        let res = db.set_workflow(workflow.clone(), None, ANONYMOUS).await;
+        assert!(matches!(res, Err(crate::error::Error::XAnonymous)));
+        db.set_workflow(workflow.clone(), None, "user:ada")
+            .await
+            .unwrap();
+        assert_eq!(db.get_workflow(None).await.unwrap(), workflow);
+
+        // Each workspace has its own, which only its owners change.
+        let workspace = db
+            .create_workspace(
+                todo_models::NewWorkspace {
+                    name: "Team".to_string(),
+                },
+                "user:ada",
+            )
+            .await
+            .unwrap();
+        let ws = Some(workspace.id.as_str());
+        assert_eq!(db.get_workflow(ws).await.unwrap(), Workflow::default());
+        let res = db
+            .set_workflow(workflow.clone(), Some(workspace.id.clone()), "user:bob")
+            .await;
+        assert!(matches!(
+            res,
+            Err(crate::error::Error::XRoleRequired(Role::Owner))
+        ));
+        let mut own = Workflow::default();
+        own.statuses.remove(1);
+        db.set_workflow(own.clone(), Some(workspace.id.clone()), "user:ada")
+            .await
+            .unwrap();
+        assert_eq!(db.get_workflow(ws).await.unwrap(), own);
+        assert_eq!(db.get_workflow(None).await.unwrap(), workflow);
+
+        let res = db
+            .set_workflow(workflow, Some("workspaces:missing".to_string()), "user:ada")
+            .await;
+        assert!(matches!(res, Err(crate::error::Error::XRecordNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_move_task() {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        let db = DB { ds, sesh };
+
+        let mut ids = vec![];
+        for title in ["a", "b", "c"] {
+            let task = db.add_task(title.to_string(), ANONYMOUS).await.unwrap();
+            ids.push(task.id);
+        }
+        let titles = |tasks: Vec<Task>| -> Vec<String> {
+            tasks.into_iter().map(|task| task.title).collect()
+        };
+
+        let moved = db
+            .move_task(ids[2].clone(), MoveTask::Before(ids[0].clone()), ANONYMOUS)
+            .await
+            .unwrap();
+        assert_eq!(moved.title, "c");
+        let tasks = db.get_all_tasks().await.unwrap();
+        assert_eq!(titles(tasks), ["c", "a", "b"]);
+
+        db.move_task(ids[2].clone(), MoveTask::After(ids[1].clone()), ANONYMOUS)
+            .await
+            .unwrap();
+        let tasks = db.get_all_tasks().await.unwrap();
+        assert_eq!(titles(tasks), ["a", "b", "c"]);
+
+        let history = db.get_task_history(ids[2].clone()).await.unwrap();
+        assert_eq!(history.last().unwrap().action, TaskAction::Moved);
+
+        let res = db
+            .move_task(
+                ids[0].clone(),
+                MoveTask::After("tasks:missing".to_string()),
+                ANONYMOUS,
+            )
+            .await;
+        assert!(matches!(res, Err(crate::error::Error::XRecordNotFound(_))));
+
+        // Tasks only move within their own list.
+        let other = db
+            .add_task_in(
+                "other".to_string(),
+                Some("workspaces:a".to_string()),
+                ANONYMOUS,
+            )
+            .await
+            .unwrap();
+        let res = db
+            .move_task(ids[0].clone(), MoveTask::After(other.id.clone()), ANONYMOUS)
+            .await;
+        assert!(matches!(res, Err(crate::error::Error::XRecordNotFound(_))));
+        assert_eq!(db.get_task(other.id).await.unwrap().rank, other.rank);
+    }
+
+    #[tokio::test]
+    async fn test_move_task_ranks_unranked_tasks() {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        let db = DB { ds, sesh };
+
+        // Tasks stored before ranks existed have none.
+        for title in ["a", "b", "c"] {
+            db.run(
+                Query::create("tasks")
+                    .set("title", title)
+                    .set("completed", false)
+                    .set("created_at", Utc::now()),
+            )
+            .await
+            .unwrap();
+        }
+        let tasks = db.get_all_tasks().await.unwrap();
+        assert!(tasks.iter().all(|task| task.rank.is_empty()));
+
+        db.move_task(
+            tasks[0].id.clone(),
+            MoveTask::After(tasks[1].id.clone()),
+            ANONYMOUS,
+        )
+        .await
+        .unwrap();
+        let tasks = db.get_all_tasks().await.unwrap();
+        let titles: Vec<&str> = tasks.iter().map(|task| task.title.as_str()).collect();
+        assert_eq!(titles, ["b", "a", "c"]);
+        assert!(tasks.iter().all(|task| !task.rank.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_toggle_task() {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        let db = DB { ds, sesh };
+
+        let task = db.add_task("test".to_string(), ANONYMOUS).await.unwrap();
+        let res = db.toggle_task(task.id.clone(), ANONYMOUS).await.unwrap();
+        assert_eq!(res.affected_rows, 1);
+        assert!(db.get_task(task.id).await.unwrap().completed);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_toggles_both_apply() {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        let db = DB { ds, sesh };
+
+        let task = db.add_task("test".to_string(), ANONYMOUS).await.unwrap();
+        let (first, second) = tokio::join!(
+            db.toggle_task(task.id.clone(), ANONYMOUS),
+            db.toggle_task(task.id.clone(), ANONYMOUS)
+        );
+        assert_eq!(first.unwrap().affected_rows, 1);
+        assert_eq!(second.unwrap().affected_rows, 1);
+
+        // Toggled twice, with a matching event for each.
+        assert!(!db.get_task(task.id.clone()).await.unwrap().completed);
+        let history = db.get_task_history(task.id).await.unwrap();
+        let toggles: Vec<&TaskEvent> = history
+            .iter()
+            .filter(|event| event.action == TaskAction::Toggled)
+            .collect();
+        assert_eq!(toggles.len(), 2);
+        assert_ne!(toggles[0].changes, toggles[1].changes);
+    }
+
+    #[tokio::test]
+    async fn test_delete_task() {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        let db = DB { ds, sesh };
+
+        let task = db.add_task("test".to_string(), ANONYMOUS).await.unwrap();
+        let res = db.delete_task(task.id.clone(), ANONYMOUS).await.unwrap();
+        assert_eq!(res.affected_rows, 1);
+        let res = db.delete_task(task.id, ANONYMOUS).await.unwrap();
+        assert_eq!(res.affected_rows, 0);
+    }
+}
@@ -3,6 +3,45 @@ pub enum Error {
     #[error("value not of type '{0}'")]
     XValueNotOfType(&'static str),
 
+    #[error("property '{0}' not found")]
+    XPropertyNotFound(&'static str),
+
+    #[error("record '{0}' not found")]
+    XRecordNotFound(String),
+
+    #[error("record '{0}' belongs to someone else")]
+    XForbidden(String),
+
+    #[error("this needs the {0} role in the workspace")]
+    XRoleRequired(todo_models::Role),
+
+    #[error("only signed-in users can do this; send a bearer token")]
+    XAnonymous,
+
+    #[error("record '{0}' kept changing while it was written; try again")]
+    XConflict(String),
+
+    #[error("a workspace must keep at least one owner")]
+    XLastOwner,
+
+    #[error("query returned no response")]
+    XNoResponse,
+
+    #[error("value conversion failed: {0}")]
+    XConversion(String),
+
+    #[error("storage failed: {0}")]
+    Storage(String),
+
+    #[error("notification failed: {0}")]
+    Notify(String),
+
+    #[error("invalid import: {0}")]
+    InvalidImport(String),
+
+    #[error(transparent)]
+    Invalid(#[from] todo_models::Error),
+
     #[error(transparent)]
     Surreal(#[from] surrealdb::Error),
 
@@ -0,0 +1,273 @@
+//! Minimal iCalendar (RFC 5545) support: tasks are written as VTODO
+//! components, and VTODOs are read back into importable records.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use todo_models::TaskRecord;
+
+use crate::db::Task;
+use crate::transfer::Row;
+
+const DATETIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+pub fn header() -> String {
+    "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//full_stack_rust//todo_api//EN\r\n".to_string()
+}
+
+pub fn footer() -> String {
+    "END:VCALENDAR\r\n".to_string()
+}
+
+/// Renders one task as a VTODO. `stamp` is the time the feed was generated.
+pub fn todo(task: &Task, stamp: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VTODO".to_string(),
+        format!("UID:{}", escape(&task.id)),
+        format!("DTSTAMP:{}", stamp.format(DATETIME_FORMAT)),
+        format!("CREATED:{}", task.created_at.format(DATETIME_FORMAT)),
+        format!("SUMMARY:{}", escape(&task.title)),
+        format!(
+            "STATUS:{}",
+            if task.completed {
+                "COMPLETED"
+            } else {
+                "NEEDS-ACTION"
+            }
+        ),
+    ];
+    if let Some(due) = task.due {
+        lines.push(format!("DUE:{}", due.format(DATETIME_FORMAT)));
+    }
+    if let Some(priority) = task.priority {
+        lines.push(format!("PRIORITY:{priority}"));
+    }
+    lines.push("END:VTODO".to_string());
+
+    lines.iter().map(|line| fold(line)).collect()
+}
+
+/// Reads every VTODO in `input`, keyed by the line of its `BEGIN:VTODO`.
+/// Other components, such as VEVENTs, are ignored.
+pub fn parse(input: &str) -> Vec<Row> {
+    let mut rows = vec![];
+    let mut todo: Option<(usize, Vec<Property>)> = None;
+
+    for (line_no, line) in unfold(input) {
+        let Some(property) = Property::parse(&line) else {
+            continue;
+        };
+        match (&mut todo, property.name.as_str()) {
+            (None, "BEGIN") if property.value.eq_ignore_ascii_case("VTODO") => {
+                todo = Some((line_no, vec![]));
+            }
+            (Some(_), "END") if property.value.eq_ignore_ascii_case("VTODO") => {
+                let (start, properties) = todo.take().expect("inside a VTODO");
+                rows.push((start, record(&properties)));
+            }
+            (Some((_, properties)), _) => properties.push(property),
+            (None, _) => {}
+        }
+    }
+
+    if let Some((start, _)) = todo {
+        rows.push((start, Err("VTODO is not closed".to_string())));
+    }
+    rows
+}
+
+struct Property {
+    name: String,
+    value: String,
+}
+
+impl Property {
+    /// Splits `NAME;PARAM=x:VALUE`, ignoring colons inside quoted params.
+    /// Parameters are dropped, since values are parsed by their shape.
+    fn parse(line: &str) -> Option<Property> {
+        let mut quoted = false;
+        let colon = line.char_indices().find_map(|(i, c)| match c {
+            '"' => {
+                quoted = !quoted;
+                None
+            }
+            ':' if !quoted => Some(i),
+            _ => None,
+        })?;
+
+        let name = line[..colon].split(';').next()?;
+        Some(Property {
+            name: name.trim().to_ascii_uppercase(),
+            value: line[colon + 1..].to_string(),
+        })
+    }
+}
+
+fn record(properties: &[Property]) -> Result<TaskRecord, String> {
+    let find = |name: &str| properties.iter().find(|p| p.name == name);
+
+    let title = find("SUMMARY")
+        .map(|p| unescape(&p.value))
+        .ok_or("missing SUMMARY")?;
+    let completed = match find("STATUS") {
+        Some(status) => status.value.eq_ignore_ascii_case("COMPLETED"),
+        None => find("COMPLETED").is_some(),
+    };
+    let priority = match find("PRIORITY") {
+        Some(p) => match p.value.trim().parse::<u8>() {
+            // 0 means the priority is undefined.
+            Ok(0) => None,
+            Ok(priority) if priority <= 9 => Some(priority),
+            _ => return Err(format!("invalid PRIORITY '{}'", p.value)),
+        },
+        None => None,
+    };
+
+    Ok(TaskRecord {
+        title,
+        completed,
+        created_at: find("CREATED").map(parse_datetime).transpose()?,
+        due: find("DUE").map(parse_datetime).transpose()?,
+        priority,
+    })
+}
+
+/// Accepts UTC date-times, dates, and floating or zoned date-times. Time
+/// zones are not resolved, so the latter two are read as UTC.
+fn parse_datetime(property: &Property) -> Result<DateTime<Utc>, String> {
+    let value = property.value.trim();
+    let value = value.strip_suffix('Z').unwrap_or(value);
+
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        return Ok(Utc.from_utc_datetime(&datetime));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y%m%d") {
+        let midnight = date.and_hms_opt(0, 0, 0).expect("midnight is valid");
+        return Ok(Utc.from_utc_datetime(&midnight));
+    }
+    Err(format!("invalid {} '{}'", property.name, property.value))
+}
+
+/// Joins continuation lines and returns each logical line with the number
+/// of the physical line it starts on.
+fn unfold(input: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = vec![];
+    for (i, line) in input.lines().enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some((_, last))) => last.push_str(rest),
+            _ if line.is_empty() => {}
+            _ => lines.push((i + 1, line.to_string())),
+        }
+    }
+    lines
+}
+
+/// Splits lines longer than 75 octets, as required by RFC 5545.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => out.push('\n'),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task() -> Task {
+        Task {
+            id: "tasks:abc".to_string(),
+            title: "call mom, then dad; \\ maybe".to_string(),
+            completed: false,
+            created_at: "2023-04-02T10:30:00Z".parse().unwrap(),
+            due: Some("2023-04-05T17:00:00Z".parse().unwrap()),
+            priority: Some(1),
+            remind_at: None,
+            rank: "i".to_string(),
+            status: "todo".to_string(),
+            workspace: None,
+            assignee: None,
+        }
+    }
+
+    #[test]
+    fn test_todo_round_trip() {
+        let stamp = "2023-04-03T00:00:00Z".parse().unwrap();
+        let calendar = [header(), todo(&task(), stamp), footer()].concat();
+        assert!(calendar.contains("SUMMARY:call mom\\, then dad\\; \\\\ maybe\r\n"));
+        assert!(calendar.contains("DUE:20230405T170000Z\r\n"));
+
+        let rows = parse(&calendar);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].0, 4);
+        let record = rows[0].1.clone().unwrap();
+        assert_eq!(record.title, task().title);
+        assert!(!record.completed);
+        assert_eq!(record.created_at, Some(task().created_at));
+        assert_eq!(record.due, task().due);
+        assert_eq!(record.priority, Some(1));
+    }
+
+    #[test]
+    fn test_long_lines_are_folded() {
+        let mut task = task();
+        task.title = "é".repeat(60);
+        let stamp = Utc::now();
+        let rendered = todo(&task, stamp);
+        assert!(rendered.split("\r\n").all(|line| line.len() <= 75));
+
+        let rows = parse(&rendered);
+        assert_eq!(rows[0].1.as_ref().unwrap().title, task.title);
+    }
+
+    #[test]
+    fn test_parse_foreign_vtodo() {
+        let input = "BEGIN:VCALENDAR\n\
+                     BEGIN:VEVENT\nSUMMARY:not a task\nEND:VEVENT\n\
+                     BEGIN:VTODO\nSUMMARY:pay rent\nDUE;VALUE=DATE:20230501\n\
+                     PRIORITY:0\nCOMPLETED:20230430T120000Z\nEND:VTODO\n\
+                     BEGIN:VTODO\nDUE;TZID=\"Europe/Berlin\":20230501T090000\nEND:VTODO\n\
+                     END:VCALENDAR\n";
+        let rows = parse(input);
+        assert_eq!(rows.len(), 2);
+
+        let record = rows[0].1.clone().unwrap();
+        assert_eq!(record.title, "pay rent");
+        assert!(record.completed);
+        assert_eq!(record.priority, None);
+        assert_eq!(record.due, Some("2023-05-01T00:00:00Z".parse().unwrap()));
+
+        assert_eq!(rows[1], (11, Err("missing SUMMARY".to_string())));
+    }
+}
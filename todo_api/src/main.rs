@@ -1,77 +1,945 @@
+// Rocket's `FromForm` derive still allows the removed `private_in_public`
+// lint, which newer compilers warn about.
+#![allow(renamed_and_removed_lints)]
+
 #[macro_use]
 extern crate rocket;
 
-use rocket::{serde::json::Json, State};
+use rocket::{
+    data::Capped, figment::Figment, form::Form, fs::TempFile, serde::json::Json, Build, Rocket,
+    State,
+};
 
-use std::{io::ErrorKind, sync::Arc};
-use surrealdb::{dbs::Session, kvs::Datastore, sql::Object};
+use std::sync::Arc;
+use surrealdb::{dbs::Session, kvs::Datastore};
 
-use crate::db::{AffectedRows, DB};
+use crate::actor::{Actor, AdminScope, ReadScope, WriteScope};
+use crate::attachments::{Attachment, Download, NewAttachment};
+use crate::comments::{Comment, EditComment, NewComment};
+use crate::db::{AffectedRows, Assignment, MoveTask, NewTask, Task, TaskEvent, UpdateTask, DB};
+use crate::error::Error;
+use crate::notifications::Notification;
+use crate::tokens::{ApiToken, IssuedToken, NewApiToken};
+use crate::transfer::{Export, Format};
+use crate::webhooks::{NewWebhook, Webhook, WebhookDelivery};
+use crate::workspaces::{denied, Invitation, Member, NewWorkspace, Role, SetRole, Workspace};
+use todo_models::{CalendarFeed, ImportReport, ValidationErrors, Workflow};
 
 use cors::*;
+use rate_limit::{RateLimit, RateLimiter};
+use reminders::Reminders;
+use storage::{FileStorage, Files};
+use validation::Rejection;
+use webhooks::Webhooks;
 
+mod actor;
+mod attachments;
+mod comments;
 mod cors;
 mod db;
 mod error;
+mod ical;
+mod notifications;
 mod prelude;
+mod query;
+mod rate_limit;
+mod reminders;
+mod storage;
+#[cfg(test)]
+mod tests;
+mod tokens;
+mod transaction;
+mod transfer;
 mod utils;
+mod validation;
+mod webhooks;
+mod workspaces;
 
-#[post("/task/<title>")]
-async fn add_task(title: String, db: &State<DB>) -> Result<Json<Object>, std::io::Error> {
+/// Adds a task, to the workspace given if any. Only its editors and owners
+/// may.
+#[post("/task/<title>?<workspace>")]
+async fn add_task(
+    title: String,
+    workspace: Option<String>,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Json<Task>, Rejection> {
+    let new_task = NewTask::new(title).sanitize()?;
+    db.authorize(workspace.as_deref(), &actor.0, Role::Editor)
+        .await
+        .map_err(denied)?;
     let task = db
-        .add_task(title)
+        .add_task_in(new_task.title, workspace, &actor.0)
         .await
-        .map_err(|_| std::io::Error::new(ErrorKind::Other, "Error adding task"))?;
+        .map_err(|_| std::io::Error::other("Error adding task"))?;
     Ok(Json(task))
 }
 
 #[get("/task/<id>")]
-async fn get_task(id: String, db: &State<DB>) -> Result<Json<Object>, std::io::Error> {
-    let task = db
-        .get_task(id)
+async fn get_task(
+    id: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<ReadScope>,
+) -> Result<Option<Json<Task>>, Rejection> {
+    db.authorize_task(&id, &actor.0, Role::Viewer)
         .await
-        .map_err(|_| std::io::Error::new(ErrorKind::Other, "Error getting task"))?;
-    Ok(Json(task))
+        .map_err(denied)?;
+    match db.get_task(id).await {
+        Ok(task) => Ok(Some(Json(task))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(_) => Err(std::io::Error::other("Error getting task").into()),
+    }
 }
 
-#[get("/tasks")]
-async fn get_tasks(db: &State<DB>) -> Result<Json<Vec<Object>>, std::io::Error> {
-    let tasks = db
-        .get_all_tasks()
+/// The tasks of the workspace given, or those outside any workspace. With
+/// `mine=true`, only the ones assigned to the caller.
+#[get("/tasks?<workspace>&<mine>")]
+async fn get_tasks(
+    workspace: Option<String>,
+    mine: Option<bool>,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<ReadScope>,
+) -> Result<Json<Vec<Task>>, Rejection> {
+    db.authorize(workspace.as_deref(), &actor.0, Role::Viewer)
         .await
-        .map_err(|_| std::io::Error::new(ErrorKind::Other, "Error getting tasks"))?;
+        .map_err(denied)?;
+    let tasks = match mine.unwrap_or(false) {
+        true => db.get_assigned_tasks(workspace.as_deref(), &actor.0).await,
+        false => db.get_tasks_in(workspace.as_deref()).await,
+    }
+    .map_err(|_| std::io::Error::other("Error getting tasks"))?;
     Ok(Json(tasks))
 }
 
+#[put("/task/<id>", data = "<update>")]
+async fn update_task(
+    id: String,
+    update: Json<UpdateTask>,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Option<Json<Task>>, Rejection> {
+    let update = update.into_inner().sanitize()?;
+    db.authorize_task(&id, &actor.0, Role::Editor)
+        .await
+        .map_err(denied)?;
+    match db.update_task(id, update, &actor.0).await {
+        Ok(task) => Ok(Some(Json(task))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(Error::Invalid(e)) => Err(ValidationErrors::from(e).into()),
+        Err(e @ Error::XConflict(_)) => Err(Rejection::Conflict(e.to_string())),
+        Err(_) => Err(std::io::Error::other("Error updating task").into()),
+    }
+}
+
+/// Moves a task to another status of the workflow. Unknown statuses are
+/// `422 Unprocessable Entity`.
+#[put("/task/<id>/status/<status>")]
+async fn set_task_status(
+    id: String,
+    status: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Option<Json<Task>>, Rejection> {
+    let update = UpdateTask {
+        status: Some(status),
+        ..Default::default()
+    };
+    db.authorize_task(&id, &actor.0, Role::Editor)
+        .await
+        .map_err(denied)?;
+    match db.update_task(id, update, &actor.0).await {
+        Ok(task) => Ok(Some(Json(task))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(Error::Invalid(e)) => Err(ValidationErrors::from(e).into()),
+        Err(e @ Error::XConflict(_)) => Err(Rejection::Conflict(e.to_string())),
+        Err(_) => Err(std::io::Error::other("Error updating task status").into()),
+    }
+}
+
+/// The statuses of the workspace given, or of the tasks outside any.
+#[get("/statuses?<workspace>")]
+async fn get_statuses(
+    workspace: Option<String>,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<ReadScope>,
+) -> Result<Json<Workflow>, Rejection> {
+    db.authorize(workspace.as_deref(), &actor.0, Role::Viewer)
+        .await
+        .map_err(denied)?;
+    let workflow = db
+        .get_workflow(workspace.as_deref())
+        .await
+        .map_err(|_| std::io::Error::other("Error getting statuses"))?;
+    Ok(Json(workflow))
+}
+
+/// Replaces the statuses of the workspace given, which only its owners
+/// may, or of the tasks outside any.
+#[put("/statuses?<workspace>", data = "<workflow>")]
+async fn set_statuses(
+    workspace: Option<String>,
+    workflow: Json<Workflow>,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<AdminScope>,
+) -> Result<Option<Json<Workflow>>, Rejection> {
+    let workflow = workflow.into_inner().sanitize()?;
+    match db.set_workflow(workflow, workspace, &actor.0).await {
+        Ok(workflow) => Ok(Some(Json(workflow))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(e) => Err(denied(e)),
+    }
+}
+
+#[put("/task/<id>/move", data = "<to>")]
+async fn move_task(
+    id: String,
+    to: Json<MoveTask>,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Option<Json<Task>>, Rejection> {
+    db.authorize_task(&id, &actor.0, Role::Editor)
+        .await
+        .map_err(denied)?;
+    match db.move_task(id, to.into_inner(), &actor.0).await {
+        Ok(task) => Ok(Some(Json(task))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(_) => Err(std::io::Error::other("Error moving task").into()),
+    }
+}
+
+/// Assigns a task to a user. In a workspace, only its members can be
+/// assigned.
+#[put("/task/<id>/assignee", data = "<assignment>")]
+async fn assign_task(
+    id: String,
+    assignment: Json<Assignment>,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Option<Json<Task>>, Rejection> {
+    let assignment = assignment.into_inner().sanitize()?;
+    db.authorize_task(&id, &actor.0, Role::Editor)
+        .await
+        .map_err(denied)?;
+    match db
+        .assign_task(id, Some(assignment.assignee), &actor.0)
+        .await
+    {
+        Ok(task) => Ok(Some(Json(task))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(Error::Invalid(e)) => Err(ValidationErrors::from(e).into()),
+        Err(_) => Err(std::io::Error::other("Error assigning task").into()),
+    }
+}
+
+#[delete("/task/<id>/assignee")]
+async fn unassign_task(
+    id: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Option<Json<Task>>, Rejection> {
+    db.authorize_task(&id, &actor.0, Role::Editor)
+        .await
+        .map_err(denied)?;
+    match db.assign_task(id, None, &actor.0).await {
+        Ok(task) => Ok(Some(Json(task))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(_) => Err(std::io::Error::other("Error unassigning task").into()),
+    }
+}
+
+/// The users the task can be assigned to.
+#[get("/task/<id>/assignees")]
+async fn task_assignees(
+    id: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<ReadScope>,
+) -> Result<Option<Json<Vec<String>>>, Rejection> {
+    db.authorize_task(&id, &actor.0, Role::Viewer)
+        .await
+        .map_err(denied)?;
+    match db.get_assignees(id).await {
+        Ok(users) => Ok(Some(Json(users))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(_) => Err(std::io::Error::other("Error getting assignees").into()),
+    }
+}
+
 #[delete("/task/<id>")]
-async fn delete_task(id: String, db: &State<DB>) -> Result<Json<AffectedRows>, std::io::Error> {
+async fn delete_task(
+    id: String,
+    db: &State<DB>,
+    files: &State<Files>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Json<AffectedRows>, Rejection> {
+    db.authorize_task(&id, &actor.0, Role::Editor)
+        .await
+        .map_err(denied)?;
     let affected_rows = db
-        .delete_task(id)
+        .delete_task(id.clone(), &actor.0)
         .await
-        .map_err(|_| std::io::Error::new(ErrorKind::Other, "Error deleting task"))?;
+        .map_err(|_| std::io::Error::other("Error deleting task"))?;
+    if affected_rows.affected_rows > 0 {
+        // The task is gone either way, so leftover files are only logged.
+        if let Err(e) = db.delete_attachments(id, files.0.as_ref()).await {
+            warn!("failed to delete attachments: {}", e);
+        }
+    }
     Ok(Json(affected_rows))
 }
 
 #[patch("/task/<id>")]
-async fn toggle_task(id: String, db: &State<DB>) -> Result<Json<AffectedRows>, std::io::Error> {
+async fn toggle_task(
+    id: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Json<AffectedRows>, Rejection> {
+    db.authorize_task(&id, &actor.0, Role::Editor)
+        .await
+        .map_err(denied)?;
+    match db.toggle_task(id, &actor.0).await {
+        Ok(affected_rows) => Ok(Json(affected_rows)),
+        Err(e @ Error::XConflict(_)) => Err(Rejection::Conflict(e.to_string())),
+        Err(e) => Err(std::io::Error::other(e.to_string()).into()),
+    }
+}
+
+/// The task's changes, oldest first. Tasks that never existed are `404 Not
+/// Found`; deleted ones still have their history.
+#[get("/task/<id>/history")]
+async fn task_history(
+    id: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<ReadScope>,
+) -> Result<Option<Json<Vec<TaskEvent>>>, Rejection> {
+    db.authorize_task(&id, &actor.0, Role::Viewer)
+        .await
+        .map_err(denied)?;
+    let history = db
+        .get_task_history(id)
+        .await
+        .map_err(|_| std::io::Error::other("Error getting task history"))?;
+    Ok((!history.is_empty()).then_some(Json(history)))
+}
+
+/// The task's comments, oldest first, with replies naming their parent.
+#[get("/task/<id>/comments")]
+async fn task_comments(
+    id: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<ReadScope>,
+) -> Result<Option<Json<Vec<Comment>>>, Rejection> {
+    db.authorize_task(&id, &actor.0, Role::Viewer)
+        .await
+        .map_err(denied)?;
+    match db.get_comments(id).await {
+        Ok(comments) => Ok(Some(Json(comments))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(_) => Err(std::io::Error::other("Error getting comments").into()),
+    }
+}
+
+#[post("/task/<id>/comments", data = "<comment>")]
+async fn add_comment(
+    id: String,
+    comment: Json<NewComment>,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Option<Json<Comment>>, Rejection> {
+    let comment = comment.into_inner().sanitize()?;
+    db.authorize_task(&id, &actor.0, Role::Editor)
+        .await
+        .map_err(denied)?;
+    match db.add_comment(id, comment, &actor.0).await {
+        Ok(comment) => Ok(Some(Json(comment))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(Error::Invalid(e)) => Err(ValidationErrors::from(e).into()),
+        Err(_) => Err(std::io::Error::other("Error adding comment").into()),
+    }
+}
+
+/// Edits a comment. Only its author may, while they can still edit the
+/// task; others get `403 Forbidden`.
+#[put("/comment/<id>", data = "<edit>")]
+async fn edit_comment(
+    id: String,
+    edit: Json<EditComment>,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Option<Json<Comment>>, Rejection> {
+    let edit = edit.into_inner().sanitize()?;
+    db.authorize_comment(&id, &actor.0, Role::Editor)
+        .await
+        .map_err(denied)?;
+    match db.edit_comment(id, edit, &actor.0).await {
+        Ok(comment) => Ok(Some(Json(comment))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(e @ Error::XForbidden(_)) => Err(Rejection::Forbidden(e.to_string())),
+        Err(_) => Err(std::io::Error::other("Error editing comment").into()),
+    }
+}
+
+#[delete("/comment/<id>")]
+async fn delete_comment(
+    id: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Json<AffectedRows>, Rejection> {
+    db.authorize_comment(&id, &actor.0, Role::Editor)
+        .await
+        .map_err(denied)?;
+    match db.delete_comment(id, &actor.0).await {
+        Ok(affected_rows) => Ok(Json(affected_rows)),
+        Err(e @ Error::XForbidden(_)) => Err(Rejection::Forbidden(e.to_string())),
+        Err(_) => Err(std::io::Error::other("Error deleting comment").into()),
+    }
+}
+
+/// A `multipart/form-data` upload with the file in its `file` field.
+#[derive(FromForm)]
+struct Upload<'r> {
+    file: Capped<TempFile<'r>>,
+}
+
+#[get("/task/<id>/attachments")]
+async fn task_attachments(
+    id: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<ReadScope>,
+) -> Result<Option<Json<Vec<Attachment>>>, Rejection> {
+    db.authorize_task(&id, &actor.0, Role::Viewer)
+        .await
+        .map_err(denied)?;
+    match db.get_attachments(id).await {
+        Ok(attachments) => Ok(Some(Json(attachments))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(_) => Err(std::io::Error::other("Error getting attachments").into()),
+    }
+}
+
+/// Attaches an uploaded file to a task. Files over the `file` limit get
+/// `413 Payload Too Large`.
+#[post("/task/<id>/attachments", data = "<upload>")]
+async fn add_attachment(
+    id: String,
+    upload: Form<Upload<'_>>,
+    db: &State<DB>,
+    files: &State<Files>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Option<Json<Attachment>>, Rejection> {
+    if !upload.file.is_complete() {
+        return Err(Rejection::TooLarge(
+            "attachment is over the upload size limit".to_string(),
+        ));
+    }
+    db.authorize_task(&id, &actor.0, Role::Editor)
+        .await
+        .map_err(denied)?;
+    let file = NewAttachment::read(&upload.file).await?;
+    match db
+        .add_attachment(id, file, &actor.0, files.0.as_ref())
+        .await
+    {
+        Ok(attachment) => Ok(Some(Json(attachment))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(Error::Invalid(e)) => Err(ValidationErrors::from(e).into()),
+        Err(_) => Err(std::io::Error::other("Error adding attachment").into()),
+    }
+}
+
+/// The attachment's contents, with the content type it was uploaded as.
+#[get("/attachment/<id>")]
+async fn download_attachment(
+    id: String,
+    db: &State<DB>,
+    files: &State<Files>,
+    _limit: RateLimit,
+    actor: Actor<ReadScope>,
+) -> Result<Option<Download>, Rejection> {
+    db.authorize_attachment(&id, &actor.0, Role::Viewer)
+        .await
+        .map_err(denied)?;
+    match db.download_attachment(id, files.0.as_ref()).await {
+        Ok(download) => Ok(Some(download)),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(_) => Err(std::io::Error::other("Error reading attachment").into()),
+    }
+}
+
+#[delete("/attachment/<id>")]
+async fn delete_attachment(
+    id: String,
+    db: &State<DB>,
+    files: &State<Files>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Json<AffectedRows>, Rejection> {
+    db.authorize_attachment(&id, &actor.0, Role::Editor)
+        .await
+        .map_err(denied)?;
     let affected_rows = db
-        .toggle_task(id)
+        .delete_attachment(id, files.0.as_ref())
         .await
-        .map_err(|e| std::io::Error::new(ErrorKind::Other, e.to_string()))?;
+        .map_err(|_| std::io::Error::other("Error deleting attachment"))?;
     Ok(Json(affected_rows))
 }
 
-#[launch]
-async fn rocket() -> _ {
-    let ds = Arc::new(Datastore::new("memory").await.unwrap());
+/// The caller's reminders kept in the app, newest first. With `unread=true`, only the
+/// ones not yet read.
+#[get("/notifications?<unread>")]
+async fn get_notifications(
+    unread: Option<bool>,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<ReadScope>,
+) -> Result<Json<Vec<Notification>>, std::io::Error> {
+    let notifications = db
+        .get_notifications(&actor.0, unread.unwrap_or(false))
+        .await
+        .map_err(|_| std::io::Error::other("Error getting notifications"))?;
+    Ok(Json(notifications))
+}
+
+#[put("/notification/<id>/read")]
+async fn read_notification(
+    id: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Option<Json<Notification>>, std::io::Error> {
+    match db.read_notification(id, &actor.0).await {
+        Ok(notification) => Ok(Some(Json(notification))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(_) => Err(std::io::Error::other("Error reading notification")),
+    }
+}
+
+/// The webhooks of a workspace, which only its owners may list, or the
+/// caller's outside any.
+#[get("/webhooks?<workspace>")]
+async fn get_webhooks(
+    workspace: Option<String>,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<AdminScope>,
+) -> Result<Json<Vec<Webhook>>, Rejection> {
+    match db.get_webhooks(workspace, &actor.0).await {
+        Ok(webhooks) => Ok(Json(webhooks)),
+        Err(e @ (Error::XAnonymous | Error::XRoleRequired(_))) => Err(denied(e)),
+        Err(_) => Err(std::io::Error::other("Error getting webhooks").into()),
+    }
+}
+
+/// Subscribes a URL to changes to the tasks of a workspace, which only its
+/// owners may, or to those outside any. The secret is not sent back.
+#[post("/webhooks?<workspace>", data = "<webhook>")]
+async fn add_webhook(
+    webhook: Json<NewWebhook>,
+    workspace: Option<String>,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<AdminScope>,
+) -> Result<Json<Webhook>, Rejection> {
+    let webhook = webhook.into_inner().sanitize()?;
+    match db.add_webhook(webhook, workspace, &actor.0).await {
+        Ok(webhook) => Ok(Json(webhook)),
+        Err(e @ (Error::XAnonymous | Error::XRoleRequired(_))) => Err(denied(e)),
+        Err(_) => Err(std::io::Error::other("Error adding webhook").into()),
+    }
+}
+
+/// Deletes a webhook, with its deliveries.
+#[delete("/webhook/<id>")]
+async fn delete_webhook(
+    id: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<AdminScope>,
+) -> Result<Json<AffectedRows>, Rejection> {
+    match db.delete_webhook(id, &actor.0).await {
+        Ok(affected_rows) => Ok(Json(affected_rows)),
+        Err(e @ Error::XForbidden(_)) => Err(Rejection::Forbidden(e.to_string())),
+        Err(e @ (Error::XAnonymous | Error::XRoleRequired(_))) => Err(denied(e)),
+        Err(_) => Err(std::io::Error::other("Error deleting webhook").into()),
+    }
+}
+
+/// The webhook's latest deliveries, newest first.
+#[get("/webhook/<id>/deliveries")]
+async fn webhook_deliveries(
+    id: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<AdminScope>,
+) -> Result<Option<Json<Vec<WebhookDelivery>>>, Rejection> {
+    match db.get_deliveries(id, &actor.0).await {
+        Ok(deliveries) => Ok(Some(Json(deliveries))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(e @ Error::XForbidden(_)) => Err(Rejection::Forbidden(e.to_string())),
+        Err(e @ (Error::XAnonymous | Error::XRoleRequired(_))) => Err(denied(e)),
+        Err(_) => Err(std::io::Error::other("Error getting deliveries").into()),
+    }
+}
+
+/// The caller's personal access tokens, without their secrets.
+#[get("/tokens")]
+async fn get_tokens(
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<AdminScope>,
+) -> Result<Json<Vec<ApiToken>>, std::io::Error> {
+    let tokens = db
+        .get_tokens(&actor.0)
+        .await
+        .map_err(|_| std::io::Error::other("Error getting tokens"))?;
+    Ok(Json(tokens))
+}
+
+/// Issues a personal access token acting as the caller, who must send a
+/// bearer token. Its secret is only ever in this response.
+#[post("/tokens", data = "<token>")]
+async fn create_token(
+    token: Json<NewApiToken>,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<AdminScope>,
+) -> Result<Json<IssuedToken>, Rejection> {
+    let token = token.into_inner().sanitize()?;
+    match db.create_token(token, &actor.0).await {
+        Ok(issued) => Ok(Json(issued)),
+        Err(e @ Error::XAnonymous) => Err(denied(e)),
+        Err(_) => Err(std::io::Error::other("Error creating token").into()),
+    }
+}
+
+/// Revokes one of the caller's tokens, which then gets `401 Unauthorized`.
+#[delete("/token/<id>")]
+async fn revoke_token(
+    id: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<AdminScope>,
+) -> Result<Json<AffectedRows>, Rejection> {
+    match db.revoke_token(id, &actor.0).await {
+        Ok(affected_rows) => Ok(Json(affected_rows)),
+        Err(e @ Error::XForbidden(_)) => Err(Rejection::Forbidden(e.to_string())),
+        Err(_) => Err(std::io::Error::other("Error revoking token").into()),
+    }
+}
+
+/// The workspaces the caller is a member of.
+#[get("/workspaces")]
+async fn get_workspaces(
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<ReadScope>,
+) -> Result<Json<Vec<Workspace>>, std::io::Error> {
+    let workspaces = db
+        .get_workspaces(&actor.0)
+        .await
+        .map_err(|_| std::io::Error::other("Error getting workspaces"))?;
+    Ok(Json(workspaces))
+}
+
+/// Creates a workspace owned by the caller, who must send a bearer token.
+#[post("/workspaces", data = "<workspace>")]
+async fn create_workspace(
+    workspace: Json<NewWorkspace>,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Json<Workspace>, Rejection> {
+    let workspace = workspace.into_inner().sanitize()?;
+    match db.create_workspace(workspace, &actor.0).await {
+        Ok(workspace) => Ok(Json(workspace)),
+        Err(e @ Error::XAnonymous) => Err(denied(e)),
+        Err(_) => Err(std::io::Error::other("Error creating workspace").into()),
+    }
+}
+
+#[get("/workspace/<id>/members")]
+async fn workspace_members(
+    id: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<ReadScope>,
+) -> Result<Option<Json<Vec<Member>>>, Rejection> {
+    match db.get_members(id, &actor.0).await {
+        Ok(members) => Ok(Some(Json(members))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(e) => Err(denied(e)),
+    }
+}
+
+/// Makes a single-use invitation granting `role`. Only owners may.
+#[post("/workspace/<id>/invitations", data = "<role>")]
+async fn create_invitation(
+    id: String,
+    role: Json<SetRole>,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Option<Json<Invitation>>, Rejection> {
+    match db.create_invitation(id, role.role, &actor.0).await {
+        Ok(invitation) => Ok(Some(Json(invitation))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(e) => Err(denied(e)),
+    }
+}
+
+/// Joins the caller to the invitation's workspace. Unknown, used and
+/// expired tokens get a 404.
+#[post("/invitation/<token>")]
+async fn accept_invitation(
+    token: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Option<Json<Member>>, Rejection> {
+    match db.accept_invitation(token, &actor.0).await {
+        Ok(member) => Ok(Some(Json(member))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(e) => Err(denied(e)),
+    }
+}
+
+/// Changes a member's role. Only owners may, and demoting the last owner
+/// gets `409 Conflict`.
+#[put("/member/<id>", data = "<role>")]
+async fn set_member_role(
+    id: String,
+    role: Json<SetRole>,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Option<Json<Member>>, Rejection> {
+    match db.set_member_role(id, role.role, &actor.0).await {
+        Ok(member) => Ok(Some(Json(member))),
+        Err(Error::XRecordNotFound(_)) => Ok(None),
+        Err(e @ Error::XLastOwner) => Err(Rejection::Conflict(e.to_string())),
+        Err(e) => Err(denied(e)),
+    }
+}
+
+/// Removes a member, or lets the caller leave. Removing the last owner
+/// gets `409 Conflict`.
+#[delete("/member/<id>")]
+async fn remove_member(
+    id: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Json<AffectedRows>, Rejection> {
+    match db.remove_member(id, &actor.0).await {
+        Ok(affected_rows) => Ok(Json(affected_rows)),
+        Err(e @ Error::XLastOwner) => Err(Rejection::Conflict(e.to_string())),
+        Err(e) => Err(denied(e)),
+    }
+}
+
+/// Exports the tasks of the workspace given, or those outside any
+/// workspace.
+#[get("/export?<format>&<workspace>")]
+async fn export_tasks(
+    format: Option<Format>,
+    workspace: Option<String>,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<ReadScope>,
+) -> Result<Export, Rejection> {
+    db.authorize(workspace.as_deref(), &actor.0, Role::Viewer)
+        .await
+        .map_err(denied)?;
+    let format = format.unwrap_or(Format::Json);
+    Ok(transfer::export(db.inner().clone(), workspace, format))
+}
+
+/// Imports tasks into the workspace given, or outside any workspace. Only
+/// the workspace's editors and owners may.
+#[post("/import?<format>&<dry_run>&<workspace>", data = "<body>")]
+async fn import_tasks(
+    format: Option<Format>,
+    dry_run: Option<bool>,
+    workspace: Option<String>,
+    body: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Json<ImportReport>, Rejection> {
+    db.authorize(workspace.as_deref(), &actor.0, Role::Editor)
+        .await
+        .map_err(denied)?;
+    let report = transfer::import(
+        db,
+        format.unwrap_or(Format::Json),
+        &body,
+        dry_run.unwrap_or(false),
+        workspace.as_deref(),
+        &actor.0,
+    )
+    .await
+    .map_err(|e| match e {
+        Error::InvalidImport(message) => Rejection::BadRequest(message),
+        _ => std::io::Error::other("Error importing tasks").into(),
+    })?;
+    Ok(Json(report))
+}
+
+/// Makes a calendar feed of the workspace given, or of the tasks outside
+/// any workspace, owned by the caller, who must send a bearer token and be
+/// able to read the tasks.
+#[post("/calendar/feed?<workspace>")]
+async fn create_calendar_feed(
+    workspace: Option<String>,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Json<CalendarFeed>, Rejection> {
+    db.authorize(workspace.as_deref(), &actor.0, Role::Viewer)
+        .await
+        .map_err(denied)?;
+    let token = match db.create_calendar_feed(workspace, &actor.0).await {
+        Ok(token) => token,
+        Err(e @ Error::XAnonymous) => return Err(denied(e)),
+        Err(_) => return Err(std::io::Error::other("Error creating calendar feed").into()),
+    };
+    Ok(Json(CalendarFeed {
+        path: uri!(calendar_feed(token.as_str())).to_string(),
+        token,
+    }))
+}
+
+/// Deletes one of the caller's calendar feeds.
+#[delete("/calendar/feed/<token>")]
+async fn delete_calendar_feed(
+    token: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+    actor: Actor<WriteScope>,
+) -> Result<Json<AffectedRows>, Rejection> {
+    match db.delete_calendar_feed(token, &actor.0).await {
+        Ok(affected_rows) => Ok(Json(affected_rows)),
+        Err(e @ Error::XForbidden(_)) => Err(Rejection::Forbidden(e.to_string())),
+        Err(_) => Err(std::io::Error::other("Error deleting calendar feed").into()),
+    }
+}
+
+/// Serves the feed's tasks as VTODOs, for subscribing from calendar apps.
+/// Unknown tokens get a 404, as do feeds whose owner has since left the
+/// workspace.
+#[get("/calendar/<token>/tasks.ics")]
+async fn calendar_feed(
+    token: String,
+    db: &State<DB>,
+    _limit: RateLimit,
+) -> Result<Option<Export>, std::io::Error> {
+    let feed = db
+        .get_calendar_feed(token)
+        .await
+        .map_err(|_| std::io::Error::other("Error reading calendar feed"))?;
+    let Some(feed) = feed else {
+        return Ok(None);
+    };
+    match db
+        .authorize(feed.workspace.as_deref(), &feed.owner, Role::Viewer)
+        .await
+    {
+        Ok(()) => {}
+        Err(Error::XRoleRequired(_)) => return Ok(None),
+        Err(_) => return Err(std::io::Error::other("Error reading calendar feed")),
+    }
+    let db = db.inner().clone();
+    Ok(Some(transfer::export(db, feed.workspace, Format::ICal)))
+}
+
+/// Builds the server from `figment`. The datastore is opened from the
+/// `datastore` key, an in-memory one by default, so every call gets a fresh
+/// store unless a path is configured.
+async fn build(figment: Figment) -> Rocket<Build> {
+    let path = figment
+        .extract_inner::<String>("datastore")
+        .unwrap_or_else(|_| "memory".to_string());
+    let ds = Arc::new(Datastore::new(&path).await.unwrap());
     let sesh = Session::for_db("my_ns", "my_db");
     let db = DB { ds, sesh };
 
-    rocket::build()
+    rocket::custom(figment)
         .mount(
             "/",
-            routes![add_task, get_task, get_tasks, delete_task, toggle_task],
+            routes![
+                add_task,
+                get_task,
+                get_tasks,
+                update_task,
+                move_task,
+                set_task_status,
+                assign_task,
+                unassign_task,
+                task_assignees,
+                delete_task,
+                toggle_task,
+                task_history,
+                task_comments,
+                add_comment,
+                edit_comment,
+                delete_comment,
+                task_attachments,
+                add_attachment,
+                download_attachment,
+                delete_attachment,
+                get_notifications,
+                read_notification,
+                get_webhooks,
+                add_webhook,
+                delete_webhook,
+                webhook_deliveries,
+                get_tokens,
+                create_token,
+                revoke_token,
+                get_workspaces,
+                create_workspace,
+                workspace_members,
+                create_invitation,
+                accept_invitation,
+                set_member_role,
+                remove_member,
+                get_statuses,
+                set_statuses,
+                export_tasks,
+                import_tasks,
+                create_calendar_feed,
+                delete_calendar_feed,
+                calendar_feed
+            ],
         )
         .attach(CORS)
+        .attach(RateLimiter)
+        .attach(FileStorage)
+        .attach(Reminders)
+        .attach(Webhooks)
         .manage(db)
 }
+
+#[launch]
+async fn rocket() -> _ {
+    build(rocket::Config::figment()).await
+}
@@ -0,0 +1,255 @@
+//! In-app notifications, and the task queries behind reminders; see
+//! [`reminders`](crate::reminders).
+
+use chrono::{DateTime, Utc};
+use rocket::serde::Deserialize;
+use surrealdb::sql::Value;
+
+use crate::db::{Task, DB};
+use crate::error::Error;
+use crate::query::{Order, Query, Return, Target};
+use crate::utils::value::from_value;
+pub use todo_models::{reminder_message, Notification};
+
+/// Which notifier already sent a reminder.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct ReminderDelivery {
+    notifier: String,
+}
+
+impl DB {
+    /// `user`'s notifications, newest first.
+    pub async fn get_notifications(
+        &self,
+        user: &str,
+        unread: bool,
+    ) -> Result<Vec<Notification>, Error> {
+        let mut query = Query::select("notifications")
+            .where_eq("user", user)
+            .order_by("created_at", Order::Desc);
+        if unread {
+            query = query.where_eq("read", false);
+        }
+        self.fetch(query).await
+    }
+
+    /// Notifies everyone the task can be assigned to: the members of its
+    /// workspace, or for tasks outside any, the signed-in users who changed
+    /// it. Each gets their own notification to read.
+    pub async fn add_notification(&self, task: &Task) -> Result<Vec<Notification>, Error> {
+        let users = self.get_assignees(task.id.clone()).await?;
+        let message = reminder_message(task);
+        let now = Utc::now();
+        let queries: Vec<Query> = users
+            .into_iter()
+            .map(|user| {
+                Query::create("notifications")
+                    .set("user", user)
+                    .set("task", task.id.as_str())
+                    .set("message", message.as_str())
+                    .set("created_at", now)
+                    .set("read", false)
+            })
+            .collect();
+        let results = self
+            .transaction(|tx| async move {
+                for query in queries {
+                    tx.add(query);
+                }
+                Ok(())
+            })
+            .await?;
+        results
+            .into_iter()
+            .map(|value| from_value(value.first()))
+            .collect()
+    }
+
+    /// Marks one of `user`'s notifications read. Other users' are not found.
+    pub async fn read_notification(&self, id: String, user: &str) -> Result<Notification, Error> {
+        // Any record id parses, so other tables are turned away here.
+        if !id.starts_with("notifications:") {
+            return Err(Error::XRecordNotFound(id));
+        }
+        // UPDATE on a record id creates the record when it is missing.
+        let query = Query::update(Target::record(&id)?)
+            .set("read", true)
+            .where_eq("read", false)
+            .where_eq("user", user)
+            .returning(Return::After);
+        if let Some(notification) = self.fetch_one(query).await? {
+            return Ok(notification);
+        }
+        let query = Query::select(Target::record(&id)?);
+        self.fetch_one(query)
+            .await?
+            .filter(|notification: &Notification| notification.user == user)
+            .ok_or(Error::XRecordNotFound(id))
+    }
+
+    /// The notifiers that already sent the task's current reminder.
+    pub async fn reminder_sent_by(&self, task: &Task) -> Result<Vec<String>, Error> {
+        let Some(remind_at) = task.remind_at else {
+            return Ok(vec![]);
+        };
+        let query = Query::select("reminder_deliveries")
+            .where_eq("task", task.id.as_str())
+            .where_eq("remind_at", remind_at);
+        let deliveries: Vec<ReminderDelivery> = self.fetch(query).await?;
+        Ok(deliveries.into_iter().map(|d| d.notifier).collect())
+    }
+
+    /// Notes that `notifier` sent the task's current reminder, so it is not
+    /// sent again while other notifiers are retried.
+    pub async fn mark_reminder_sent(&self, task: &Task, notifier: &str) -> Result<(), Error> {
+        let Some(remind_at) = task.remind_at else {
+            return Ok(());
+        };
+        let query = Query::create("reminder_deliveries")
+            .set("task", task.id.as_str())
+            .set("remind_at", remind_at)
+            .set("notifier", notifier)
+            .returning(Return::None);
+        self.run(query).await.map(drop)
+    }
+
+    /// Open tasks whose reminder is due by `now`, earliest first.
+    pub async fn due_reminders(&self, now: DateTime<Utc>) -> Result<Vec<Task>, Error> {
+        // NONE sorts before every datetime, so unset reminders are excluded.
+        let query = Query::select("tasks")
+            .where_cmp("remind_at", "!=", Value::None)
+            .where_cmp("remind_at", "<=", now)
+            .where_eq("completed", false)
+            .order_by("remind_at", Order::Asc);
+        self.fetch(query).await
+    }
+
+    /// Clears the reminder of `task` once it is sent, unless it was moved
+    /// to another time in the meantime, along with which notifiers sent it.
+    pub async fn clear_reminder(&self, task: &Task) -> Result<(), Error> {
+        let Some(remind_at) = task.remind_at else {
+            return Ok(());
+        };
+        let clear = Query::update(Target::record(&task.id)?)
+            .set_expr("remind_at", "NONE")
+            .where_eq("remind_at", remind_at)
+            .returning(Return::None);
+        let deliveries = Query::delete("reminder_deliveries")
+            .where_eq("task", task.id.as_str())
+            .returning(Return::None);
+        self.transaction(|tx| async move {
+            tx.add(clear);
+            tx.add(deliveries);
+            Ok(())
+        })
+        .await
+        .map(drop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::ANONYMOUS;
+    use crate::db::UpdateTask;
+    use std::sync::Arc;
+    use surrealdb::{dbs::Session, kvs::Datastore};
+
+    async fn db() -> DB {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        DB { ds, sesh }
+    }
+
+    async fn remind(db: &DB, title: &str, at: &str) -> Task {
+        let task = db.add_task(title.to_string(), "user:ada").await.unwrap();
+        let update = UpdateTask {
+            remind_at: Some(Some(at.parse().unwrap())),
+            ..Default::default()
+        };
+        db.update_task(task.id, update, ANONYMOUS).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_due_reminders() {
+        let db = db().await;
+        db.add_task("no reminder".to_string(), ANONYMOUS)
+            .await
+            .unwrap();
+        let later = remind(&db, "later", "2023-04-03T09:00:00Z").await;
+        let due = remind(&db, "due", "2023-04-02T09:00:00Z").await;
+        let done = remind(&db, "done", "2023-04-01T09:00:00Z").await;
+        db.toggle_task(done.id, ANONYMOUS).await.unwrap();
+
+        let now = "2023-04-02T10:00:00Z".parse().unwrap();
+        let tasks = db.due_reminders(now).await.unwrap();
+        assert_eq!(tasks, std::slice::from_ref(&due));
+
+        // A reminder moved since it was read is kept.
+        let moved = Task {
+            remind_at: later.remind_at,
+            ..due.clone()
+        };
+        db.clear_reminder(&moved).await.unwrap();
+        assert_eq!(db.due_reminders(now).await.unwrap().len(), 1);
+
+        db.mark_reminder_sent(&due, "email").await.unwrap();
+        assert_eq!(db.reminder_sent_by(&due).await.unwrap(), ["email"]);
+        assert!(db.reminder_sent_by(&moved).await.unwrap().is_empty());
+
+        db.clear_reminder(&due).await.unwrap();
+        assert!(db.due_reminders(now).await.unwrap().is_empty());
+        assert!(db.reminder_sent_by(&due).await.unwrap().is_empty());
+        assert_eq!(db.get_task(due.id).await.unwrap().remind_at, None);
+        assert!(db.get_task(later.id).await.unwrap().remind_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_notifications() {
+        let db = db().await;
+        let task = remind(&db, "call mom", "2023-04-02T09:00:00Z").await;
+
+        // Anonymous changes notify no one.
+        let notifications = db.add_notification(&task).await.unwrap();
+        assert_eq!(notifications.len(), 1);
+        let notification = notifications[0].clone();
+        assert_eq!(notification.user, "user:ada");
+        assert_eq!(notification.task, task.id);
+        assert_eq!(notification.message, "Reminder: call mom");
+        assert!(!notification.read);
+
+        let res = db
+            .read_notification(notification.id.clone(), "user:bob")
+            .await;
+        assert!(matches!(res, Err(Error::XRecordNotFound(_))));
+        assert!(db
+            .get_notifications("user:bob", false)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let read = db
+            .read_notification(notification.id.clone(), "user:ada")
+            .await
+            .unwrap();
+        assert!(read.read);
+        // Reading it again changes nothing.
+        let again = db.read_notification(notification.id, "user:ada").await;
+        assert_eq!(again.unwrap(), read);
+        let notifications = db.get_notifications("user:ada", false).await.unwrap();
+        assert_eq!(notifications, [read]);
+        assert!(db
+            .get_notifications("user:ada", true)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let res = db.read_notification(task.id, "user:ada").await;
+        assert!(matches!(res, Err(Error::XRecordNotFound(_))));
+        let res = db
+            .read_notification("notifications:missing".to_string(), "user:ada")
+            .await;
+        assert!(matches!(res, Err(Error::XRecordNotFound(_))));
+    }
+}
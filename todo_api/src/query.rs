@@ -0,0 +1,384 @@
+//! A small builder for the SurrealQL statements the [`DB`](crate::db::DB)
+//! layer runs.
+//!
+//! Tables, fields and filter expressions are `&'static str`, so they can only
+//! come from the code. Values are always bound as parameters and never
+//! formatted into the query text.
+
+use std::collections::BTreeMap;
+
+use surrealdb::sql::{thing, Thing, Value};
+
+use crate::error::Error;
+
+/// What a statement runs against.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    Table(&'static str),
+    Record(Thing),
+    /// A record given by its table and key, such as a rate limit bucket.
+    Key(&'static str, String),
+}
+
+impl Target {
+    /// Parses a record id such as `tasks:abc`.
+    pub fn record(id: &str) -> Result<Self, Error> {
+        let thing = thing(id).map_err(|e| Error::Surreal(surrealdb::Error::Db(e)))?;
+        Ok(Target::Record(thing))
+    }
+}
+
+impl From<&'static str> for Target {
+    fn from(table: &'static str) -> Self {
+        Target::Table(table)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+/// Which version of the records a write returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Return {
+    None,
+    Before,
+    After,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Statement {
+    Select,
+    Create,
+    Update,
+    Delete,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    /// A bound parameter, by name.
+    Param(String),
+    Raw(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Data {
+    Set(Vec<(&'static str, Expr)>),
+    Merge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    statement: Statement,
+    target: Target,
+    data: Option<Data>,
+    /// Fields compared to a parameter or expression, by operator.
+    conditions: Vec<(&'static str, &'static str, Expr)>,
+    order: Vec<(&'static str, Order)>,
+    limit: Option<u64>,
+    start: Option<u64>,
+    output: Option<Return>,
+    vars: BTreeMap<String, Value>,
+}
+
+impl Query {
+    fn new(statement: Statement, target: Target) -> Self {
+        Query {
+            statement,
+            target,
+            data: None,
+            conditions: vec![],
+            order: vec![],
+            limit: None,
+            start: None,
+            output: None,
+            vars: BTreeMap::new(),
+        }
+    }
+
+    pub fn select(target: impl Into<Target>) -> Self {
+        Self::new(Statement::Select, target.into())
+    }
+
+    pub fn create(target: impl Into<Target>) -> Self {
+        Self::new(Statement::Create, target.into())
+    }
+
+    pub fn update(target: impl Into<Target>) -> Self {
+        Self::new(Statement::Update, target.into())
+    }
+
+    pub fn delete(target: impl Into<Target>) -> Self {
+        Self::new(Statement::Delete, target.into())
+    }
+
+    /// Sets a field to a value.
+    pub fn set(mut self, field: &'static str, value: impl Into<Value>) -> Self {
+        let name = format!("set_{field}");
+        self.vars.insert(name.clone(), value.into());
+        self.set_clause(field, Expr::Param(name))
+    }
+
+    /// Sets a field to a SurrealQL expression, such as `time.now()`.
+    pub fn set_expr(self, field: &'static str, expr: &'static str) -> Self {
+        self.set_clause(field, Expr::Raw(expr))
+    }
+
+    fn set_clause(mut self, field: &'static str, expr: Expr) -> Self {
+        match &mut self.data {
+            Some(Data::Set(fields)) => fields.push((field, expr)),
+            _ => self.data = Some(Data::Set(vec![(field, expr)])),
+        }
+        self
+    }
+
+    /// Merges an object into the records.
+    pub fn merge(mut self, value: impl Into<Value>) -> Self {
+        self.vars.insert("data".to_string(), value.into());
+        self.data = Some(Data::Merge);
+        self
+    }
+
+    /// Keeps records where `field` equals `value`. Conditions are joined by
+    /// `AND`.
+    pub fn where_eq(self, field: &'static str, value: impl Into<Value>) -> Self {
+        self.where_cmp(field, "=", value)
+    }
+
+    /// Keeps records where `field` compares to `value` by `op`, such as
+    /// `<=`. Like `where_eq`, conditions are joined by `AND`.
+    pub fn where_cmp(
+        mut self,
+        field: &'static str,
+        op: &'static str,
+        value: impl Into<Value>,
+    ) -> Self {
+        let name = format!("where_{}", self.conditions.len());
+        self.vars.insert(name.clone(), value.into());
+        self.conditions.push((field, op, Expr::Param(name)));
+        self
+    }
+
+    /// Keeps records where `field` compares to a SurrealQL expression by
+    /// `op`, such as a variable a transaction bound earlier.
+    pub fn where_expr(mut self, field: &'static str, op: &'static str, expr: &'static str) -> Self {
+        self.conditions.push((field, op, Expr::Raw(expr)));
+        self
+    }
+
+    pub fn order_by(mut self, field: &'static str, order: Order) -> Self {
+        self.order.push((field, order));
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Skips the first `start` records, for reading a list a page at a
+    /// time.
+    pub fn start(mut self, start: u64) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    pub fn returning(mut self, output: Return) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    /// The query text and its parameters.
+    pub fn build(self) -> (String, BTreeMap<String, Value>) {
+        self.build_scoped("")
+    }
+
+    /// Like [`build`](Self::build), with every parameter name prefixed by
+    /// `scope`, so several queries can run in one request.
+    pub fn build_scoped(mut self, scope: &str) -> (String, BTreeMap<String, Value>) {
+        let param = |name: &str| format!("${scope}{name}");
+
+        let target = match self.target {
+            Target::Table(table) => table.to_string(),
+            Target::Record(thing) => {
+                self.vars.insert("target".to_string(), thing.into());
+                param("target")
+            }
+            Target::Key(table, key) => {
+                self.vars.insert("target".to_string(), key.into());
+                format!("type::thing('{table}', {})", param("target"))
+            }
+        };
+
+        let mut sql = match self.statement {
+            Statement::Select => format!("SELECT * FROM {target}"),
+            Statement::Create => format!("CREATE {target}"),
+            Statement::Update => format!("UPDATE {target}"),
+            Statement::Delete => format!("DELETE {target}"),
+        };
+
+        match self.data {
+            Some(Data::Set(fields)) => {
+                let fields: Vec<String> = fields
+                    .into_iter()
+                    .map(|(field, expr)| match expr {
+                        Expr::Param(name) => format!("{field} = {}", param(&name)),
+                        Expr::Raw(expr) => format!("{field} = {expr}"),
+                    })
+                    .collect();
+                sql.push_str(&format!(" SET {}", fields.join(", ")));
+            }
+            Some(Data::Merge) => sql.push_str(&format!(" MERGE {}", param("data"))),
+            None => {}
+        }
+
+        if !self.conditions.is_empty() {
+            let conditions: Vec<String> = self
+                .conditions
+                .iter()
+                .map(|(field, op, expr)| match expr {
+                    Expr::Param(name) => format!("{field} {op} {}", param(name)),
+                    Expr::Raw(expr) => format!("{field} {op} {expr}"),
+                })
+                .collect();
+            sql.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
+        }
+
+        if !self.order.is_empty() {
+            let order: Vec<String> = self
+                .order
+                .into_iter()
+                .map(|(field, order)| match order {
+                    Order::Asc => format!("{field} ASC"),
+                    Order::Desc => format!("{field} DESC"),
+                })
+                .collect();
+            sql.push_str(&format!(" ORDER BY {}", order.join(", ")));
+        }
+
+        if let Some(limit) = self.limit {
+            sql.push_str(&format!(" LIMIT {limit}"));
+        }
+
+        if let Some(start) = self.start {
+            sql.push_str(&format!(" START {start}"));
+        }
+
+        if let Some(output) = self.output {
+            sql.push_str(match output {
+                Return::None => " RETURN NONE",
+                Return::Before => " RETURN BEFORE",
+                Return::After => " RETURN AFTER",
+            });
+        }
+
+        let vars = self
+            .vars
+            .into_iter()
+            .map(|(name, value)| (format!("{scope}{name}"), value))
+            .collect();
+        (sql, vars)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select() {
+        let (sql, vars) = Query::select("calendar_feeds")
+            .where_eq("token", "abc")
+            .where_eq("completed", false)
+            .order_by("created_at", Order::Asc)
+            .order_by("title", Order::Desc)
+            .limit(10)
+            .start(20)
+            .build();
+        assert_eq!(
+            sql,
+            "SELECT * FROM calendar_feeds WHERE token = $where_0 AND completed = $where_1 \
+             ORDER BY created_at ASC, title DESC LIMIT 10 START 20"
+        );
+        assert_eq!(vars["where_0"], Value::from("abc"));
+
+        let (sql, _) = Query::select("tasks")
+            .where_cmp("remind_at", "<=", "2023-04-02T10:00:00Z")
+            .where_eq("completed", false)
+            .build();
+        assert_eq!(
+            sql,
+            "SELECT * FROM tasks WHERE remind_at <= $where_0 AND completed = $where_1"
+        );
+
+        let (sql, vars) = Query::select("members")
+            .where_expr("workspace", "=", "$invitation[0].workspace")
+            .where_eq("user", "user:ada")
+            .build();
+        assert_eq!(
+            sql,
+            "SELECT * FROM members WHERE workspace = $invitation[0].workspace AND user = $where_1"
+        );
+        assert_eq!(vars.len(), 1);
+    }
+
+    #[test]
+    fn test_writes() {
+        let (sql, vars) = Query::create("tasks")
+            .set("title", "test")
+            .set_expr("created_at", "time.now()")
+            .build();
+        assert_eq!(
+            sql,
+            "CREATE tasks SET title = $set_title, created_at = time.now()"
+        );
+        assert_eq!(vars.len(), 1);
+
+        let (sql, vars) = Query::update(Target::record("tasks:abc").unwrap())
+            .merge(Value::from("x"))
+            .returning(Return::After)
+            .build();
+        assert_eq!(sql, "UPDATE $target MERGE $data RETURN AFTER");
+        assert!(matches!(vars["target"], Value::Thing(_)));
+
+        let (sql, vars) = Query::update(Target::Key("rate_limits", "k".to_string()))
+            .set("tokens", 1.5)
+            .returning(Return::None)
+            .build();
+        assert_eq!(
+            sql,
+            "UPDATE type::thing('rate_limits', $target) SET tokens = $set_tokens RETURN NONE"
+        );
+        assert_eq!(vars["target"], Value::from("k"));
+
+        let (sql, _) = Query::delete("calendar_feeds")
+            .where_eq("token", "abc")
+            .returning(Return::Before)
+            .build();
+        assert_eq!(
+            sql,
+            "DELETE calendar_feeds WHERE token = $where_0 RETURN BEFORE"
+        );
+    }
+
+    #[test]
+    fn test_scoped() {
+        let (sql, vars) = Query::update(Target::record("tasks:abc").unwrap())
+            .set("title", "test")
+            .where_eq("completed", false)
+            .build_scoped("q1_");
+        assert_eq!(
+            sql,
+            "UPDATE $q1_target SET title = $q1_set_title WHERE completed = $q1_where_0"
+        );
+        let names: Vec<&str> = vars.keys().map(String::as_str).collect();
+        assert_eq!(names, ["q1_set_title", "q1_target", "q1_where_0"]);
+    }
+
+    #[test]
+    fn test_bad_record() {
+        assert!(Target::record("not a record").is_err());
+    }
+}
@@ -0,0 +1,550 @@
+//! Token-bucket rate limiting, per client IP and per user.
+//!
+//! Routes opt in by taking a [`RateLimit`] guard. Each route belongs to a
+//! group from the `rate_limit` config table, and every group keeps its own
+//! buckets. The [`RateLimiter`] fairing loads the config and adds the
+//! `RateLimit-*` headers to responses.
+//!
+//! Rocket runs guards in the order they are declared, so routes take the
+//! guard before [`Actor`](crate::actor::Actor): requests with a bad token
+//! are limited before the token is looked up.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use chrono::Utc;
+use rocket::{
+    fairing::{self, Fairing, Info, Kind},
+    http::{Header, Status},
+    request::{FromRequest, Outcome},
+    serde::{Deserialize, Serialize},
+    Build, Request, Response, Rocket,
+};
+use sha2::{Digest, Sha256};
+
+use crate::db::DB;
+use crate::error::Error;
+use crate::utils::crypto::hex;
+
+/// Idle buckets are dropped once the in-memory store holds this many keys.
+const MAX_KEYS: usize = 10_000;
+/// How long a bucket must be idle before it can be dropped.
+const IDLE_MILLIS: i64 = 60 * 60 * 1000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Limit {
+    /// Requests that can be made at once.
+    pub burst: u32,
+    /// Requests per minute once the burst is used up.
+    pub per_minute: u32,
+    /// Names of the route handlers in this group.
+    #[serde(default)]
+    pub routes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum StoreKind {
+    Memory,
+    /// Keeps buckets in the `rate_limits` table, so instances sharing a
+    /// database share limits.
+    Surreal,
+}
+
+/// The route handlers in the `write` group unless it lists its own.
+const WRITE_ROUTES: &[&str] = &[
+    "add_task",
+    "update_task",
+    "move_task",
+    "set_task_status",
+    "assign_task",
+    "unassign_task",
+    "set_statuses",
+    "add_comment",
+    "edit_comment",
+    "delete_comment",
+    "add_attachment",
+    "delete_attachment",
+    "read_notification",
+    "add_webhook",
+    "delete_webhook",
+    "create_token",
+    "revoke_token",
+    "create_workspace",
+    "create_invitation",
+    "accept_invitation",
+    "set_member_role",
+    "remove_member",
+    "delete_task",
+    "toggle_task",
+    "import_tasks",
+    "create_calendar_feed",
+    "delete_calendar_feed",
+];
+
+/// Routes that are not listed in any group use the `default` group.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct RateLimitConfig {
+    pub store: StoreKind,
+    pub groups: BTreeMap<String, Limit>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        let mut groups = BTreeMap::new();
+        groups.insert(
+            "default".to_string(),
+            Limit {
+                burst: 60,
+                per_minute: 120,
+                routes: vec![],
+            },
+        );
+        groups.insert(
+            "write".to_string(),
+            Limit {
+                burst: 10,
+                per_minute: 30,
+                routes: WRITE_ROUTES.iter().map(|r| r.to_string()).collect(),
+            },
+        );
+        RateLimitConfig {
+            store: StoreKind::Memory,
+            groups,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    fn validate(&self) -> Result<(), String> {
+        if !self.groups.contains_key("default") {
+            return Err("a `default` group is required".into());
+        }
+        match self
+            .groups
+            .iter()
+            .find(|(_, limit)| limit.burst == 0 || limit.per_minute == 0)
+        {
+            Some((name, _)) => Err(format!("group '{name}' must allow at least one request")),
+            None => Ok(()),
+        }
+    }
+
+    /// Gives a configured `write` group without `routes` the built-in list,
+    /// so the list of write routes lives only here.
+    fn with_default_routes(mut self) -> Self {
+        if let Some(write) = self.groups.get_mut("write") {
+            if write.routes.is_empty() {
+                write.routes = WRITE_ROUTES.iter().map(|r| r.to_string()).collect();
+            }
+        }
+        self
+    }
+
+    /// Returns the group a route belongs to.
+    pub fn group(&self, route: Option<&str>) -> (&str, &Limit) {
+        let listed = route.and_then(|route| {
+            self.groups
+                .iter()
+                .find(|(_, limit)| limit.routes.iter().any(|r| r == route))
+        });
+        match listed {
+            Some((name, limit)) => (name, limit),
+            None => ("default", &self.groups["default"]),
+        }
+    }
+}
+
+/// The outcome of taking a token, reported in the response headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again.
+    pub reset: u64,
+    /// Seconds until the next request is allowed, when this one was not.
+    pub retry_after: Option<u64>,
+}
+
+impl Decision {
+    /// Picks the decision to report when several buckets were checked.
+    fn stricter(self, other: Decision) -> Decision {
+        match (self.retry_after, other.retry_after) {
+            (Some(a), Some(b)) if b > a => other,
+            (None, Some(_)) => other,
+            (None, None) if other.remaining < self.remaining => other,
+            _ => self,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Bucket {
+    pub tokens: f64,
+    /// Unix time in milliseconds.
+    pub updated_at: i64,
+}
+
+impl Bucket {
+    pub fn full(limit: &Limit, now: i64) -> Self {
+        Bucket {
+            tokens: f64::from(limit.burst),
+            updated_at: now,
+        }
+    }
+
+    /// Refills the bucket for the time since it was last used, then takes a
+    /// token if one is left.
+    pub fn take(&mut self, limit: &Limit, now: i64) -> Decision {
+        let per_milli = f64::from(limit.per_minute) / 60_000.0;
+        let elapsed = (now - self.updated_at).max(0) as f64;
+        self.tokens = (self.tokens + elapsed * per_milli).min(f64::from(limit.burst));
+        self.updated_at = now;
+
+        let allowed = self.tokens >= 1.0;
+        if allowed {
+            self.tokens -= 1.0;
+        }
+        let seconds_for = |tokens: f64| (tokens / per_milli / 1000.0).ceil() as u64;
+        Decision {
+            limit: limit.burst,
+            remaining: self.tokens.floor() as u32,
+            reset: seconds_for(f64::from(limit.burst) - self.tokens),
+            retry_after: (!allowed).then(|| seconds_for(1.0 - self.tokens)),
+        }
+    }
+}
+
+#[rocket::async_trait]
+pub trait Store: Send + Sync {
+    async fn take(&self, key: &str, limit: &Limit, now: i64) -> Result<Decision, Error>;
+}
+
+#[derive(Default)]
+pub struct MemoryStore {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+#[rocket::async_trait]
+impl Store for MemoryStore {
+    async fn take(&self, key: &str, limit: &Limit, now: i64) -> Result<Decision, Error> {
+        let mut buckets = self.buckets.lock().expect("rate limit buckets poisoned");
+        if buckets.len() >= MAX_KEYS {
+            buckets.retain(|_, bucket| now - bucket.updated_at < IDLE_MILLIS);
+        }
+        let bucket = buckets
+            .entry(key.to_string())
+            .or_insert_with(|| Bucket::full(limit, now));
+        Ok(bucket.take(limit, now))
+    }
+}
+
+/// Shares buckets through the database. Reads and writes are separate
+/// queries, so concurrent requests for one key may both get a token.
+pub struct SurrealStore {
+    db: DB,
+}
+
+#[rocket::async_trait]
+impl Store for SurrealStore {
+    async fn take(&self, key: &str, limit: &Limit, now: i64) -> Result<Decision, Error> {
+        let mut bucket = self
+            .db
+            .get_rate_limit(key.to_string())
+            .await?
+            .unwrap_or_else(|| Bucket::full(limit, now));
+        let decision = bucket.take(limit, now);
+        self.db.set_rate_limit(key.to_string(), bucket).await?;
+        Ok(decision)
+    }
+}
+
+struct Limiter {
+    config: RateLimitConfig,
+    store: Box<dyn Store>,
+}
+
+/// The decision for the current request, kept for the response headers.
+struct Decided(Option<Decision>);
+
+/// The token in the request's `Authorization: Bearer` header.
+pub fn bearer<'r>(request: &'r Request<'_>) -> Option<&'r str> {
+    request
+        .headers()
+        .get_one("Authorization")?
+        .strip_prefix("Bearer ")
+}
+
+/// Identifies the user by a hash of their bearer token, so tokens are not
+/// kept in the store. The key is also recorded as the user's actor, so it
+/// must not change between builds.
+pub fn user_key(request: &Request<'_>) -> Option<String> {
+    bearer(request).map(token_key)
+}
+
+fn token_key(token: &str) -> String {
+    hex(&Sha256::digest(token.as_bytes()))
+}
+
+/// Request guard that takes a token from the caller's IP bucket and, for
+/// authenticated requests, their user bucket. Fails with `429 Too Many
+/// Requests` when either is empty.
+pub struct RateLimit;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RateLimit {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let limiter = match request.rocket().state::<Limiter>() {
+            Some(limiter) => limiter,
+            None => return Outcome::Success(RateLimit),
+        };
+        let route = request.route().and_then(|r| r.name.as_deref());
+        let (group, limit) = limiter.config.group(route);
+
+        let ip = request
+            .client_ip()
+            .map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+        let mut keys = vec![format!("{group}:ip:{ip}")];
+        if let Some(user) = user_key(request) {
+            keys.push(format!("{group}:user:{user}"));
+        }
+
+        let now = Utc::now().timestamp_millis();
+        let mut decision: Option<Decision> = None;
+        for key in keys {
+            match limiter.store.take(&key, limit, now).await {
+                Ok(taken) => {
+                    decision = Some(decision.map_or(taken, |d| d.stricter(taken)));
+                }
+                // Failing open keeps the API up when the shared store is not.
+                Err(e) => warn!("rate limit store failed: {}", e),
+            }
+        }
+
+        let decision = request.local_cache(|| Decided(decision)).0;
+        match decision {
+            Some(Decision {
+                retry_after: Some(_),
+                ..
+            }) => Outcome::Failure((Status::TooManyRequests, ())),
+            _ => Outcome::Success(RateLimit),
+        }
+    }
+}
+
+pub struct RateLimiter;
+
+#[rocket::async_trait]
+impl Fairing for RateLimiter {
+    fn info(&self) -> Info {
+        Info {
+            name: "Rate limiter",
+            kind: Kind::Ignite | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let config = match rocket.figment().find_value("rate_limit") {
+            Ok(_) => rocket
+                .figment()
+                .extract_inner::<RateLimitConfig>("rate_limit"),
+            Err(_) => Ok(RateLimitConfig::default()),
+        };
+        let config = match config.map_err(|e| e.to_string()).and_then(|config| {
+            config.validate()?;
+            Ok(config.with_default_routes())
+        }) {
+            Ok(config) => config,
+            Err(e) => {
+                error!("invalid rate_limit config: {}", e);
+                return Err(rocket);
+            }
+        };
+
+        let store: Box<dyn Store> = match config.store {
+            StoreKind::Memory => Box::<MemoryStore>::default(),
+            StoreKind::Surreal => match rocket.state::<DB>() {
+                Some(db) => Box::new(SurrealStore { db: db.clone() }),
+                None => {
+                    error!("the surreal rate limit store needs a managed DB");
+                    return Err(rocket);
+                }
+            },
+        };
+        Ok(rocket.manage(Limiter { config, store }))
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let decision = match request.local_cache(|| Decided(None)).0 {
+            Some(decision) => decision,
+            None => return,
+        };
+
+        response.set_header(Header::new("RateLimit-Limit", decision.limit.to_string()));
+        response.set_header(Header::new(
+            "RateLimit-Remaining",
+            decision.remaining.to_string(),
+        ));
+        response.set_header(Header::new("RateLimit-Reset", decision.reset.to_string()));
+        if let Some(retry_after) = decision.retry_after {
+            response.set_header(Header::new("Retry-After", retry_after.to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+// The route macros export `uri!` helpers that these tests don't use.
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use rocket::figment::Figment;
+    use rocket::local::blocking::Client;
+    use std::net::SocketAddr;
+
+    fn limit(burst: u32, per_minute: u32) -> Limit {
+        Limit {
+            burst,
+            per_minute,
+            routes: vec![],
+        }
+    }
+
+    #[get("/ping")]
+    fn ping(_limit: RateLimit) -> &'static str {
+        "pong"
+    }
+
+    #[post("/ping")]
+    fn add_ping(_limit: RateLimit) -> &'static str {
+        "added"
+    }
+
+    fn client() -> Client {
+        let mut config = RateLimitConfig::default();
+        config.groups.insert("default".to_string(), limit(5, 60));
+        config.groups.insert(
+            "write".to_string(),
+            Limit {
+                routes: vec!["add_ping".to_string()],
+                ..limit(2, 60)
+            },
+        );
+        let figment = rocket::Config::figment().merge(("rate_limit", config));
+        let rocket = rocket::custom(figment)
+            .mount("/", routes![ping, add_ping])
+            .attach(RateLimiter);
+        Client::tracked(rocket).expect("valid rocket instance")
+    }
+
+    #[test]
+    fn test_bucket_refills() {
+        let limit = limit(2, 60);
+        let mut bucket = Bucket::full(&limit, 0);
+
+        assert_eq!(bucket.take(&limit, 0).remaining, 1);
+        assert_eq!(bucket.take(&limit, 0).remaining, 0);
+        let denied = bucket.take(&limit, 0);
+        assert_eq!(denied.retry_after, Some(1));
+        assert_eq!(denied.reset, 2);
+
+        // One token per second comes back.
+        assert_eq!(bucket.take(&limit, 1500).retry_after, None);
+        assert_eq!(bucket.take(&limit, 1500).retry_after, Some(1));
+
+        // Never above the burst.
+        assert_eq!(bucket.take(&limit, 60_000).remaining, 1);
+    }
+
+    #[test]
+    fn test_token_key_is_stable() {
+        assert_eq!(
+            token_key("secret"),
+            "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b"
+        );
+    }
+
+    #[test]
+    fn test_route_groups() {
+        let config = RateLimitConfig::default();
+        assert_eq!(config.group(Some("add_task")).0, "write");
+        assert_eq!(config.group(Some("get_tasks")).0, "default");
+        assert_eq!(config.group(None).0, "default");
+
+        let mut config = RateLimitConfig::default();
+        config.groups.insert("write".to_string(), limit(5, 60));
+        let config = config.with_default_routes();
+        assert_eq!(config.group(Some("add_task")).0, "write");
+    }
+
+    #[test]
+    fn test_too_many_requests() {
+        let client = client();
+        let remote: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+
+        for remaining in ["1", "0"] {
+            let response = client.post("/ping").remote(remote).dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            assert_eq!(response.headers().get_one("RateLimit-Limit"), Some("2"));
+            assert_eq!(
+                response.headers().get_one("RateLimit-Remaining"),
+                Some(remaining)
+            );
+        }
+
+        let response = client.post("/ping").remote(remote).dispatch();
+        assert_eq!(response.status(), Status::TooManyRequests);
+        assert_eq!(response.headers().get_one("Retry-After"), Some("1"));
+
+        // Other groups and other clients have their own buckets.
+        let response = client.get("/ping").remote(remote).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let other: SocketAddr = "10.0.0.2:4000".parse().unwrap();
+        let response = client.post("/ping").remote(other).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[test]
+    fn test_user_limit_applies_across_ips() {
+        let client = client();
+        let token = Header::new("Authorization", "Bearer secret");
+
+        for i in 0..2 {
+            let remote: SocketAddr = format!("10.0.1.{i}:4000").parse().unwrap();
+            let response = client
+                .post("/ping")
+                .remote(remote)
+                .header(token.clone())
+                .dispatch();
+            assert_eq!(response.status(), Status::Ok);
+        }
+
+        let remote: SocketAddr = "10.0.1.9:4000".parse().unwrap();
+        let response = client.post("/ping").remote(remote).header(token).dispatch();
+        assert_eq!(response.status(), Status::TooManyRequests);
+    }
+
+    #[test]
+    fn test_missing_default_group_fails_to_launch() {
+        let config = RateLimitConfig {
+            store: StoreKind::Memory,
+            groups: BTreeMap::new(),
+        };
+        // Not `Config::figment()`, which would merge in Rocket.toml's groups.
+        let figment = Figment::from(rocket::Config::default()).merge(("rate_limit", config));
+        let rocket = rocket::custom(figment).attach(RateLimiter);
+        match Client::tracked(rocket) {
+            Err(e) => assert!(matches!(
+                e.kind(),
+                rocket::error::ErrorKind::FailedFairings(_)
+            )),
+            Ok(_) => panic!("rocket launched with an invalid rate_limit config"),
+        }
+    }
+}
@@ -0,0 +1,761 @@
+//! Task reminders.
+//!
+//! Once a task's `remind_at` has passed, the scheduler started by the
+//! [`Reminders`] fairing sends it through every [`Notifier`] named in the
+//! `reminders` config table, then clears it so it goes out once. Reminders
+//! are checked every `interval` seconds, so they may arrive that much late.
+//! A notifier that fails tries again on the next check, without the others
+//! sending the reminder twice.
+
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use reqwest::Url;
+use rocket::{
+    fairing::{self, Fairing, Info, Kind},
+    serde::{Deserialize, Serialize},
+    Build, Orbit, Rocket,
+};
+use tokio::time::Instant;
+
+use crate::db::{Task, DB};
+use crate::error::Error;
+use crate::notifications::reminder_message;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum NotifierKind {
+    /// Listed by `GET /notifications`.
+    InApp,
+    Email,
+    Webhook,
+}
+
+/// How the email notifier secures its connection to the relay.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum EmailTls {
+    /// Upgrades to TLS with STARTTLS, on port 587 unless told otherwise,
+    /// and fails if the relay can't.
+    #[default]
+    StartTls,
+    /// TLS from the start, on port 465 unless told otherwise.
+    Tls,
+    /// Plain text, on port 25 unless told otherwise. Only for relays on a
+    /// trusted network, such as a local Postfix or a development sink.
+    None,
+}
+
+/// The SMTP relay reminders are mailed through.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct EmailConfig {
+    pub host: String,
+    /// Defaults to the usual port for `tls`.
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub tls: EmailTls,
+    /// Login for relays that need one, which is only sent over TLS.
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    pub from: String,
+    /// The user whose reminders are mailed to `to`. Only tasks assigned to
+    /// them are, so reminders from workspaces they are not in stay there.
+    pub user: String,
+    pub to: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct WebhookConfig {
+    pub url: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct ReminderConfig {
+    /// Seconds between checks for due reminders.
+    pub interval: u64,
+    pub notifiers: Vec<NotifierKind>,
+    /// Required by the `email` notifier.
+    pub email: Option<EmailConfig>,
+    /// Required by the `webhook` notifier.
+    pub webhook: Option<WebhookConfig>,
+}
+
+impl Default for ReminderConfig {
+    fn default() -> Self {
+        ReminderConfig {
+            interval: 60,
+            notifiers: vec![NotifierKind::InApp],
+            email: None,
+            webhook: None,
+        }
+    }
+}
+
+#[rocket::async_trait]
+pub trait Notifier: Send + Sync {
+    /// Tells notifiers apart when noting which ones sent a reminder.
+    fn name(&self) -> &str;
+
+    async fn notify(&self, task: &Task) -> Result<(), Error>;
+}
+
+/// Keeps the reminder as a [`Notification`](crate::notifications::Notification).
+pub struct InAppNotifier {
+    db: DB,
+}
+
+impl InAppNotifier {
+    pub fn new(db: DB) -> Self {
+        InAppNotifier { db }
+    }
+}
+
+#[rocket::async_trait]
+impl Notifier for InAppNotifier {
+    fn name(&self) -> &str {
+        "in_app"
+    }
+
+    async fn notify(&self, task: &Task) -> Result<(), Error> {
+        self.db.add_notification(task).await.map(drop)
+    }
+}
+
+/// What the webhook notifier posts, as JSON.
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ReminderPayload<'a> {
+    event: &'static str,
+    message: String,
+    task: &'a Task,
+}
+
+/// Posts the reminder as JSON to a URL. Any status but `2xx` is a failure.
+pub struct WebhookNotifier {
+    url: Url,
+    client: reqwest::Client,
+}
+
+impl WebhookNotifier {
+    pub fn new(config: WebhookConfig) -> Result<Self, Error> {
+        let url = Url::parse(&config.url)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .ok_or_else(|| Error::Notify(format!("invalid webhook url {:?}", config.url)))?;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|e| Error::Notify(e.to_string()))?;
+        Ok(WebhookNotifier { url, client })
+    }
+}
+
+#[rocket::async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn notify(&self, task: &Task) -> Result<(), Error> {
+        let payload = ReminderPayload {
+            event: "reminder",
+            message: reminder_message(task),
+            task,
+        };
+        let body =
+            rocket::serde::json::to_string(&payload).map_err(|e| Error::Notify(e.to_string()))?;
+        let response = self
+            .client
+            .post(self.url.clone())
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| Error::Notify(e.to_string()))?;
+        match response.status().is_success() {
+            true => Ok(()),
+            false => Err(Error::Notify(format!(
+                "webhook answered {}",
+                response.status()
+            ))),
+        }
+    }
+}
+
+/// Mails the reminder as plain text, with the message as its subject, if
+/// the task is assigned to the configured user.
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    user: String,
+    to: Vec<Mailbox>,
+}
+
+impl EmailNotifier {
+    pub fn new(config: EmailConfig) -> Result<Self, Error> {
+        let mailbox = |address: &String| {
+            address
+                .parse::<Mailbox>()
+                .map_err(|_| Error::Notify(format!("invalid email address {address:?}")))
+        };
+        let from = mailbox(&config.from)?;
+        let to = config
+            .to
+            .iter()
+            .map(mailbox)
+            .collect::<Result<Vec<_>, _>>()?;
+        if to.is_empty() {
+            return Err(Error::Notify(
+                "email needs at least one recipient".to_string(),
+            ));
+        }
+
+        let smtp_error = |e: lettre::transport::smtp::Error| Error::Notify(e.to_string());
+        let (builder, port) = match config.tls {
+            EmailTls::StartTls => (
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+                    .map_err(smtp_error)?,
+                587,
+            ),
+            EmailTls::Tls => (
+                AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host).map_err(smtp_error)?,
+                465,
+            ),
+            EmailTls::None => (
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+                25,
+            ),
+        };
+        let mut builder = builder
+            .port(config.port.unwrap_or(port))
+            .timeout(Some(Duration::from_secs(30)));
+        match (config.username, config.password, config.tls) {
+            (None, None, _) => {}
+            (Some(_), Some(_), EmailTls::None) => {
+                return Err(Error::Notify(
+                    "email logins are only sent over TLS".to_string(),
+                ))
+            }
+            (Some(username), Some(password), _) => {
+                builder = builder.credentials(Credentials::new(username, password));
+            }
+            _ => {
+                return Err(Error::Notify(
+                    "email needs both a username and a password".to_string(),
+                ))
+            }
+        }
+        Ok(EmailNotifier {
+            transport: builder.build(),
+            from,
+            user: config.user,
+            to,
+        })
+    }
+
+    fn message(&self, subject: &str, body: &str, date: DateTime<Utc>) -> Result<Message, Error> {
+        let builder = self
+            .to
+            .iter()
+            .fold(Message::builder().from(self.from.clone()), |builder, to| {
+                builder.to(to.clone())
+            });
+        builder
+            .subject(subject)
+            .date(date.into())
+            .header(ContentType::TEXT_PLAIN)
+            .body(body.to_string())
+            .map_err(|e| Error::Notify(e.to_string()))
+    }
+}
+
+#[rocket::async_trait]
+impl Notifier for EmailNotifier {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    async fn notify(&self, task: &Task) -> Result<(), Error> {
+        // Nothing to send counts as sent, so it isn't tried again.
+        if task.assignee.as_deref() != Some(self.user.as_str()) {
+            return Ok(());
+        }
+        let text = reminder_message(task);
+        let message = self.message(&text, &text, Utc::now())?;
+        self.transport
+            .send(message)
+            .await
+            .map(drop)
+            .map_err(|e| Error::Notify(e.to_string()))
+    }
+}
+
+/// Sends every reminder due by `now` and returns how many were sent. A
+/// reminder is cleared once every notifier has sent it. Until then it stays
+/// due, and only the notifiers that have not sent it yet try again on the
+/// next check.
+pub async fn send_reminders(
+    db: &DB,
+    notifiers: &[Box<dyn Notifier>],
+    now: DateTime<Utc>,
+) -> Result<usize, Error> {
+    let mut sent = 0;
+    for task in db.due_reminders(now).await? {
+        match send_reminder(db, notifiers, &task).await {
+            Ok(true) => sent += 1,
+            Ok(false) => {}
+            Err(e) => warn!("failed to send reminder for {}: {}", task.id, e),
+        }
+    }
+    Ok(sent)
+}
+
+/// Sends one reminder through the notifiers that have not sent it yet, and
+/// returns whether all of them now have.
+async fn send_reminder(
+    db: &DB,
+    notifiers: &[Box<dyn Notifier>],
+    task: &Task,
+) -> Result<bool, Error> {
+    let sent_by = db.reminder_sent_by(task).await?;
+    let mut done = true;
+    for notifier in notifiers {
+        if sent_by.iter().any(|name| name == notifier.name()) {
+            continue;
+        }
+        let sent = match notifier.notify(task).await {
+            Ok(()) => db.mark_reminder_sent(task, notifier.name()).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = sent {
+            warn!(
+                "{} failed to send reminder for {}: {}",
+                notifier.name(),
+                task.id,
+                e
+            );
+            done = false;
+        }
+    }
+    if done {
+        db.clear_reminder(task).await?;
+    }
+    Ok(done)
+}
+
+/// The notifiers and how often to check, managed by [`Reminders`].
+pub struct Scheduler {
+    interval: Duration,
+    notifiers: Arc<[Box<dyn Notifier>]>,
+}
+
+impl Scheduler {
+    /// Runs the check the scheduler makes every `interval`.
+    #[cfg(test)]
+    pub async fn send_due(&self, db: &DB, now: DateTime<Utc>) -> Result<usize, Error> {
+        send_reminders(db, &self.notifiers, now).await
+    }
+}
+
+/// Reads the `reminders` config at ignition and starts the scheduler at
+/// liftoff. Its first check is one interval later, and it stops when the
+/// server shuts down.
+pub struct Reminders;
+
+#[rocket::async_trait]
+impl Fairing for Reminders {
+    fn info(&self) -> Info {
+        Info {
+            name: "Reminders",
+            kind: Kind::Ignite | Kind::Liftoff,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let config = match rocket.figment().find_value("reminders") {
+            Ok(_) => rocket
+                .figment()
+                .extract_inner::<ReminderConfig>("reminders")
+                .map_err(|e| e.to_string()),
+            Err(_) => Ok(ReminderConfig::default()),
+        };
+        let db = rocket.state::<DB>().cloned();
+        let scheduler = match (config, db) {
+            (Ok(config), Some(db)) => scheduler(config, db),
+            (Err(e), _) => Err(e),
+            (_, None) => Err("reminders need the database".to_string()),
+        };
+        match scheduler {
+            Ok(scheduler) => Ok(rocket.manage(scheduler)),
+            Err(e) => {
+                error!("invalid reminders config: {}", e);
+                Err(rocket)
+            }
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let (Some(db), Some(scheduler)) = (rocket.state::<DB>(), rocket.state::<Scheduler>())
+        else {
+            return;
+        };
+        if scheduler.notifiers.is_empty() {
+            return;
+        }
+        let db = db.clone();
+        let notifiers = scheduler.notifiers.clone();
+        let interval = scheduler.interval;
+        let mut ticks = tokio::time::interval_at(Instant::now() + interval, interval);
+        let shutdown = rocket.shutdown();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = ticks.tick() => {}
+                    _ = shutdown.clone() => break,
+                }
+                if let Err(e) = send_reminders(&db, &notifiers, Utc::now()).await {
+                    warn!("failed to check reminders: {}", e);
+                }
+            }
+        });
+    }
+}
+
+fn scheduler(config: ReminderConfig, db: DB) -> Result<Scheduler, String> {
+    if config.interval == 0 {
+        return Err("`interval` must be at least 1 second".to_string());
+    }
+    let mut notifiers: Vec<Box<dyn Notifier>> = vec![];
+    for kind in &config.notifiers {
+        let notifier: Box<dyn Notifier> = match kind {
+            NotifierKind::InApp => Box::new(InAppNotifier::new(db.clone())),
+            NotifierKind::Email => {
+                let email = config
+                    .email
+                    .clone()
+                    .ok_or("the `email` notifier needs an `email` table")?;
+                Box::new(EmailNotifier::new(email).map_err(|e| e.to_string())?)
+            }
+            NotifierKind::Webhook => {
+                let webhook = config
+                    .webhook
+                    .clone()
+                    .ok_or("the `webhook` notifier needs a `webhook` table")?;
+                Box::new(WebhookNotifier::new(webhook).map_err(|e| e.to_string())?)
+            }
+        };
+        notifiers.push(notifier);
+    }
+    Ok(Scheduler {
+        interval: Duration::from_secs(config.interval),
+        notifiers: notifiers.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::ANONYMOUS;
+    use crate::db::UpdateTask;
+    use std::sync::Mutex;
+    use surrealdb::{dbs::Session, kvs::Datastore};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+        sync::{mpsc, oneshot},
+    };
+
+    async fn db() -> DB {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        DB { ds, sesh }
+    }
+
+    fn task(title: &str) -> Task {
+        Task {
+            id: "tasks:abc".to_string(),
+            title: title.to_string(),
+            completed: false,
+            created_at: "2023-04-02T10:00:00Z".parse().unwrap(),
+            due: None,
+            priority: None,
+            remind_at: Some("2023-04-02T11:00:00Z".parse().unwrap()),
+            rank: "i".to_string(),
+            status: "todo".to_string(),
+            workspace: None,
+            assignee: None,
+        }
+    }
+
+    /// A mail sink that accepts one message and sends back the commands and
+    /// data it received.
+    async fn smtp_sink() -> (u16, oneshot::Receiver<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = oneshot::channel();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut received = vec![];
+            stream.get_mut().write_all(b"220 sink\r\n").await.unwrap();
+            let mut in_data = false;
+            let mut line = String::new();
+            loop {
+                line.clear();
+                if stream.read_line(&mut line).await.unwrap() == 0 {
+                    break;
+                }
+                let command = line.trim_end_matches("\r\n").to_string();
+                let reply: &[u8] = match command.as_str() {
+                    "." if in_data => {
+                        in_data = false;
+                        b"250 queued\r\n"
+                    }
+                    _ if in_data => b"",
+                    "DATA" => {
+                        in_data = true;
+                        b"354 go ahead\r\n"
+                    }
+                    "QUIT" => b"221 bye\r\n",
+                    c if c.starts_with("EHLO") => b"250-sink\r\n250 8BITMIME\r\n",
+                    _ => b"250 ok\r\n",
+                };
+                received.push(command);
+                stream.get_mut().write_all(reply).await.unwrap();
+            }
+            tx.send(received).unwrap();
+        });
+        (port, rx)
+    }
+
+    fn email_config(port: u16) -> EmailConfig {
+        EmailConfig {
+            host: "127.0.0.1".to_string(),
+            port: Some(port),
+            tls: EmailTls::None,
+            username: None,
+            password: None,
+            from: "todo@example.com".to_string(),
+            user: "user:ada".to_string(),
+            to: vec!["ada@example.com".to_string(), "bob@example.com".to_string()],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_email_notifier() {
+        let (port, received) = smtp_sink().await;
+        let notifier = EmailNotifier::new(email_config(port)).unwrap();
+        let task = Task {
+            assignee: Some("user:ada".to_string()),
+            ..task("call mom")
+        };
+        notifier.notify(&task).await.unwrap();
+
+        let received = received.await.unwrap();
+        assert!(received[0].starts_with("EHLO "));
+        assert!(received[1].starts_with("MAIL FROM:<todo@example.com>"));
+        assert_eq!(received[2], "RCPT TO:<ada@example.com>");
+        assert_eq!(received[3], "RCPT TO:<bob@example.com>");
+        assert_eq!(received[4], "DATA");
+        assert!(received.contains(&"To: ada@example.com, bob@example.com".to_string()));
+        assert!(received.contains(&"Subject: Reminder: call mom".to_string()));
+        assert!(received.contains(&"Reminder: call mom".to_string()));
+        assert!(received.contains(&".".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_email_notifier_skips_other_users_tasks() {
+        // Nothing listens on port 1, so mailing would fail.
+        let notifier = EmailNotifier::new(email_config(1)).unwrap();
+        notifier.notify(&task("unassigned")).await.unwrap();
+        let task = Task {
+            assignee: Some("user:bob".to_string()),
+            workspace: Some("workspaces:private".to_string()),
+            ..task("someone else's")
+        };
+        notifier.notify(&task).await.unwrap();
+    }
+
+    #[test]
+    fn test_email_message() {
+        let notifier = EmailNotifier::new(email_config(25)).unwrap();
+        let date = "2023-04-02T10:00:00Z".parse().unwrap();
+        let message = notifier.message("café", "one\n.two", date).unwrap();
+        let message = String::from_utf8(message.formatted()).unwrap();
+        assert!(message.contains("\r\nSubject: =?utf-8?"));
+        assert!(message.contains("\r\nDate: Sun, 02 Apr 2023 10:00:00 +0000\r\n"));
+        assert!(message.contains("Content-Type: text/plain; charset=utf-8\r\n"));
+
+        let config = EmailConfig {
+            to: vec!["ada@example.com\r\nBcc: eve@example.com".to_string()],
+            ..email_config(25)
+        };
+        assert!(EmailNotifier::new(config).is_err());
+        let config = EmailConfig {
+            username: Some("todo".to_string()),
+            password: Some("secret".to_string()),
+            ..email_config(25)
+        };
+        assert!(EmailNotifier::new(config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_webhook_notifier() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hooks/todo", listener.local_addr().unwrap());
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = vec![];
+                let mut buf = [0; 4096];
+                // The body is small JSON, so it ends with its closing brace.
+                while !request.ends_with(b"}") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let request = String::from_utf8(request).unwrap();
+                let status = match request.contains("\"title\":\"fail\"") {
+                    true => "500 Internal Server Error",
+                    false => "204 No Content",
+                };
+                let response = format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\n\r\n");
+                stream.write_all(response.as_bytes()).await.unwrap();
+                tx.send(request).unwrap();
+            }
+        });
+
+        let notifier = WebhookNotifier::new(WebhookConfig { url }).unwrap();
+        notifier.notify(&task("call mom")).await.unwrap();
+        let request = rx.recv().await.unwrap();
+        assert!(request.starts_with("POST /hooks/todo HTTP/1.1\r\n"));
+        assert!(request.contains("content-type: application/json\r\n"));
+        assert!(request.contains(
+            r#"{"event":"reminder","message":"Reminder: call mom","task":{"id":"tasks:abc""#
+        ));
+
+        assert!(notifier.notify(&task("fail")).await.is_err());
+        let config = WebhookConfig {
+            url: "ftp://example.com".to_string(),
+        };
+        assert!(WebhookNotifier::new(config).is_err());
+    }
+
+    /// Remembers the tasks it was asked about, and fails when told to.
+    #[derive(Clone, Default)]
+    struct Recorder {
+        name: &'static str,
+        tasks: Arc<Mutex<Vec<String>>>,
+        fail: bool,
+    }
+
+    #[rocket::async_trait]
+    impl Notifier for Recorder {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        async fn notify(&self, task: &Task) -> Result<(), Error> {
+            self.tasks.lock().unwrap().push(task.title.clone());
+            match self.fail {
+                true => Err(Error::Notify("down".to_string())),
+                false => Ok(()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_reminders() {
+        let db = db().await;
+        let task = db
+            .add_task("call mom".to_string(), "user:ada")
+            .await
+            .unwrap();
+        let update = UpdateTask {
+            remind_at: Some(Some("2023-04-02T09:00:00Z".parse().unwrap())),
+            ..Default::default()
+        };
+        db.update_task(task.id.clone(), update, ANONYMOUS)
+            .await
+            .unwrap();
+        let now = "2023-04-02T10:00:00Z".parse().unwrap();
+
+        // Reminders that some notifier could not send stay due.
+        let down = Recorder {
+            name: "down",
+            fail: true,
+            ..Default::default()
+        };
+        let up = Recorder {
+            name: "up",
+            ..Default::default()
+        };
+        let notifiers: Vec<Box<dyn Notifier>> = vec![
+            Box::new(down.clone()),
+            Box::new(up.clone()),
+            Box::new(InAppNotifier::new(db.clone())),
+        ];
+        assert_eq!(send_reminders(&db, &notifiers, now).await.unwrap(), 0);
+        assert_eq!(*up.tasks.lock().unwrap(), ["call mom"]);
+        let unread = db.get_notifications("user:ada", true).await.unwrap();
+        assert_eq!(unread.len(), 1);
+
+        // Only the notifier that failed tries again.
+        assert_eq!(send_reminders(&db, &notifiers, now).await.unwrap(), 0);
+        assert_eq!(down.tasks.lock().unwrap().len(), 2);
+        assert_eq!(up.tasks.lock().unwrap().len(), 1);
+
+        let back = Recorder {
+            fail: false,
+            ..down.clone()
+        };
+        let notifiers: Vec<Box<dyn Notifier>> = vec![
+            Box::new(back),
+            Box::new(up.clone()),
+            Box::new(InAppNotifier::new(db.clone())),
+        ];
+        assert_eq!(send_reminders(&db, &notifiers, now).await.unwrap(), 1);
+        assert_eq!(down.tasks.lock().unwrap().len(), 3);
+        assert_eq!(up.tasks.lock().unwrap().len(), 1);
+        let unread = db.get_notifications("user:ada", true).await.unwrap();
+        assert_eq!(unread.len(), 1);
+        assert_eq!(db.get_task(task.id).await.unwrap().remind_at, None);
+
+        // Each reminder goes out once.
+        assert_eq!(send_reminders(&db, &notifiers, now).await.unwrap(), 0);
+        assert_eq!(up.tasks.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_config() {
+        let config = ReminderConfig {
+            notifiers: vec![NotifierKind::Email],
+            ..Default::default()
+        };
+        let db = db().await;
+        assert!(scheduler(config, db.clone()).is_err());
+
+        let config = ReminderConfig {
+            interval: 0,
+            ..Default::default()
+        };
+        assert!(scheduler(config, db.clone()).is_err());
+        assert_eq!(
+            scheduler(ReminderConfig::default(), db)
+                .unwrap()
+                .notifiers
+                .len(),
+            1
+        );
+    }
+}
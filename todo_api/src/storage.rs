@@ -0,0 +1,483 @@
+//! Where attachment contents are kept.
+//!
+//! Routes reach the store through the [`Files`] state, which the
+//! [`FileStorage`] fairing opens from the `attachments` config table: a
+//! directory on local disk, an S3-compatible bucket, or memory for tests.
+//! Keys are the id part of an attachment's record id.
+
+use std::{collections::HashMap, path::PathBuf, sync::Mutex};
+
+use chrono::{DateTime, Utc};
+use reqwest::{Method, StatusCode, Url};
+use rocket::{
+    fairing::{self, Fairing, Info, Kind},
+    serde::{Deserialize, Serialize},
+    Build, Rocket,
+};
+use sha2::{Digest, Sha256};
+
+use crate::error::Error;
+use crate::utils::crypto::{hex, hmac};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum StorageKind {
+    Local,
+    S3,
+    /// Keeps nothing across restarts, like the `memory` datastore.
+    Memory,
+}
+
+/// Credentials and location of an S3-compatible bucket. Objects are
+/// addressed path-style, as `{endpoint}/{bucket}/{key}`, which MinIO and
+/// other stand-ins support too.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct S3Config {
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default = "default_region")]
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+}
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+/// Upload sizes are capped by the `file` and `data-form` entries of the
+/// `limits` table instead, which Rocket enforces while reading the upload.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct AttachmentConfig {
+    pub storage: StorageKind,
+    /// Directory for `local` storage, created if it is missing.
+    pub dir: PathBuf,
+    /// Required for `s3` storage.
+    pub s3: Option<S3Config>,
+}
+
+impl Default for AttachmentConfig {
+    fn default() -> Self {
+        AttachmentConfig {
+            storage: StorageKind::Local,
+            dir: PathBuf::from("attachments"),
+            s3: None,
+        }
+    }
+}
+
+#[rocket::async_trait]
+pub trait Storage: Send + Sync {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), Error>;
+
+    /// Returns `None` when nothing is stored under `key`.
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Deleting a missing key is not an error.
+    async fn delete(&self, key: &str) -> Result<(), Error>;
+}
+
+/// Record ids are generated by the database, so anything else is refused
+/// rather than turned into a path or URL.
+fn check_key(key: &str) -> Result<(), Error> {
+    let valid = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    match valid {
+        true => Ok(()),
+        false => Err(Error::Storage(format!("invalid key {key:?}"))),
+    }
+}
+
+/// One file per key in a directory.
+pub struct LocalStorage {
+    dir: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        LocalStorage { dir: dir.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, Error> {
+        check_key(key)?;
+        Ok(self.dir.join(key))
+    }
+}
+
+#[rocket::async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), Error> {
+        Ok(tokio::fs::write(self.path(key)?, bytes).await?)
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        match tokio::fs::read(self.path(key)?).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryStorage {
+    files: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    fn files(&self) -> std::sync::MutexGuard<'_, HashMap<String, Vec<u8>>> {
+        self.files.lock().expect("attachment files poisoned")
+    }
+}
+
+#[rocket::async_trait]
+impl Storage for MemoryStorage {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), Error> {
+        check_key(key)?;
+        self.files().insert(key.to_string(), bytes);
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.files().get(key).cloned())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.files().remove(key);
+        Ok(())
+    }
+}
+
+/// Objects in an S3-compatible bucket, with requests signed by AWS
+/// Signature Version 4.
+pub struct S3Storage {
+    config: S3Config,
+    endpoint: Url,
+    client: reqwest::Client,
+}
+
+impl S3Storage {
+    pub fn new(config: S3Config) -> Result<Self, Error> {
+        let endpoint = Url::parse(&config.endpoint)
+            .ok()
+            .filter(|url| url.has_host())
+            .ok_or_else(|| Error::Storage(format!("invalid endpoint {:?}", config.endpoint)))?;
+        Ok(S3Storage {
+            config,
+            endpoint,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    fn url(&self, key: &str) -> Result<Url, Error> {
+        check_key(key)?;
+        let mut url = self.endpoint.clone();
+        url.path_segments_mut()
+            .map_err(|_| Error::Storage("endpoint cannot be a base".to_string()))?
+            .pop_if_empty()
+            .push(&self.config.bucket)
+            .push(key);
+        Ok(url)
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        body: Vec<u8>,
+    ) -> Result<reqwest::Response, Error> {
+        let url = self.url(key)?;
+        let payload_hash = hex(&Sha256::digest(&body));
+        let (amz_date, authorization) =
+            self.authorization(&method, &url, &payload_hash, Utc::now());
+        self.client
+            .request(method, url)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", amz_date)
+            .header("authorization", authorization)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| Error::Storage(e.to_string()))
+    }
+
+    /// Signs a request with the `host`, `x-amz-content-sha256` and
+    /// `x-amz-date` headers. Returns the date header and the
+    /// `Authorization` header.
+    fn authorization(
+        &self,
+        method: &Method,
+        url: &Url,
+        payload_hash: &str,
+        now: DateTime<Utc>,
+    ) -> (String, String) {
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = &amz_date[..8];
+        let host = match url.port() {
+            Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{method}\n{}\n{}\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{amz_date}\n\n{signed_headers}\n{payload_hash}",
+            url.path(),
+            url.query().unwrap_or_default(),
+        );
+        let scope = format!("{date}/{}/s3/aws4_request", self.config.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let key = signing_key(&self.config.secret_key, date, &self.config.region, "s3");
+        let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            self.config.access_key
+        );
+        (amz_date, authorization)
+    }
+}
+
+#[rocket::async_trait]
+impl Storage for S3Storage {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), Error> {
+        let response = self.send(Method::PUT, key, bytes).await?;
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(Error::Storage(format!("upload failed with {status}"))),
+        }
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let response = self.send(Method::GET, key, vec![]).await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => response
+                .bytes()
+                .await
+                .map(|bytes| Some(bytes.to_vec()))
+                .map_err(|e| Error::Storage(e.to_string())),
+            status => Err(Error::Storage(format!("download failed with {status}"))),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        let response = self.send(Method::DELETE, key, vec![]).await?;
+        match response.status() {
+            status if status.is_success() || status == StatusCode::NOT_FOUND => Ok(()),
+            status => Err(Error::Storage(format!("delete failed with {status}"))),
+        }
+    }
+}
+
+fn signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac(format!("AWS4{secret_key}").as_bytes(), date.as_bytes());
+    let key = hmac(&key, region.as_bytes());
+    let key = hmac(&key, service.as_bytes());
+    hmac(&key, b"aws4_request")
+}
+
+/// The attachment store, managed by [`FileStorage`].
+pub struct Files(pub Box<dyn Storage>);
+
+pub struct FileStorage;
+
+#[rocket::async_trait]
+impl Fairing for FileStorage {
+    fn info(&self) -> Info {
+        Info {
+            name: "Attachment storage",
+            kind: Kind::Ignite,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let config = match rocket.figment().find_value("attachments") {
+            Ok(_) => rocket
+                .figment()
+                .extract_inner::<AttachmentConfig>("attachments"),
+            Err(_) => Ok(AttachmentConfig::default()),
+        };
+        let storage = match config.map_err(|e| e.to_string()) {
+            Ok(config) => open(config).await,
+            Err(e) => Err(e),
+        };
+        match storage {
+            Ok(storage) => Ok(rocket.manage(Files(storage))),
+            Err(e) => {
+                error!("invalid attachments config: {}", e);
+                Err(rocket)
+            }
+        }
+    }
+}
+
+async fn open(config: AttachmentConfig) -> Result<Box<dyn Storage>, String> {
+    match config.storage {
+        StorageKind::Local => {
+            tokio::fs::create_dir_all(&config.dir)
+                .await
+                .map_err(|e| format!("cannot create {}: {e}", config.dir.display()))?;
+            Ok(Box::new(LocalStorage::new(config.dir)))
+        }
+        StorageKind::S3 => {
+            let s3 = config.s3.ok_or("`s3` storage needs an `s3` table")?;
+            Ok(Box::new(S3Storage::new(s3).map_err(|e| e.to_string())?))
+        }
+        StorageKind::Memory => Ok(Box::<MemoryStorage>::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    type Bucket = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    /// A local stand-in for S3 that keeps objects in memory. It refuses
+    /// unsigned requests and bodies that do not match their signed hash.
+    async fn stand_in() -> (String, Bucket) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let bucket = Bucket::default();
+        let objects = bucket.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, objects.clone()));
+            }
+        });
+        (endpoint, bucket)
+    }
+
+    async fn serve(mut stream: TcpStream, objects: Bucket) {
+        let mut request = vec![];
+        let mut buf = [0; 4096];
+        let head_len = loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+            if let Some(i) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                break i + 4;
+            }
+        };
+        let head = String::from_utf8(request[..head_len].to_vec()).unwrap();
+        let header = |name: &str| {
+            head.lines()
+                .filter_map(|line| line.split_once(": "))
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.to_string())
+        };
+        let len: usize = header("content-length").map_or(0, |len| len.parse().unwrap());
+        while request.len() < head_len + len {
+            let n = stream.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+        }
+        let body = request[head_len..].to_vec();
+
+        let mut request_line = head.split(' ');
+        let (method, path) = (request_line.next().unwrap(), request_line.next().unwrap());
+        let signed = header("authorization")
+            .is_some_and(|auth| auth.starts_with("AWS4-HMAC-SHA256 Credential=key/"))
+            && header("x-amz-content-sha256") == Some(hex(&Sha256::digest(&body)));
+        let (status, body) = match method {
+            _ if !signed => ("403 Forbidden", vec![]),
+            "PUT" => {
+                objects.lock().unwrap().insert(path.to_string(), body);
+                ("200 OK", vec![])
+            }
+            "GET" => match objects.lock().unwrap().get(path) {
+                Some(object) => ("200 OK", object.clone()),
+                None => ("404 Not Found", vec![]),
+            },
+            "DELETE" => {
+                objects.lock().unwrap().remove(path);
+                ("204 No Content", vec![])
+            }
+            _ => ("405 Method Not Allowed", vec![]),
+        };
+        let head = format!(
+            "HTTP/1.1 {status}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(head.as_bytes()).await.unwrap();
+        stream.write_all(&body).await.unwrap();
+    }
+
+    fn s3_config(endpoint: String) -> S3Config {
+        S3Config {
+            endpoint,
+            bucket: "attachments".to_string(),
+            region: default_region(),
+            access_key: "key".to_string(),
+            secret_key: "secret".to_string(),
+        }
+    }
+
+    async fn round_trip(storage: &dyn Storage) {
+        assert_eq!(storage.get("abc").await.unwrap(), None);
+        storage.put("abc", b"hello".to_vec()).await.unwrap();
+        assert_eq!(storage.get("abc").await.unwrap(), Some(b"hello".to_vec()));
+        storage.delete("abc").await.unwrap();
+        storage.delete("abc").await.unwrap();
+        assert_eq!(storage.get("abc").await.unwrap(), None);
+        assert!(storage.put("../abc", vec![1]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_local_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        round_trip(&LocalStorage::new(dir.path())).await;
+    }
+
+    #[tokio::test]
+    async fn test_s3_storage() {
+        let (endpoint, bucket) = stand_in().await;
+        let storage = S3Storage::new(s3_config(endpoint)).unwrap();
+        round_trip(&storage).await;
+
+        storage.put("def", b"hi".to_vec()).await.unwrap();
+        let objects = bucket.lock().unwrap();
+        assert_eq!(objects.get("/attachments/def"), Some(&b"hi".to_vec()));
+    }
+
+    #[test]
+    fn test_s3_signature() {
+        // Derivation example from the AWS Signature Version 4 documentation.
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex(&key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+
+        let storage = S3Storage::new(s3_config("http://localhost:9000".to_string())).unwrap();
+        let url = storage.url("abc").unwrap();
+        assert_eq!(url.as_str(), "http://localhost:9000/attachments/abc");
+        let now = "2023-04-02T10:00:00Z".parse().unwrap();
+        let (date, authorization) = storage.authorization(&Method::GET, &url, "UNSIGNED", now);
+        assert_eq!(date, "20230402T100000Z");
+        assert!(authorization.starts_with(
+            "AWS4-HMAC-SHA256 Credential=key/20230402/us-east-1/s3/aws4_request, \
+             SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature="
+        ));
+    }
+}
@@ -0,0 +1,130 @@
+use rocket::http::{ContentType, Status};
+use rocket::local::asynchronous::{Client, LocalResponse};
+
+use super::{client, client_with, create_task};
+use crate::attachments::Attachment;
+use crate::db::AffectedRows;
+
+/// Attaches `body` to the task as the `file` field of a multipart form.
+async fn upload<'c>(
+    client: &'c Client,
+    task: &str,
+    name: &str,
+    content_type: &str,
+    body: &str,
+) -> LocalResponse<'c> {
+    let form = format!(
+        "--BOUNDARY\r\n\
+         Content-Disposition: form-data; name=\"file\"; filename=\"{name}\"\r\n\
+         Content-Type: {content_type}\r\n\r\n\
+         {body}\r\n\
+         --BOUNDARY--\r\n"
+    );
+    client
+        .post(format!("/task/{task}/attachments"))
+        .header(ContentType::new("multipart", "form-data").with_params(("boundary", "BOUNDARY")))
+        .body(form)
+        .dispatch()
+        .await
+}
+
+#[rocket::async_test]
+async fn test_attachments() {
+    let client = client().await;
+    let task = create_task(&client, "report").await;
+
+    let response = upload(&client, &task.id, "notes.txt", "text/plain", "hello").await;
+    assert_eq!(response.status(), Status::Ok);
+    let attachment: Attachment = response.into_json().await.unwrap();
+    assert_eq!(attachment.name, "notes.txt");
+    assert_eq!(attachment.content_type, "text/plain");
+    assert_eq!(attachment.size, 5);
+
+    let response = client
+        .get(format!("/task/{}/attachments", task.id))
+        .dispatch()
+        .await;
+    let attachments: Vec<Attachment> = response.into_json().await.unwrap();
+    assert_eq!(attachments, std::slice::from_ref(&attachment));
+
+    let response = client
+        .get(format!("/attachment/{}", attachment.id))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::Plain));
+    assert_eq!(
+        response.headers().get_one("Content-Disposition"),
+        Some("attachment; filename=\"notes.txt\"; filename*=UTF-8''notes.txt")
+    );
+    assert_eq!(response.into_string().await.as_deref(), Some("hello"));
+
+    let response = client
+        .delete(format!("/attachment/{}", attachment.id))
+        .dispatch()
+        .await;
+    let res: AffectedRows = response.into_json().await.unwrap();
+    assert_eq!(res.affected_rows, 1);
+    let response = client
+        .get(format!("/attachment/{}", attachment.id))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn test_attachment_content_type_is_guessed() {
+    let client = client().await;
+    let task = create_task(&client, "report").await;
+
+    let response = upload(
+        &client,
+        &task.id,
+        "shot.png",
+        "application/octet-stream",
+        "png",
+    )
+    .await;
+    let attachment: Attachment = response.into_json().await.unwrap();
+    assert_eq!(attachment.content_type, "image/png");
+
+    let response = client
+        .get(format!("/attachment/{}", attachment.id))
+        .dispatch()
+        .await;
+    assert_eq!(response.content_type(), Some(ContentType::PNG));
+    assert!(response
+        .headers()
+        .get_one("Content-Disposition")
+        .is_some_and(|disposition| disposition.starts_with("inline")));
+}
+
+#[rocket::async_test]
+async fn test_attachment_limits() {
+    let client = client_with(|figment| figment.merge(("limits.file", 8))).await;
+    let task = create_task(&client, "report").await;
+
+    let response = upload(&client, &task.id, "big.txt", "text/plain", "0123456789").await;
+    assert_eq!(response.status(), Status::PayloadTooLarge);
+
+    let response = upload(&client, &task.id, "empty.txt", "text/plain", "").await;
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+
+    let response = upload(&client, "tasks:missing", "a.txt", "text/plain", "a").await;
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn test_deleting_a_task_deletes_its_attachments() {
+    let client = client().await;
+    let task = create_task(&client, "report").await;
+    let response = upload(&client, &task.id, "a.txt", "text/plain", "a").await;
+    let attachment: Attachment = response.into_json().await.unwrap();
+
+    client.delete(format!("/task/{}", task.id)).dispatch().await;
+    let response = client
+        .get(format!("/attachment/{}", attachment.id))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+}
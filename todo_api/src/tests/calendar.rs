@@ -0,0 +1,71 @@
+use rocket::http::{ContentType, Header, Status};
+use todo_models::CalendarFeed;
+
+use super::{client, create_task};
+use crate::db::AffectedRows;
+
+fn bearer(token: &str) -> Header<'static> {
+    Header::new("Authorization", format!("Bearer {token}"))
+}
+
+#[rocket::async_test]
+async fn test_calendar_feed() {
+    let client = client().await;
+    create_task(&client, "pay%20rent").await;
+
+    let response = client
+        .post("/calendar/feed")
+        .header(bearer("owner"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let feed: CalendarFeed = response.into_json().await.unwrap();
+    assert_eq!(feed.path, format!("/calendar/{}/tasks.ics", feed.token));
+
+    let response = client.get(feed.path.clone()).dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::Calendar));
+    let body = response.into_string().await.unwrap();
+    assert!(body.starts_with("BEGIN:VCALENDAR\r\n"));
+    assert!(body.contains("SUMMARY:pay rent\r\n"));
+
+    let response = client
+        .delete(format!("/calendar/feed/{}", feed.token))
+        .header(bearer("other"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let response = client
+        .delete(format!("/calendar/feed/{}", feed.token))
+        .header(bearer("owner"))
+        .dispatch()
+        .await;
+    let res: AffectedRows = response.into_json().await.unwrap();
+    assert_eq!(res.affected_rows, 1);
+
+    let response = client.get(feed.path).dispatch().await;
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn test_anonymous_calendar_feed() {
+    let client = client().await;
+    let response = client.post("/calendar/feed").dispatch().await;
+    assert_eq!(response.status(), Status::Forbidden);
+}
+
+#[rocket::async_test]
+async fn test_unknown_calendar_feed() {
+    let client = client().await;
+    let response = client.get("/calendar/guess/tasks.ics").dispatch().await;
+    assert_eq!(response.status(), Status::NotFound);
+
+    let response = client
+        .delete("/calendar/feed/guess")
+        .header(bearer("owner"))
+        .dispatch()
+        .await;
+    let res: AffectedRows = response.into_json().await.unwrap();
+    assert_eq!(res.affected_rows, 0);
+}
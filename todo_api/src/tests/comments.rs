@@ -0,0 +1,102 @@
+use rocket::http::{ContentType, Header, Status};
+use todo_models::ValidationErrors;
+
+use super::{client, create_task};
+use crate::comments::Comment;
+use crate::db::AffectedRows;
+
+#[rocket::async_test]
+async fn test_comments() {
+    let client = client().await;
+    let task = create_task(&client, "discuss").await;
+
+    let response = client
+        .post(format!("/task/{}/comments", task.id))
+        .header(ContentType::JSON)
+        .header(Header::new("Authorization", "Bearer secret"))
+        .body(r#"{"body":"  first\nthought "}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let first: Comment = response.into_json().await.unwrap();
+    assert_eq!(first.body, "first\nthought");
+    assert!(first.author.starts_with("user:"));
+
+    let response = client
+        .post(format!("/task/{}/comments", task.id))
+        .header(ContentType::JSON)
+        .body(format!(r#"{{"body":"reply","parent":"{}"}}"#, first.id))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let response = client
+        .get(format!("/task/{}/comments", task.id))
+        .dispatch()
+        .await;
+    let comments: Vec<Comment> = response.into_json().await.unwrap();
+    assert_eq!(comments.len(), 2);
+    assert_eq!(comments[1].parent.as_deref(), Some(first.id.as_str()));
+
+    // Someone else may not edit or delete the comment.
+    let edit = r#"{"body":"edited"}"#;
+    let response = client
+        .put(format!("/comment/{}", first.id))
+        .header(ContentType::JSON)
+        .body(edit)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client
+        .put(format!("/comment/{}", first.id))
+        .header(ContentType::JSON)
+        .header(Header::new("Authorization", "Bearer secret"))
+        .body(edit)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let edited: Comment = response.into_json().await.unwrap();
+    assert_eq!(edited.body, "edited");
+
+    let response = client
+        .delete(format!("/comment/{}", first.id))
+        .header(Header::new("Authorization", "Bearer secret"))
+        .dispatch()
+        .await;
+    let res: AffectedRows = response.into_json().await.unwrap();
+    assert_eq!(res.affected_rows, 1);
+}
+
+#[rocket::async_test]
+async fn test_invalid_comments() {
+    let client = client().await;
+    let task = create_task(&client, "discuss").await;
+
+    let response = client
+        .post(format!("/task/{}/comments", task.id))
+        .header(ContentType::JSON)
+        .body(r#"{"body":" "}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    let errors: ValidationErrors = response.into_json().await.unwrap();
+    assert!(errors.message("body").is_some());
+
+    let response = client
+        .post(format!("/task/{}/comments", task.id))
+        .header(ContentType::JSON)
+        .body(r#"{"body":"reply","parent":"comments:missing"}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+
+    let response = client.get("/task/tasks:missing/comments").dispatch().await;
+    assert_eq!(response.status(), Status::NotFound);
+    let response = client
+        .put("/comment/tasks:abc")
+        .header(ContentType::JSON)
+        .body(r#"{"body":"edited"}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+}
@@ -0,0 +1,70 @@
+use rocket::http::{Header, Status};
+
+use super::{client, ORIGIN};
+
+#[rocket::async_test]
+async fn test_cors_headers() {
+    let client = client().await;
+    let response = client
+        .get("/tasks")
+        .header(Header::new("Origin", ORIGIN))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let headers = response.headers();
+    assert_eq!(headers.get_one("Access-Control-Allow-Origin"), Some(ORIGIN));
+    assert_eq!(headers.get_one("Vary"), Some("Origin"));
+
+    let response = client
+        .get("/tasks")
+        .header(Header::new("Origin", "http://other.test"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        response.headers().get_one("Access-Control-Allow-Origin"),
+        None
+    );
+}
+
+#[rocket::async_test]
+async fn test_preflight_for_every_route() {
+    let client = client().await;
+    let paths = [
+        ("/task/buy", "POST"),
+        ("/task/tasks:abc", "PUT"),
+        ("/task/tasks:abc", "PATCH"),
+        ("/task/tasks:abc", "DELETE"),
+        ("/tasks", "GET"),
+        ("/export?format=csv", "GET"),
+        ("/import?format=csv", "POST"),
+        ("/calendar/feed", "POST"),
+        ("/calendar/feed/token", "DELETE"),
+        ("/calendar/token/tasks.ics", "GET"),
+    ];
+    for (path, method) in paths {
+        let response = client
+            .options(path)
+            .header(Header::new("Origin", ORIGIN))
+            .header(Header::new("Access-Control-Request-Method", method))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NoContent, "{path}");
+        let headers = response.headers();
+        assert_eq!(headers.get_one("Access-Control-Allow-Origin"), Some(ORIGIN));
+        let methods = headers.get_one("Access-Control-Allow-Methods").unwrap();
+        assert!(methods.contains(method), "{path}: {methods}");
+    }
+}
+
+#[rocket::async_test]
+async fn test_options_on_unknown_path() {
+    let client = client().await;
+    let response = client
+        .options("/nowhere")
+        .header(Header::new("Origin", ORIGIN))
+        .header(Header::new("Access-Control-Request-Method", "GET"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+}
@@ -0,0 +1,53 @@
+//! End-to-end tests of the HTTP routes. Every test builds the server through
+//! [`client`], so it gets its own in-memory datastore.
+
+use rocket::{figment::Figment, http::Status, local::asynchronous::Client};
+
+use crate::db::Task;
+use crate::rate_limit::RateLimitConfig;
+
+mod attachments;
+mod calendar;
+mod comments;
+mod cors;
+mod notifications;
+mod tasks;
+mod tokens;
+mod transfer;
+mod webhooks;
+mod workspaces;
+
+/// The only origin allowed by the test server's CORS policy.
+pub const ORIGIN: &str = "http://app.test";
+
+pub async fn client() -> Client {
+    client_with(|figment| figment).await
+}
+
+/// Builds a client after letting `configure` override the test config,
+/// which has rate limits high enough not to get in the way.
+pub async fn client_with(configure: impl FnOnce(Figment) -> Figment) -> Client {
+    let mut rate_limit = RateLimitConfig::default();
+    for limit in rate_limit.groups.values_mut() {
+        limit.burst = 10_000;
+    }
+    let figment = rocket::Config::figment()
+        .merge(("datastore", "memory"))
+        .merge(("attachments.storage", "memory"))
+        .merge(("log_level", "off"))
+        .merge(("cors.allowed_origins", [ORIGIN]))
+        .merge(("cors.allow_credentials", false))
+        .merge(("rate_limit", rate_limit));
+
+    Client::tracked(crate::build(configure(figment)).await)
+        .await
+        .expect("valid rocket instance")
+}
+
+/// Creates a task through the API. `title` goes into the path, so it must
+/// be percent-encoded.
+pub async fn create_task(client: &Client, title: &str) -> Task {
+    let response = client.post(format!("/task/{title}")).dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    response.into_json().await.expect("task json")
+}
@@ -0,0 +1,114 @@
+use chrono::Utc;
+use rocket::http::{ContentType, Header, Status};
+use todo_models::ValidationErrors;
+
+use super::{client, create_task};
+use crate::db::{Task, DB};
+use crate::notifications::Notification;
+use crate::reminders::Scheduler;
+
+fn bearer(token: &str) -> Header<'static> {
+    Header::new("Authorization", format!("Bearer {token}"))
+}
+
+#[rocket::async_test]
+async fn test_reminder_notifications() {
+    let client = client().await;
+    let task = create_task(&client, "call%20mom").await;
+
+    // Changing the task makes ada one of the users it reminds.
+    let response = client
+        .put(format!("/task/{}", task.id))
+        .header(ContentType::JSON)
+        .header(bearer("ada"))
+        .body(r#"{"remind_at":"2023-04-02T09:00:00Z"}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let task: Task = response.into_json().await.unwrap();
+    assert!(task.remind_at.is_some());
+
+    // Runs the check the scheduler makes every `interval`.
+    let rocket = client.rocket();
+    let (db, scheduler) = (rocket.state::<DB>(), rocket.state::<Scheduler>());
+    let sent = scheduler
+        .unwrap()
+        .send_due(db.unwrap(), Utc::now())
+        .await
+        .unwrap();
+    assert_eq!(sent, 1);
+
+    let response = client.get(format!("/task/{}", task.id)).dispatch().await;
+    let task: Task = response.into_json().await.unwrap();
+    assert_eq!(task.remind_at, None);
+
+    let response = client
+        .get("/notifications?unread=true")
+        .header(bearer("ada"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let notifications: Vec<Notification> = response.into_json().await.unwrap();
+    assert_eq!(notifications.len(), 1);
+    assert_eq!(notifications[0].task, task.id);
+    assert_eq!(notifications[0].message, "Reminder: call mom");
+
+    let response = client.get("/notifications").dispatch().await;
+    let others: Vec<Notification> = response.into_json().await.unwrap();
+    assert!(others.is_empty());
+    let response = client
+        .put(format!("/notification/{}/read", notifications[0].id))
+        .header(bearer("bob"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+
+    let response = client
+        .put(format!("/notification/{}/read", notifications[0].id))
+        .header(bearer("ada"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let read: Notification = response.into_json().await.unwrap();
+    assert!(read.read);
+
+    let response = client
+        .get("/notifications?unread=true")
+        .header(bearer("ada"))
+        .dispatch()
+        .await;
+    let notifications: Vec<Notification> = response.into_json().await.unwrap();
+    assert!(notifications.is_empty());
+    let response = client
+        .get("/notifications")
+        .header(bearer("ada"))
+        .dispatch()
+        .await;
+    let notifications: Vec<Notification> = response.into_json().await.unwrap();
+    assert_eq!(notifications, [read]);
+}
+
+#[rocket::async_test]
+async fn test_invalid_reminders() {
+    let client = client().await;
+    let task = create_task(&client, "test").await;
+
+    let response = client
+        .put(format!("/task/{}", task.id))
+        .header(ContentType::JSON)
+        .body(r#"{"remind_at":"2100-01-01T00:00:00Z"}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    let errors: ValidationErrors = response.into_json().await.unwrap();
+    assert_eq!(
+        errors.message("remind_at"),
+        Some("reminders must be between the years 2000 and 2099")
+    );
+
+    let response = client
+        .put(format!("/notification/{}/read", task.id))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+}
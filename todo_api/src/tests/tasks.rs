@@ -0,0 +1,406 @@
+use rocket::http::{ContentType, Header, Status};
+use todo_models::{ValidationErrors, Workflow};
+
+use super::{client, client_with, create_task};
+use crate::comments::Comment;
+use crate::db::{AffectedRows, Task, TaskAction, TaskEvent};
+
+#[rocket::async_test]
+async fn test_add_and_get_task() {
+    let client = client().await;
+    let task = create_task(&client, "buy%20milk").await;
+    assert_eq!(task.title, "buy milk");
+    assert!(!task.completed);
+
+    let response = client.get(format!("/task/{}", task.id)).dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::JSON));
+    assert_eq!(response.into_json::<Task>().await, Some(task.clone()));
+
+    let response = client.get("/tasks").dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.into_json::<Vec<Task>>().await, Some(vec![task]));
+}
+
+#[rocket::async_test]
+async fn test_add_task_cleans_title() {
+    let client = client().await;
+    let task = create_task(&client, "%20%20call%09mom%07%20").await;
+    assert_eq!(task.title, "call mom");
+}
+
+#[rocket::async_test]
+async fn test_add_task_rejects_invalid_title() {
+    let client = client().await;
+    for title in ["%20%20%20", "%07", &"a".repeat(300)] {
+        let response = client.post(format!("/task/{title}")).dispatch().await;
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+        let errors: ValidationErrors = response.into_json().await.unwrap();
+        assert!(errors.message("title").is_some());
+    }
+
+    let response = client.get("/tasks").dispatch().await;
+    assert_eq!(response.into_json::<Vec<Task>>().await, Some(vec![]));
+}
+
+#[rocket::async_test]
+async fn test_get_missing_task() {
+    let client = client().await;
+    let response = client.get("/task/tasks:missing").dispatch().await;
+    assert_eq!(response.status(), Status::NotFound);
+
+    // Ids of other tables are not tasks either.
+    let task = create_task(&client, "test").await;
+    let response = client
+        .post(format!("/task/{}/comments", task.id))
+        .header(ContentType::JSON)
+        .body(r#"{"body":"hello"}"#)
+        .dispatch()
+        .await;
+    let comment: Comment = response.into_json().await.unwrap();
+    let response = client.get(format!("/task/{}", comment.id)).dispatch().await;
+    assert_eq!(response.status(), Status::NotFound);
+    let response = client.patch("/task/tasks:missing").dispatch().await;
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn test_update_task() {
+    let client = client().await;
+    let task = create_task(&client, "draft").await;
+
+    let response = client
+        .put(format!("/task/{}", task.id))
+        .header(ContentType::JSON)
+        .body(r#"{"title":" final ","completed":true,"due":"2023-05-01T09:00:00Z","priority":2}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let updated: Task = response.into_json().await.unwrap();
+    assert_eq!(updated.id, task.id);
+    assert_eq!(updated.title, "final");
+    assert!(updated.completed);
+    assert_eq!(updated.due, Some("2023-05-01T09:00:00Z".parse().unwrap()));
+    assert_eq!(updated.priority, Some(2));
+    assert_eq!(updated.created_at, task.created_at);
+
+    let response = client
+        .put(format!("/task/{}", task.id))
+        .header(ContentType::JSON)
+        .body(r#"{"due":null,"priority":null}"#)
+        .dispatch()
+        .await;
+    let cleared: Task = response.into_json().await.unwrap();
+    assert_eq!((cleared.due, cleared.priority), (None, None));
+    assert_eq!(cleared.title, "final");
+}
+
+#[rocket::async_test]
+async fn test_update_task_errors() {
+    let client = client().await;
+    let task = create_task(&client, "draft").await;
+
+    let response = client
+        .put(format!("/task/{}", task.id))
+        .header(ContentType::JSON)
+        .body(r#"{"title":"","due":"1900-01-01T00:00:00Z","priority":12}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    let errors: ValidationErrors = response.into_json().await.unwrap();
+    let fields: Vec<&str> = errors.errors.iter().map(|e| e.field.as_str()).collect();
+    assert_eq!(fields, ["title", "due", "priority"]);
+
+    // Not JSON, or not an update.
+    for body in ["title=x", r#"{"completed":"yes"}"#] {
+        let response = client
+            .put(format!("/task/{}", task.id))
+            .header(ContentType::JSON)
+            .body(body)
+            .dispatch()
+            .await;
+        assert!(response.status().class().is_client_error(), "{body}");
+    }
+
+    let response = client
+        .put("/task/tasks:missing")
+        .header(ContentType::JSON)
+        .body(r#"{"completed":true}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+    let response = client.get("/tasks").dispatch().await;
+    assert_eq!(response.into_json::<Vec<Task>>().await.unwrap().len(), 1);
+}
+
+#[rocket::async_test]
+async fn test_toggle_task() {
+    let client = client().await;
+    let task = create_task(&client, "toggle").await;
+
+    for completed in [true, false] {
+        let response = client.patch(format!("/task/{}", task.id)).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let res: AffectedRows = response.into_json().await.unwrap();
+        assert_eq!(res.affected_rows, 1);
+
+        let response = client.get(format!("/task/{}", task.id)).dispatch().await;
+        assert_eq!(
+            response.into_json::<Task>().await.unwrap().completed,
+            completed
+        );
+    }
+}
+
+#[rocket::async_test]
+async fn test_delete_task() {
+    let client = client().await;
+    let task = create_task(&client, "delete").await;
+
+    let response = client.delete(format!("/task/{}", task.id)).dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    let res: AffectedRows = response.into_json().await.unwrap();
+    assert_eq!(res.affected_rows, 1);
+
+    let response = client.get(format!("/task/{}", task.id)).dispatch().await;
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn test_task_history() {
+    let client = client().await;
+    let task = create_task(&client, "history").await;
+
+    let response = client
+        .patch(format!("/task/{}", task.id))
+        .header(Header::new("Authorization", "Bearer secret"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let response = client
+        .get(format!("/task/{}/history", task.id))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let history: Vec<TaskEvent> = response.into_json().await.unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].action, TaskAction::Created);
+    assert_eq!(history[0].actor, "anonymous");
+    assert_eq!(history[1].action, TaskAction::Toggled);
+    assert!(history[1].actor.starts_with("user:"));
+
+    let response = client.get("/task/tasks:missing/history").dispatch().await;
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn test_assign_task() {
+    let client = client().await;
+    let task = create_task(&client, "review").await;
+    let bearer = || Header::new("Authorization", "Bearer secret");
+
+    // Users who changed a task can be assigned it.
+    let response = client
+        .patch(format!("/task/{}", task.id))
+        .header(bearer())
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let response = client
+        .get(format!("/task/{}/assignees", task.id))
+        .dispatch()
+        .await;
+    let users: Vec<String> = response.into_json().await.unwrap();
+    assert_eq!(users.len(), 1);
+
+    let response = client
+        .put(format!("/task/{}/assignee", task.id))
+        .header(ContentType::JSON)
+        .body(format!(r#"{{"assignee":"{}"}}"#, users[0]))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let assigned: Task = response.into_json().await.unwrap();
+    assert_eq!(assigned.assignee, Some(users[0].clone()));
+
+    let response = client
+        .get("/tasks?mine=true")
+        .header(bearer())
+        .dispatch()
+        .await;
+    assert_eq!(
+        response.into_json::<Vec<Task>>().await,
+        Some(vec![assigned])
+    );
+    let response = client.get("/tasks?mine=true").dispatch().await;
+    assert_eq!(response.into_json::<Vec<Task>>().await, Some(vec![]));
+
+    let response = client
+        .put(format!("/task/{}/assignee", task.id))
+        .header(ContentType::JSON)
+        .body(r#"{"assignee":"anonymous"}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+
+    let response = client
+        .delete(format!("/task/{}/assignee", task.id))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.into_json::<Task>().await.unwrap().assignee, None);
+
+    let response = client
+        .delete("/task/tasks:missing/assignee")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn test_move_task() {
+    let client = client().await;
+    let first = create_task(&client, "first").await;
+    let second = create_task(&client, "second").await;
+
+    let response = client
+        .put(format!("/task/{}/move", second.id))
+        .header(ContentType::JSON)
+        .body(format!(r#"{{"before":"{}"}}"#, first.id))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let moved: Task = response.into_json().await.unwrap();
+    assert!(moved.rank < first.rank);
+
+    let response = client.get("/tasks").dispatch().await;
+    let tasks: Vec<Task> = response.into_json().await.unwrap();
+    let ids: Vec<&str> = tasks.iter().map(|task| task.id.as_str()).collect();
+    assert_eq!(ids, [second.id.as_str(), first.id.as_str()]);
+
+    let response = client
+        .put(format!("/task/{}/move", first.id))
+        .header(ContentType::JSON)
+        .body(r#"{"after":"tasks:missing"}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+
+    let response = client
+        .put(format!("/task/{}/move", first.id))
+        .header(ContentType::JSON)
+        .body("{}")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+}
+
+#[rocket::async_test]
+async fn test_task_status() {
+    let client = client().await;
+    let task = create_task(&client, "status").await;
+    assert_eq!(task.status, "todo");
+
+    let response = client
+        .put(format!("/task/{}/status/review", task.id))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let task: Task = response.into_json().await.unwrap();
+    assert_eq!(task.status, "review");
+
+    let response = client
+        .put(format!("/task/{}/status/archived", task.id))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    let errors: ValidationErrors = response.into_json().await.unwrap();
+    assert!(errors.message("status").is_some());
+
+    let response = client
+        .put("/task/tasks:missing/status/done")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn test_statuses() {
+    let client = client().await;
+    let response = client.get("/statuses").dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    let workflow: Workflow = response.into_json().await.unwrap();
+    assert_eq!(workflow, Workflow::default());
+
+    let statuses = r#"[{"id":"open","name":"Open"},{"id":"closed","name":"Closed","done":true}]"#;
+    let response = client
+        .put("/statuses")
+        .header(ContentType::JSON)
+        .body(statuses)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client
+        .put("/statuses")
+        .header(ContentType::JSON)
+        .header(Header::new("Authorization", "Bearer ada"))
+        .body(statuses)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let task = create_task(&client, "open").await;
+    assert_eq!(task.status, "open");
+
+    let response = client
+        .put("/statuses")
+        .header(ContentType::JSON)
+        .header(Header::new("Authorization", "Bearer ada"))
+        .body(r#"[{"id":"open","name":"Open"}]"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+}
+
+#[rocket::async_test]
+async fn test_unknown_route() {
+    let client = client().await;
+    let response = client.get("/nowhere").dispatch().await;
+    assert_eq!(response.status(), Status::NotFound);
+    let response = client.post("/tasks").dispatch().await;
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn test_rate_limit() {
+    let client = client_with(|figment| figment.merge(("rate_limit.groups.write.burst", 1))).await;
+    create_task(&client, "first").await;
+
+    let response = client.post("/task/second").dispatch().await;
+    assert_eq!(response.status(), Status::TooManyRequests);
+    assert!(response.headers().get_one("Retry-After").is_some());
+
+    // Reads have their own limit.
+    let response = client
+        .get("/tasks")
+        .header(Header::new("Authorization", "Bearer token"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response.headers().get_one("RateLimit-Remaining").is_some());
+}
+
+#[rocket::async_test]
+async fn test_rate_limit_before_token_lookup() {
+    let client = client_with(|figment| figment.merge(("rate_limit.groups.write.burst", 1))).await;
+    let guess = Header::new("Authorization", "Bearer todo_pat_guess");
+    let response = client
+        .post("/task/a")
+        .header(guess.clone())
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Unauthorized);
+    let response = client.post("/task/a").header(guess).dispatch().await;
+    assert_eq!(response.status(), Status::TooManyRequests);
+}
@@ -0,0 +1,146 @@
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::asynchronous::Client;
+
+use super::{client, create_task};
+use crate::db::{AffectedRows, Task, TaskEvent};
+use crate::tokens::{ApiToken, IssuedToken};
+
+fn bearer(token: &str) -> Header<'static> {
+    Header::new("Authorization", format!("Bearer {token}"))
+}
+
+/// Issues a token with `scopes`, a JSON array, to the user of the bearer
+/// token `owner`.
+async fn issue(client: &Client, owner: &str, scopes: &str) -> IssuedToken {
+    let response = client
+        .post("/tokens")
+        .header(ContentType::JSON)
+        .header(bearer(owner))
+        .body(format!(r#"{{"name":"ci","scopes":{scopes}}}"#))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    response.into_json().await.unwrap()
+}
+
+#[rocket::async_test]
+async fn test_token_scopes() {
+    let client = client().await;
+    let task = create_task(&client, "ship").await;
+    let read = issue(&client, "owner", r#"["read"]"#).await;
+    let write = issue(&client, "owner", r#"["write"]"#).await;
+
+    let response = client
+        .get("/tasks")
+        .header(bearer(&read.token))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let response = client
+        .patch(format!("/task/{}", task.id))
+        .header(bearer(&read.token))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+
+    // Tokens act as the user who issued them.
+    let response = client
+        .patch(format!("/task/{}", task.id))
+        .header(bearer(&write.token))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let response = client
+        .get(format!("/task/{}/history", task.id))
+        .dispatch()
+        .await;
+    let history: Vec<TaskEvent> = response.into_json().await.unwrap();
+    assert_eq!(history[1].actor, write.api_token.user);
+
+    // Managing tokens needs the admin scope.
+    let response = client
+        .get("/tokens")
+        .header(bearer(&write.token))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let response = client
+        .get("/tasks")
+        .header(bearer("todo_pat_unknown"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[rocket::async_test]
+async fn test_tokens() {
+    let client = client().await;
+    let response = client
+        .post("/tokens")
+        .header(ContentType::JSON)
+        .body(r#"{"name":"ci","scopes":["read"]}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client
+        .post("/tokens")
+        .header(ContentType::JSON)
+        .header(bearer("owner"))
+        .body(r#"{"name":" ","scopes":[]}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+
+    let admin = issue(&client, "owner", r#"["admin"]"#).await;
+    let response = client
+        .get("/tasks")
+        .header(bearer(&admin.token))
+        .dispatch()
+        .await;
+    assert_eq!(response.into_json::<Vec<Task>>().await, Some(vec![]));
+
+    let response = client
+        .get("/tokens")
+        .header(bearer(&admin.token))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let tokens: Vec<ApiToken> = response.into_json().await.unwrap();
+    assert_eq!(tokens.len(), 1);
+    assert!(tokens[0].last_used_at.is_some());
+    let response = client
+        .get("/tokens")
+        .header(bearer("someone"))
+        .dispatch()
+        .await;
+    let others: Vec<ApiToken> = response.into_json().await.unwrap();
+    assert!(others.is_empty());
+    // Anonymous callers are held to the same scopes, without admin.
+    let response = client.get("/tokens").dispatch().await;
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client.get("/tasks").dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let response = client
+        .delete(format!("/token/{}", admin.api_token.id))
+        .header(bearer("someone"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client
+        .delete(format!("/token/{}", admin.api_token.id))
+        .header(bearer(&admin.token))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let affected: AffectedRows = response.into_json().await.unwrap();
+    assert_eq!(affected.affected_rows, 1);
+
+    let response = client
+        .get("/tasks")
+        .header(bearer(&admin.token))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Unauthorized);
+}
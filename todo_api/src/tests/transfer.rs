@@ -0,0 +1,105 @@
+use rocket::http::{ContentType, Status};
+use todo_models::{ImportReport, TaskRecord};
+
+use super::{client, create_task};
+use crate::db::Task;
+
+#[rocket::async_test]
+async fn test_export() {
+    let client = client().await;
+    let task = create_task(&client, "export%20me").await;
+
+    let response = client.get("/export").dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::JSON));
+    let records: Vec<TaskRecord> = response.into_json().await.unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].title, task.title);
+
+    for (format, content_type, expected) in [
+        ("csv", ContentType::CSV, "export me"),
+        ("todotxt", ContentType::Plain, "export me"),
+        ("ics", ContentType::Calendar, "SUMMARY:export me"),
+    ] {
+        let response = client
+            .get(format!("/export?format={format}"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(content_type));
+        let body = response.into_string().await.unwrap();
+        assert!(body.contains(expected), "{format}: {body}");
+    }
+}
+
+#[rocket::async_test]
+async fn test_import() {
+    let client = client().await;
+    let body = r#"[{"title":"one"},{"title":"  "},{"title":"two","priority":3}]"#;
+
+    let response = client
+        .post("/import?format=json&dry_run=true")
+        .body(body)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let report: ImportReport = response.into_json().await.unwrap();
+    assert!(report.dry_run);
+    assert_eq!(report.created.len(), 2);
+    assert_eq!(report.invalid.len(), 1);
+    assert_eq!(report.invalid[0].line, 2);
+    let response = client.get("/tasks").dispatch().await;
+    assert_eq!(response.into_json::<Vec<Task>>().await, Some(vec![]));
+
+    let response = client.post("/import").body(body).dispatch().await;
+    let report: ImportReport = response.into_json().await.unwrap();
+    assert!(!report.dry_run);
+    let response = client.get("/tasks").dispatch().await;
+    let tasks: Vec<Task> = response.into_json().await.unwrap();
+    let titles: Vec<&str> = tasks.iter().map(|t| t.title.as_str()).collect();
+    assert_eq!(titles.len(), 2);
+    assert!(titles.contains(&"one") && titles.contains(&"two"));
+}
+
+#[rocket::async_test]
+async fn test_export_import_round_trip() {
+    let source = client().await;
+    create_task(&source, "round%20trip").await;
+    let export = source
+        .get("/export?format=csv")
+        .dispatch()
+        .await
+        .into_string()
+        .await
+        .unwrap();
+
+    let target = client().await;
+    let response = target
+        .post("/import?format=csv")
+        .body(&export)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    // Importing the same file again only finds duplicates.
+    let response = target
+        .post("/import?format=csv")
+        .body(&export)
+        .dispatch()
+        .await;
+    let report: ImportReport = response.into_json().await.unwrap();
+    assert!(report.created.is_empty());
+    assert_eq!(report.duplicates.len(), 1);
+}
+
+#[rocket::async_test]
+async fn test_import_malformed_file() {
+    let client = client().await;
+    let response = client
+        .post("/import?format=json")
+        .body("{")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::BadRequest);
+    assert!(response.into_string().await.unwrap().contains("EOF"));
+}
@@ -0,0 +1,111 @@
+use rocket::http::{ContentType, Header, Status};
+use todo_models::ValidationErrors;
+
+use super::{client, create_task};
+use crate::db::{AffectedRows, TaskAction};
+use crate::webhooks::{DeliveryStatus, Webhook, WebhookDelivery};
+
+fn bearer(token: &str) -> Header<'static> {
+    Header::new("Authorization", format!("Bearer {token}"))
+}
+
+#[rocket::async_test]
+async fn test_webhooks() {
+    let client = client().await;
+    let body =
+        r#"{"url":"https://ci.example.com/todo","secret":"0123456789abcdef","events":["toggled"]}"#;
+
+    let response = client
+        .post("/webhooks")
+        .header(ContentType::JSON)
+        .body(body)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let response = client
+        .post("/webhooks")
+        .header(ContentType::JSON)
+        .header(bearer("ada"))
+        .body(body)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let body = response.into_string().await.unwrap();
+    assert!(!body.contains("secret"));
+    let webhook: Webhook = rocket::serde::json::from_str(&body).unwrap();
+    assert_eq!(webhook.events, [TaskAction::Toggled]);
+    assert_eq!(webhook.workspace, None);
+
+    let response = client
+        .get("/webhooks")
+        .header(bearer("ada"))
+        .dispatch()
+        .await;
+    let webhooks: Vec<Webhook> = response.into_json().await.unwrap();
+    assert_eq!(webhooks, std::slice::from_ref(&webhook));
+    let response = client
+        .get("/webhooks")
+        .header(bearer("bob"))
+        .dispatch()
+        .await;
+    let webhooks: Vec<Webhook> = response.into_json().await.unwrap();
+    assert!(webhooks.is_empty());
+    let response = client.get("/webhooks").dispatch().await;
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let task = create_task(&client, "test").await;
+    client.patch(format!("/task/{}", task.id)).dispatch().await;
+
+    let url = format!("/webhook/{}/deliveries", webhook.id);
+    let response = client.get(&url).header(bearer("bob")).dispatch().await;
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client.get(&url).header(bearer("ada")).dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    let deliveries: Vec<WebhookDelivery> = response.into_json().await.unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].event, TaskAction::Toggled);
+    assert_eq!(deliveries[0].status, DeliveryStatus::Pending);
+
+    let response = client
+        .delete(format!("/webhook/{}", webhook.id))
+        .header(bearer("bob"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client
+        .delete(format!("/webhook/{}", webhook.id))
+        .header(bearer("ada"))
+        .dispatch()
+        .await;
+    let res: AffectedRows = response.into_json().await.unwrap();
+    assert_eq!(res.affected_rows, 1);
+    let response = client.get(&url).header(bearer("ada")).dispatch().await;
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn test_invalid_webhooks() {
+    let client = client().await;
+
+    let response = client
+        .post("/webhooks")
+        .header(ContentType::JSON)
+        .header(bearer("ada"))
+        .body(r#"{"url":"ftp://example.com","secret":"short","events":[]}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    let errors: ValidationErrors = response.into_json().await.unwrap();
+    assert!(errors.message("url").is_some());
+    assert!(errors.message("secret").is_some());
+    assert!(errors.message("events").is_some());
+
+    let task = create_task(&client, "test").await;
+    let response = client
+        .get(format!("/webhook/{}/deliveries", task.id))
+        .header(bearer("ada"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+}
@@ -0,0 +1,386 @@
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::asynchronous::Client;
+use todo_models::{CalendarFeed, Workflow};
+
+use super::client;
+use crate::comments::Comment;
+use crate::db::{AffectedRows, Task, TaskAction};
+use crate::webhooks::{Webhook, WebhookDelivery};
+use crate::workspaces::{Invitation, Member, Role, Workspace};
+
+fn bearer(token: &str) -> Header<'static> {
+    Header::new("Authorization", format!("Bearer {token}"))
+}
+
+/// A workspace owned by `owner`, with `viewer` as a viewer.
+async fn shared_workspace(client: &Client) -> Workspace {
+    let response = client
+        .post("/workspaces")
+        .header(ContentType::JSON)
+        .header(bearer("owner"))
+        .body(r#"{"name":"Team"}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let workspace: Workspace = response.into_json().await.unwrap();
+
+    let response = client
+        .post(format!("/workspace/{}/invitations", workspace.id))
+        .header(ContentType::JSON)
+        .header(bearer("owner"))
+        .body(r#"{"role":"viewer"}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let invitation: Invitation = response.into_json().await.unwrap();
+
+    let response = client
+        .post(format!("/invitation/{}", invitation.token))
+        .header(bearer("viewer"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let member: Member = response.into_json().await.unwrap();
+    assert_eq!(member.role, Role::Viewer);
+    workspace
+}
+
+#[rocket::async_test]
+async fn test_viewers_can_only_read() {
+    let client = client().await;
+    let workspace = shared_workspace(&client).await;
+
+    let response = client
+        .post(format!("/task/plan?workspace={}", workspace.id))
+        .header(bearer("viewer"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client
+        .post(format!("/task/plan?workspace={}", workspace.id))
+        .header(bearer("owner"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let task: Task = response.into_json().await.unwrap();
+    assert_eq!(task.workspace.as_deref(), Some(workspace.id.as_str()));
+
+    let url = format!("/task/{}", task.id);
+    let response = client.get(&url).header(bearer("viewer")).dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    let response = client
+        .get(format!("/tasks?workspace={}", workspace.id))
+        .header(bearer("viewer"))
+        .dispatch()
+        .await;
+    let tasks: Vec<Task> = response.into_json().await.unwrap();
+    assert_eq!(tasks, std::slice::from_ref(&task));
+
+    // Outside the workspace, the task is neither listed nor readable.
+    let response = client
+        .get("/tasks")
+        .header(bearer("viewer"))
+        .dispatch()
+        .await;
+    let tasks: Vec<Task> = response.into_json().await.unwrap();
+    assert!(tasks.is_empty());
+    let response = client.get(&url).dispatch().await;
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let response = client.patch(&url).header(bearer("viewer")).dispatch().await;
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client
+        .delete(&url)
+        .header(bearer("viewer"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let response = client.patch(&url).header(bearer("owner")).dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    let response = client.delete(&url).header(bearer("owner")).dispatch().await;
+    let res: AffectedRows = response.into_json().await.unwrap();
+    assert_eq!(res.affected_rows, 1);
+
+    // The history outlives the task, but stays in the workspace.
+    let history = format!("/task/{}/history", task.id);
+    let response = client.get(&history).dispatch().await;
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client
+        .get(&history)
+        .header(bearer("viewer"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[rocket::async_test]
+async fn test_members() {
+    let client = client().await;
+
+    let response = client
+        .post("/workspaces")
+        .header(ContentType::JSON)
+        .body(r#"{"name":"Team"}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client
+        .post("/workspaces")
+        .header(ContentType::JSON)
+        .header(bearer("owner"))
+        .body(r#"{"name":" "}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+
+    let workspace = shared_workspace(&client).await;
+    let response = client
+        .get("/workspaces")
+        .header(bearer("viewer"))
+        .dispatch()
+        .await;
+    let workspaces: Vec<Workspace> = response.into_json().await.unwrap();
+    assert_eq!(workspaces, std::slice::from_ref(&workspace));
+
+    let response = client
+        .get(format!("/workspace/{}/members", workspace.id))
+        .header(bearer("viewer"))
+        .dispatch()
+        .await;
+    let members: Vec<Member> = response.into_json().await.unwrap();
+    let roles: Vec<Role> = members.iter().map(|m| m.role).collect();
+    assert_eq!(roles, [Role::Owner, Role::Viewer]);
+
+    let response = client
+        .put(format!("/member/{}", members[0].id))
+        .header(ContentType::JSON)
+        .header(bearer("owner"))
+        .body(r#"{"role":"editor"}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Conflict);
+    let response = client
+        .put(format!("/member/{}", members[1].id))
+        .header(ContentType::JSON)
+        .header(bearer("viewer"))
+        .body(r#"{"role":"owner"}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let response = client
+        .delete(format!("/member/{}", members[1].id))
+        .header(bearer("viewer"))
+        .dispatch()
+        .await;
+    let res: AffectedRows = response.into_json().await.unwrap();
+    assert_eq!(res.affected_rows, 1);
+    let response = client
+        .get(format!("/workspace/{}/members", workspace.id))
+        .header(bearer("viewer"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+}
+
+#[rocket::async_test]
+async fn test_workspace_calendar_feed() {
+    let client = client().await;
+    let workspace = shared_workspace(&client).await;
+
+    let response = client
+        .post(format!("/calendar/feed?workspace={}", workspace.id))
+        .header(bearer("stranger"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client
+        .post(format!("/calendar/feed?workspace={}", workspace.id))
+        .header(bearer("viewer"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let feed: CalendarFeed = response.into_json().await.unwrap();
+    let response = client.get(feed.path.clone()).dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let response = client
+        .get(format!("/workspace/{}/members", workspace.id))
+        .header(bearer("owner"))
+        .dispatch()
+        .await;
+    let members: Vec<Member> = response.into_json().await.unwrap();
+    client
+        .delete(format!("/member/{}", members[1].id))
+        .header(bearer("owner"))
+        .dispatch()
+        .await;
+    let response = client.get(feed.path).dispatch().await;
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn test_workspace_webhooks() {
+    let client = client().await;
+    let workspace = shared_workspace(&client).await;
+    let webhooks = format!("/webhooks?workspace={}", workspace.id);
+    let body =
+        r#"{"url":"https://ci.example.com/todo","secret":"0123456789abcdef","events":["created"]}"#;
+
+    let response = client
+        .post(&webhooks)
+        .header(ContentType::JSON)
+        .header(bearer("viewer"))
+        .body(body)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client
+        .post(&webhooks)
+        .header(ContentType::JSON)
+        .header(bearer("owner"))
+        .body(body)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let webhook: Webhook = response.into_json().await.unwrap();
+    assert_eq!(webhook.workspace.as_deref(), Some(workspace.id.as_str()));
+
+    let response = client
+        .get(&webhooks)
+        .header(bearer("viewer"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client
+        .get(&webhooks)
+        .header(bearer("owner"))
+        .dispatch()
+        .await;
+    let listed: Vec<Webhook> = response.into_json().await.unwrap();
+    assert_eq!(listed, std::slice::from_ref(&webhook));
+    let response = client
+        .get("/webhooks")
+        .header(bearer("owner"))
+        .dispatch()
+        .await;
+    let listed: Vec<Webhook> = response.into_json().await.unwrap();
+    assert!(listed.is_empty());
+
+    // Only changes to the workspace's tasks are delivered.
+    client.post("/task/outside").dispatch().await;
+    client
+        .post(format!("/task/inside?workspace={}", workspace.id))
+        .header(bearer("owner"))
+        .dispatch()
+        .await;
+    let url = format!("/webhook/{}/deliveries", webhook.id);
+    let response = client.get(&url).header(bearer("viewer")).dispatch().await;
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client.get(&url).header(bearer("owner")).dispatch().await;
+    let deliveries: Vec<WebhookDelivery> = response.into_json().await.unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].event, TaskAction::Created);
+}
+
+#[rocket::async_test]
+async fn test_comments_need_membership() {
+    let client = client().await;
+    let workspace = shared_workspace(&client).await;
+    let response = client
+        .post(format!("/workspace/{}/invitations", workspace.id))
+        .header(ContentType::JSON)
+        .header(bearer("owner"))
+        .body(r#"{"role":"editor"}"#)
+        .dispatch()
+        .await;
+    let invitation: Invitation = response.into_json().await.unwrap();
+    let response = client
+        .post(format!("/invitation/{}", invitation.token))
+        .header(bearer("editor"))
+        .dispatch()
+        .await;
+    let editor: Member = response.into_json().await.unwrap();
+
+    let response = client
+        .post(format!("/task/plan?workspace={}", workspace.id))
+        .header(bearer("owner"))
+        .dispatch()
+        .await;
+    let task: Task = response.into_json().await.unwrap();
+    let response = client
+        .post(format!("/task/{}/comments", task.id))
+        .header(ContentType::JSON)
+        .header(bearer("editor"))
+        .body(r#"{"body":"first"}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let comment: Comment = response.into_json().await.unwrap();
+
+    // Once removed, the author can no longer change their comment.
+    client
+        .delete(format!("/member/{}", editor.id))
+        .header(bearer("owner"))
+        .dispatch()
+        .await;
+    let url = format!("/comment/{}", comment.id);
+    let response = client
+        .put(&url)
+        .header(ContentType::JSON)
+        .header(bearer("editor"))
+        .body(r#"{"body":"edited"}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client
+        .delete(&url)
+        .header(bearer("editor"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+}
+
+#[rocket::async_test]
+async fn test_workspace_statuses() {
+    let client = client().await;
+    let workspace = shared_workspace(&client).await;
+    let url = format!("/statuses?workspace={}", workspace.id);
+    let statuses = r#"[{"id":"open","name":"Open"},{"id":"closed","name":"Closed","done":true}]"#;
+
+    let response = client
+        .put(&url)
+        .header(ContentType::JSON)
+        .header(bearer("viewer"))
+        .body(statuses)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client
+        .put(&url)
+        .header(ContentType::JSON)
+        .header(bearer("owner"))
+        .body(statuses)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let response = client.get(&url).header(bearer("viewer")).dispatch().await;
+    let workflow: Workflow = response.into_json().await.unwrap();
+    assert_eq!(workflow.statuses[0].id, "open");
+    let response = client.get(&url).header(bearer("stranger")).dispatch().await;
+    assert_eq!(response.status(), Status::Forbidden);
+
+    // Only the workspace's tasks use its statuses.
+    let response = client
+        .post(format!("/task/plan?workspace={}", workspace.id))
+        .header(bearer("owner"))
+        .dispatch()
+        .await;
+    let task: Task = response.into_json().await.unwrap();
+    assert_eq!(task.status, "open");
+    let response = client.get("/statuses").dispatch().await;
+    let workflow: Workflow = response.into_json().await.unwrap();
+    assert_eq!(workflow, Workflow::default());
+}
@@ -0,0 +1,163 @@
+//! Personal access tokens, for scripts and integrations. A token acts as the
+//! user who issued it, limited to its scopes, and is checked by the
+//! [`Actor`](crate::actor::Actor) guard against the scope each route names.
+//! The guard holds other callers to the same scopes: signed-in users have
+//! them all, and anonymous callers [`ANONYMOUS_SCOPE`].
+//!
+//! Only a SHA-256 hash of each token is stored, so a leaked database does
+//! not leak working tokens.
+
+use chrono::{Duration, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+
+use crate::actor::ANONYMOUS;
+use crate::db::{AffectedRows, DB};
+use crate::error::Error;
+use crate::query::{Order, Query, Return, Target};
+use crate::utils::{crypto::hex, value::to_value};
+pub use todo_models::{ApiToken, IssuedToken, NewApiToken, Scope};
+
+/// Starts every token, so the API can tell them from other bearer tokens
+/// and secret scanners can find them.
+pub const TOKEN_PREFIX: &str = "todo_pat_";
+/// Random characters after the prefix.
+const SECRET_LEN: usize = 40;
+/// Characters of the secret kept in the listed prefix.
+const SHOWN_LEN: usize = 4;
+/// How stale the recorded last use may get, so busy tokens are not written
+/// back on every request.
+const LAST_USED_SECONDS: i64 = 60;
+
+/// What callers without a bearer token may do: anything but admin.
+pub const ANONYMOUS_SCOPE: Scope = Scope::Write;
+
+impl DB {
+    /// `user`'s tokens, oldest first.
+    pub async fn get_tokens(&self, user: &str) -> Result<Vec<ApiToken>, Error> {
+        let query = Query::select("api_tokens")
+            .where_eq("user", user)
+            .order_by("created_at", Order::Asc);
+        self.fetch(query).await
+    }
+
+    /// Issues a token acting as `user`. Only its hash is stored.
+    pub async fn create_token(&self, token: NewApiToken, user: &str) -> Result<IssuedToken, Error> {
+        if user == ANONYMOUS {
+            return Err(Error::XAnonymous);
+        }
+        let secret: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(SECRET_LEN)
+            .map(char::from)
+            .collect();
+        let secret = format!("{TOKEN_PREFIX}{secret}");
+        let query = Query::create("api_tokens")
+            .set("name", token.name)
+            .set("user", user)
+            .set("scopes", to_value(&token.scopes)?)
+            .set("prefix", &secret[..TOKEN_PREFIX.len() + SHOWN_LEN])
+            .set("token_hash", token_hash(&secret))
+            .set("created_at", Utc::now());
+        let api_token = self.fetch_one(query).await?.ok_or(Error::XNoResponse)?;
+        Ok(IssuedToken {
+            token: secret,
+            api_token,
+        })
+    }
+
+    /// Revokes one of `user`'s tokens. Missing tokens affect no rows.
+    pub async fn revoke_token(&self, id: String, user: &str) -> Result<AffectedRows, Error> {
+        // Any record id parses, so other tables are turned away here.
+        if !id.starts_with("api_tokens:") {
+            return Ok(AffectedRows { affected_rows: 0 });
+        }
+        let query = Query::select(Target::record(&id)?);
+        let Some(token) = self.fetch_one::<ApiToken>(query).await? else {
+            return Ok(AffectedRows { affected_rows: 0 });
+        };
+        if token.user != user {
+            return Err(Error::XForbidden(id));
+        }
+        let _ = self.run(Query::delete(Target::record(&id)?)).await?;
+        Ok(AffectedRows { affected_rows: 1 })
+    }
+
+    /// The token sent by a caller, if it was issued and not revoked, and
+    /// records that it was used.
+    pub async fn use_token(&self, token: &str) -> Result<Option<ApiToken>, Error> {
+        let query = Query::select("api_tokens")
+            .where_eq("token_hash", token_hash(token))
+            .limit(1);
+        let Some(mut api_token) = self.fetch_one::<ApiToken>(query).await? else {
+            return Ok(None);
+        };
+        let now = Utc::now();
+        let stale = Duration::seconds(LAST_USED_SECONDS);
+        if !matches!(api_token.last_used_at, Some(at) if now - at < stale) {
+            let query = Query::update(Target::record(&api_token.id)?)
+                .set("last_used_at", now)
+                .returning(Return::None);
+            let _ = self.run(query).await?;
+            api_token.last_used_at = Some(now);
+        }
+        Ok(Some(api_token))
+    }
+}
+
+fn token_hash(token: &str) -> String {
+    hex(&Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use surrealdb::{dbs::Session, kvs::Datastore};
+
+    async fn db() -> DB {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        DB { ds, sesh }
+    }
+
+    fn new_token() -> NewApiToken {
+        NewApiToken {
+            name: "ci".to_string(),
+            scopes: vec![Scope::Read],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tokens() {
+        let db = db().await;
+        let res = db.create_token(new_token(), ANONYMOUS).await;
+        assert!(matches!(res, Err(Error::XAnonymous)));
+
+        let issued = db.create_token(new_token(), "user:a").await.unwrap();
+        assert!(issued.token.starts_with(TOKEN_PREFIX));
+        assert_eq!(issued.token.len(), TOKEN_PREFIX.len() + SECRET_LEN);
+        assert!(issued.token.starts_with(&issued.api_token.prefix));
+        assert_eq!(issued.api_token.last_used_at, None);
+
+        // Only the hash is kept.
+        let stored = db.run(Query::select("api_tokens")).await.unwrap();
+        assert!(!stored.to_string().contains(&issued.token));
+
+        let used = db.use_token(&issued.token).await.unwrap().unwrap();
+        assert_eq!(used.user, "user:a");
+        assert!(used.last_used_at.is_some());
+        let listed = db.get_tokens("user:a").await.unwrap();
+        assert_eq!(listed[0].last_used_at, used.last_used_at);
+        assert!(db.get_tokens("user:b").await.unwrap().is_empty());
+
+        let id = issued.api_token.id;
+        let res = db.revoke_token(id.clone(), "user:b").await;
+        assert!(matches!(res, Err(Error::XForbidden(_))));
+        let res = db.revoke_token(id.clone(), "user:a").await.unwrap();
+        assert_eq!(res.affected_rows, 1);
+        assert_eq!(db.use_token(&issued.token).await.unwrap(), None);
+        let res = db.revoke_token(id, "user:a").await.unwrap();
+        assert_eq!(res.affected_rows, 0);
+    }
+}
@@ -0,0 +1,300 @@
+//! Runs several queries atomically.
+//!
+//! Each `execute` call on the datastore is its own request, so a transaction
+//! cannot stay open across them. Instead, [`DB::transaction`] hands the
+//! closure a [`Transaction`] that queues queries, then sends them all in one
+//! `BEGIN`/`COMMIT` script. If the closure fails, nothing is sent. If any
+//! statement fails, SurrealDB cancels the whole script.
+//!
+//! The closure itself cannot read inside the transaction, so anything it
+//! reads first, like the task a change is computed from, may be changed by
+//! another request before the script runs. To act on what the transaction
+//! sees instead, [`Transaction::bind`] reads into a variable, and
+//! [`Transaction::add_if`] runs a query only when a condition on such
+//! variables holds, like "the task is still open".
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use surrealdb::sql::Value;
+
+use crate::db::DB;
+use crate::error::Error;
+use crate::query::Query;
+
+/// One statement of a transaction.
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Run(Query),
+    /// Stores the query's result in a variable.
+    Bind(&'static str, Query),
+    /// Runs the query if the condition holds, and otherwise evaluates to
+    /// the fallback expression.
+    If {
+        condition: &'static str,
+        query: Query,
+        otherwise: &'static str,
+    },
+}
+
+/// The queries queued by a [`DB::transaction`] closure.
+#[derive(Clone, Default)]
+pub struct Transaction {
+    steps: Arc<Mutex<Vec<Step>>>,
+}
+
+impl Transaction {
+    /// Queues a query. Its result is at the returned index once the
+    /// transaction commits.
+    pub fn add(&self, query: Query) -> usize {
+        self.push(Step::Run(query))
+    }
+
+    /// Queues a query whose result later steps read as `$name`, such as
+    /// `array::len($name) > 0`. Its own result is `NONE`.
+    pub fn bind(&self, name: &'static str, query: Query) -> usize {
+        self.push(Step::Bind(name, query))
+    }
+
+    /// Queues a query that only runs if `condition` holds when the
+    /// transaction reaches it. Its result is `NONE` when it doesn't run.
+    pub fn add_if(&self, condition: &'static str, query: Query) -> usize {
+        self.add_if_else(condition, query, "NONE")
+    }
+
+    /// Like [`add_if`](Self::add_if), with `otherwise` as the result when
+    /// the query doesn't run.
+    pub fn add_if_else(
+        &self,
+        condition: &'static str,
+        query: Query,
+        otherwise: &'static str,
+    ) -> usize {
+        self.push(Step::If {
+            condition,
+            query,
+            otherwise,
+        })
+    }
+
+    fn push(&self, step: Step) -> usize {
+        let mut steps = self.steps.lock().expect("transaction lock poisoned");
+        steps.push(step);
+        steps.len() - 1
+    }
+
+    fn take(&self) -> Vec<Step> {
+        std::mem::take(&mut *self.steps.lock().expect("transaction lock poisoned"))
+    }
+}
+
+/// Joins steps into one transaction. Each query's parameters are scoped by
+/// its position, so queries that set the same fields don't collide.
+fn script(steps: Vec<Step>) -> (String, BTreeMap<String, Value>) {
+    let mut sql = "BEGIN TRANSACTION;\n".to_string();
+    let mut vars = BTreeMap::new();
+    for (i, step) in steps.into_iter().enumerate() {
+        let scope = format!("q{i}_");
+        let statement = match step {
+            Step::Run(query) => {
+                let (statement, statement_vars) = query.build_scoped(&scope);
+                vars.extend(statement_vars);
+                statement
+            }
+            Step::Bind(name, query) => {
+                let (statement, statement_vars) = query.build_scoped(&scope);
+                vars.extend(statement_vars);
+                format!("LET ${name} = ({statement})")
+            }
+            Step::If {
+                condition,
+                query,
+                otherwise,
+            } => {
+                let (statement, statement_vars) = query.build_scoped(&scope);
+                vars.extend(statement_vars);
+                format!("IF {condition} THEN ({statement}) ELSE {otherwise} END")
+            }
+        };
+        sql.push_str(&statement);
+        sql.push_str(";\n");
+    }
+    sql.push_str("COMMIT TRANSACTION;");
+    (sql, vars)
+}
+
+impl DB {
+    /// Runs the queries that `build` queues as one transaction and returns
+    /// their results in order. Either every query takes effect or none do,
+    /// but reads made before it are not part of it; see the module docs.
+    pub async fn transaction<F, Fut>(&self, build: F) -> Result<Vec<Value>, Error>
+    where
+        F: FnOnce(Transaction) -> Fut,
+        Fut: Future<Output = Result<(), Error>>,
+    {
+        let tx = Transaction::default();
+        build(tx.clone()).await?;
+
+        let steps = tx.take();
+        let count = steps.len();
+        if count == 0 {
+            return Ok(vec![]);
+        }
+
+        let (sql, vars) = script(steps);
+        let res = self.execute(&sql, Some(vars)).await?;
+        if res.len() != count {
+            return Err(Error::XNoResponse);
+        }
+        res.into_iter()
+            .map(|r| {
+                r.result
+                    .map_err(|e| Error::Surreal(surrealdb::Error::Db(e)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::ANONYMOUS;
+    use crate::db::Task;
+    use crate::query::{Return, Target};
+    use crate::utils::value::from_value;
+    use surrealdb::{dbs::Session, kvs::Datastore};
+
+    async fn db() -> DB {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        DB { ds, sesh }
+    }
+
+    fn create(title: &'static str) -> Query {
+        Query::create("tasks")
+            .set("title", title)
+            .set("completed", false)
+            .set_expr("created_at", "time.now()")
+    }
+
+    #[test]
+    fn test_script() {
+        let (sql, vars) = script(vec![Step::Run(create("a")), Step::Run(create("b"))]);
+        assert_eq!(
+            sql,
+            "BEGIN TRANSACTION;\n\
+             CREATE tasks SET title = $q0_set_title, completed = $q0_set_completed, created_at = time.now();\n\
+             CREATE tasks SET title = $q1_set_title, completed = $q1_set_completed, created_at = time.now();\n\
+             COMMIT TRANSACTION;"
+        );
+        assert_eq!(vars["q0_set_title"], Value::from("a"));
+        assert_eq!(vars["q1_set_title"], Value::from("b"));
+    }
+
+    #[test]
+    fn test_script_reads() {
+        let open = Query::select(Target::record("tasks:a").unwrap()).where_eq("completed", false);
+        let close = Query::update(Target::record("tasks:a").unwrap()).set("completed", true);
+        let (sql, _) = script(vec![
+            Step::Bind("open", open),
+            Step::If {
+                condition: "array::len($open) > 0",
+                query: close,
+                otherwise: "NONE",
+            },
+        ]);
+        assert_eq!(
+            sql,
+            "BEGIN TRANSACTION;\n\
+             LET $open = (SELECT * FROM $q0_target WHERE completed = $q0_where_0);\n\
+             IF array::len($open) > 0 THEN (UPDATE $q1_target SET completed = $q1_set_completed) ELSE NONE END;\n\
+             COMMIT TRANSACTION;"
+        );
+        assert!(surrealdb::sql::parse(&sql).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_commit() {
+        let db = db().await;
+        let results = db
+            .transaction(|tx| async move {
+                tx.add(create("first"));
+                tx.add(create("second"));
+                Ok(())
+            })
+            .await
+            .unwrap();
+
+        let second: Task = from_value(results[1].clone().first()).unwrap();
+        assert_eq!(second.title, "second");
+        assert_eq!(db.get_all_tasks().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_conditional_write() {
+        let db = db().await;
+        let task = db.add_task("test".to_string(), ANONYMOUS).await.unwrap();
+
+        for expected in [true, false] {
+            let id = task.id.clone();
+            let results = db
+                .transaction(|tx| async move {
+                    tx.bind(
+                        "open",
+                        Query::select(Target::record(&id)?).where_eq("completed", false),
+                    );
+                    tx.add_if(
+                        "array::len($open) > 0",
+                        Query::update(Target::record(&id)?)
+                            .set("completed", true)
+                            .returning(Return::After),
+                    );
+                    Ok(())
+                })
+                .await
+                .unwrap();
+            // Only the first transaction finds the task open.
+            assert_eq!(results[0], Value::None);
+            assert_eq!(results[1].clone().first() != Value::None, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_statement_rolls_back() {
+        let db = db().await;
+        let existing = db
+            .add_task("existing".to_string(), ANONYMOUS)
+            .await
+            .unwrap();
+
+        let res = db
+            .transaction(|tx| async move {
+                tx.add(create("new"));
+                // Creating a record that already exists fails.
+                tx.add(Query::create(Target::record(&existing.id)?).set("title", "copy"));
+                Ok(())
+            })
+            .await;
+        assert!(res.is_err());
+
+        let tasks = db.get_all_tasks().await.unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title, "existing");
+    }
+
+    #[tokio::test]
+    async fn test_failed_closure_runs_nothing() {
+        let db = db().await;
+        let res = db
+            .transaction(|tx| async move {
+                tx.add(create("new"));
+                Err(Error::InvalidImport("stop".to_string()))
+            })
+            .await;
+        assert!(matches!(res, Err(Error::InvalidImport(_))));
+        assert!(db.get_all_tasks().await.unwrap().is_empty());
+    }
+}
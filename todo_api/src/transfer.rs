@@ -0,0 +1,519 @@
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use rocket::{
+    futures::stream::{self, BoxStream, StreamExt},
+    http::ContentType,
+    response::stream::TextStream,
+    serde::json::{self, serde_json},
+};
+use todo_models::{ImportReport, InvalidRecord, TaskRecord};
+
+use crate::db::{Task, DB};
+use crate::error::Error;
+use crate::ical;
+
+pub type Export = (ContentType, TextStream<BoxStream<'static, String>>);
+
+/// Tasks read from the database at a time while exporting.
+const PAGE_SIZE: u64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromFormField)]
+pub enum Format {
+    #[field(value = "json")]
+    Json,
+    #[field(value = "csv")]
+    Csv,
+    #[field(value = "todotxt")]
+    TodoTxt,
+    #[field(value = "ics")]
+    ICal,
+}
+
+impl Format {
+    pub fn content_type(self) -> ContentType {
+        match self {
+            Format::Json => ContentType::JSON,
+            Format::Csv => ContentType::CSV,
+            Format::TodoTxt => ContentType::Plain,
+            Format::ICal => ContentType::Calendar,
+        }
+    }
+
+    /// Compares timestamps at the precision the format stores: iCalendar
+    /// drops fractions of a second and todo.txt keeps only the date.
+    fn same_time(self, a: DateTime<Utc>, b: DateTime<Utc>) -> bool {
+        match self {
+            Format::Json | Format::Csv => a == b,
+            Format::ICal => a.timestamp() == b.timestamp(),
+            Format::TodoTxt => a.date_naive() == b.date_naive(),
+        }
+    }
+}
+
+/// A decoded record, or why it was rejected, keyed by its 1-based line
+/// (array position for JSON).
+pub type Row = (usize, Result<TaskRecord, String>);
+
+/// Streams the tasks of a workspace, or those outside any with `None`. They
+/// are read a page at a time, so the export is never held in memory whole.
+/// Tasks added or removed while it runs may shift a page boundary, and a
+/// failing read ends the body early, as the status has been sent by then.
+pub fn export(db: DB, workspace: Option<String>, format: Format) -> Export {
+    let stamp = Utc::now();
+    let pages = stream::unfold(Some(0), move |start| {
+        let (db, workspace) = (db.clone(), workspace.clone());
+        async move {
+            let start = start?;
+            let tasks = match db
+                .get_tasks_page(workspace.as_deref(), start, PAGE_SIZE)
+                .await
+            {
+                Ok(tasks) => tasks,
+                Err(e) => {
+                    error!("export failed after {} tasks: {}", start, e);
+                    return None;
+                }
+            };
+            let next = (tasks.len() as u64 == PAGE_SIZE).then_some(start + PAGE_SIZE);
+            let chunks: Vec<String> = tasks
+                .iter()
+                .enumerate()
+                .map(|(i, task)| encode_task(format, task, start + i as u64 == 0, stamp))
+                .collect();
+            Some((stream::iter(chunks), next))
+        }
+    })
+    .flatten();
+    let chunks = stream::iter(header(format))
+        .chain(pages)
+        .chain(stream::iter(footer(format)));
+    (format.content_type(), TextStream(chunks.boxed()))
+}
+
+/// What comes before the first task.
+fn header(format: Format) -> Option<String> {
+    match format {
+        Format::Json => Some("[".to_string()),
+        Format::Csv => Some(csv_line(&[
+            "id",
+            "title",
+            "completed",
+            "created_at",
+            "due",
+            "priority",
+        ])),
+        Format::TodoTxt => None,
+        Format::ICal => Some(ical::header()),
+    }
+}
+
+/// Renders one task. In JSON, every task but the `first` is preceded by a
+/// comma.
+fn encode_task(format: Format, task: &Task, first: bool, stamp: DateTime<Utc>) -> String {
+    match format {
+        Format::Json => {
+            let task = json::to_string(task).expect("tasks always serialize");
+            if first {
+                task
+            } else {
+                format!(",{task}")
+            }
+        }
+        Format::Csv => csv_line(&[
+            &task.id,
+            &task.title,
+            &task.completed.to_string(),
+            &task.created_at.to_rfc3339(),
+            &task.due.map(|due| due.to_rfc3339()).unwrap_or_default(),
+            &task.priority.map(|p| p.to_string()).unwrap_or_default(),
+        ]),
+        Format::TodoTxt => todotxt_line(task),
+        Format::ICal => ical::todo(task, stamp),
+    }
+}
+
+/// What comes after the last task.
+fn footer(format: Format) -> Option<String> {
+    match format {
+        Format::Json => Some("]".to_string()),
+        Format::Csv | Format::TodoTxt => None,
+        Format::ICal => Some(ical::footer()),
+    }
+}
+
+pub fn decode(format: Format, input: &str) -> Result<Vec<Row>, Error> {
+    match format {
+        Format::Json => {
+            let items: Vec<json::Value> =
+                json::from_str(input).map_err(|e| Error::InvalidImport(e.to_string()))?;
+            Ok(items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    (
+                        i + 1,
+                        serde_json::from_value(item).map_err(|e| e.to_string()),
+                    )
+                })
+                .collect())
+        }
+        Format::Csv => {
+            let mut reader = csv::Reader::from_reader(input.as_bytes());
+            let headers = reader
+                .headers()
+                .map_err(|e| Error::InvalidImport(e.to_string()))?
+                .clone();
+            Ok(reader
+                .records()
+                .map(|record| match record {
+                    Ok(record) => (
+                        record.position().map_or(0, |p| p.line() as usize),
+                        record
+                            .deserialize(Some(&headers))
+                            .map_err(|e| e.to_string()),
+                    ),
+                    Err(e) => (
+                        e.position().map_or(0, |p| p.line() as usize),
+                        Err(e.to_string()),
+                    ),
+                })
+                .collect())
+        }
+        Format::TodoTxt => Ok(input
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| (i + 1, parse_todotxt_line(line)))
+            .collect()),
+        Format::ICal => Ok(ical::parse(input)),
+    }
+}
+
+/// Creates every valid, non-duplicate record in `input` in the workspace
+/// given, or outside any with `None`, all together or not at all. A record
+/// is a duplicate when a task there with the same title and creation time
+/// already exists or appeared earlier in the same import.
+pub async fn import(
+    db: &DB,
+    format: Format,
+    input: &str,
+    dry_run: bool,
+    workspace: Option<&str>,
+    actor: &str,
+) -> Result<ImportReport, Error> {
+    let rows = decode(format, input)?;
+    let mut seen: Vec<(String, DateTime<Utc>)> = db
+        .get_tasks_in(workspace)
+        .await?
+        .into_iter()
+        .map(|task| (task.title, task.created_at))
+        .collect();
+
+    let mut report = ImportReport {
+        dry_run,
+        ..Default::default()
+    };
+    for (line, row) in rows {
+        let record = match row.and_then(|record| record.sanitize().map_err(|e| e.to_string())) {
+            Ok(record) => record,
+            Err(message) => {
+                report.invalid.push(InvalidRecord { line, message });
+                continue;
+            }
+        };
+
+        if let Some(created_at) = record.created_at {
+            if is_duplicate(&seen, &record.title, created_at, format) {
+                report.duplicates.push(record);
+                continue;
+            }
+            seen.push((record.title.clone(), created_at));
+        }
+
+        report.created.push(record);
+    }
+
+    if !dry_run {
+        db.insert_tasks(report.created.clone(), workspace, actor)
+            .await?;
+    }
+    Ok(report)
+}
+
+fn is_duplicate(
+    seen: &[(String, DateTime<Utc>)],
+    title: &str,
+    created_at: DateTime<Utc>,
+    format: Format,
+) -> bool {
+    seen.iter()
+        .any(|(seen_title, seen_at)| seen_title == title && format.same_time(*seen_at, created_at))
+}
+
+fn csv_line(fields: &[&str]) -> String {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer
+        .write_record(fields)
+        .expect("writing to a Vec cannot fail");
+    let bytes = writer.into_inner().expect("writing to a Vec cannot fail");
+    String::from_utf8(bytes).expect("csv of utf-8 fields is utf-8")
+}
+
+/// The app does not track when a task was completed, so completed tasks
+/// repeat their creation date as the completion date. Priorities 1 to 9
+/// map to `(A)` to `(I)`, and due dates use the common `due:` extension.
+fn todotxt_line(task: &Task) -> String {
+    let created = task.created_at.format("%Y-%m-%d");
+    let mut line = match (task.completed, task.priority) {
+        (true, _) => format!("x {created} {created} "),
+        (false, Some(priority)) => format!("({}) {created} ", (b'A' + priority - 1) as char),
+        (false, None) => format!("{created} "),
+    };
+    line.push_str(&task.title.replace(['\r', '\n'], " "));
+    if let Some(due) = task.due {
+        line.push_str(&format!(" due:{}", due.format("%Y-%m-%d")));
+    }
+    line.push('\n');
+    line
+}
+
+fn parse_todotxt_line(line: &str) -> Result<TaskRecord, String> {
+    let mut rest = line.trim();
+
+    let completed = match rest.strip_prefix("x ") {
+        Some(r) => {
+            rest = r.trim_start();
+            true
+        }
+        None => false,
+    };
+
+    let mut priority = None;
+    let bytes = rest.as_bytes();
+    if !completed
+        && bytes.len() > 4
+        && bytes[0] == b'('
+        && bytes[1].is_ascii_uppercase()
+        && bytes[2] == b')'
+        && bytes[3] == b' '
+    {
+        // Only (A) to (I) fit the 1 to 9 priority range.
+        priority = Some(bytes[1] - b'A' + 1).filter(|p| *p <= 9);
+        rest = rest[4..].trim_start();
+    }
+
+    // A completed task lists its completion date before its creation date.
+    let mut created = take_date(&mut rest);
+    if completed {
+        created = take_date(&mut rest);
+    }
+
+    let mut due = None;
+    let mut words = vec![];
+    for word in rest.split_whitespace() {
+        match word.strip_prefix("due:") {
+            Some(date) => {
+                let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .map_err(|_| format!("invalid due date '{date}'"))?;
+                due = Some(midnight(date));
+            }
+            None => words.push(word),
+        }
+    }
+
+    let title = words.join(" ");
+    if title.is_empty() {
+        return Err("missing title".to_string());
+    }
+
+    Ok(TaskRecord {
+        title,
+        completed,
+        created_at: created.map(midnight),
+        due,
+        priority,
+    })
+}
+
+fn midnight(date: NaiveDate) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).expect("midnight is valid"))
+}
+
+fn take_date(rest: &mut &str) -> Option<NaiveDate> {
+    let (word, tail) = rest.split_once(' ').unwrap_or((rest, ""));
+    let date = NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()?;
+    *rest = tail.trim_start();
+    Some(date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::ANONYMOUS;
+    use std::sync::Arc;
+    use surrealdb::dbs::Session;
+    use surrealdb::kvs::Datastore;
+
+    async fn db() -> DB {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        DB { ds, sesh }
+    }
+
+    async fn seeded_db() -> DB {
+        let db = db().await;
+        let records = [
+            ("buy milk", true, None),
+            ("walk the \"dog\", twice", false, Some(2)),
+        ]
+        .into_iter()
+        .map(|(title, completed, priority)| TaskRecord {
+            title: title.to_string(),
+            completed,
+            created_at: Some("2023-04-02T10:30:00Z".parse().unwrap()),
+            due: Some("2023-04-05T00:00:00Z".parse().unwrap()),
+            priority,
+        })
+        .collect();
+        db.insert_tasks(records, None, ANONYMOUS).await.unwrap();
+        db
+    }
+
+    async fn export_string(db: &DB, format: Format) -> String {
+        let (_, TextStream(chunks)) = export(db.clone(), None, format);
+        chunks.collect::<Vec<String>>().await.concat()
+    }
+
+    async fn assert_round_trip(format: Format) {
+        let source = seeded_db().await;
+        let tasks = source.get_all_tasks().await.unwrap();
+        let exported = export_string(&source, format).await;
+
+        let target = db().await;
+        let report = import(&target, format, &exported, false, None, ANONYMOUS)
+            .await
+            .unwrap();
+        assert_eq!(report.created.len(), 2, "{exported}");
+        assert!(report.invalid.is_empty());
+
+        let imported = target.get_all_tasks().await.unwrap();
+        for task in &tasks {
+            let copy = imported.iter().find(|t| t.title == task.title).unwrap();
+            assert_eq!(copy.completed, task.completed);
+            assert_eq!(copy.due, task.due);
+            assert_eq!(copy.priority, task.priority);
+            assert!(format.same_time(copy.created_at, task.created_at));
+        }
+
+        // Importing the same file again only finds duplicates.
+        let report = import(&target, format, &exported, false, None, ANONYMOUS)
+            .await
+            .unwrap();
+        assert!(report.created.is_empty());
+        assert_eq!(report.duplicates.len(), 2);
+        assert_eq!(target.get_all_tasks().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_json_round_trip() {
+        assert_round_trip(Format::Json).await;
+    }
+
+    #[tokio::test]
+    async fn test_csv_round_trip() {
+        assert_round_trip(Format::Csv).await;
+    }
+
+    #[tokio::test]
+    async fn test_todotxt_round_trip() {
+        assert_round_trip(Format::TodoTxt).await;
+    }
+
+    #[tokio::test]
+    async fn test_ical_round_trip() {
+        assert_round_trip(Format::ICal).await;
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_writes_nothing() {
+        let source = seeded_db().await;
+        let exported = export_string(&source, Format::Json).await;
+
+        let target = db().await;
+        let report = import(&target, Format::Json, &exported, true, None, ANONYMOUS)
+            .await
+            .unwrap();
+        assert!(report.dry_run);
+        assert_eq!(report.created.len(), 2);
+        assert!(target.get_all_tasks().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_records_are_reported() {
+        let input = "title,completed\nok,false\n   ,false\nbad,maybe\n";
+        let report = import(&db().await, Format::Csv, input, true, None, ANONYMOUS)
+            .await
+            .unwrap();
+        assert_eq!(report.created.len(), 1);
+        let lines: Vec<usize> = report.invalid.iter().map(|r| r.line).collect();
+        assert_eq!(lines, vec![3, 4]);
+
+        assert!(
+            import(&db().await, Format::Json, "{", true, None, ANONYMOUS)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_pages() {
+        let db = db().await;
+        let records = (0..PAGE_SIZE + 1)
+            .map(|i| TaskRecord {
+                title: format!("task {i}"),
+                completed: false,
+                created_at: None,
+                due: None,
+                priority: None,
+            })
+            .collect();
+        db.insert_tasks(records, None, ANONYMOUS).await.unwrap();
+
+        let exported = export_string(&db, Format::Json).await;
+        let tasks: Vec<Task> = json::from_str(&exported).unwrap();
+        assert_eq!(tasks, db.get_all_tasks().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_import_into_workspace() {
+        let db = seeded_db().await;
+        let input = r#"[{"title":"buy milk","created_at":"2023-04-02T10:30:00Z"}]"#;
+        let workspace = Some("workspaces:a");
+        let report = import(&db, Format::Json, input, false, workspace, ANONYMOUS)
+            .await
+            .unwrap();
+        // Tasks outside the workspace are not duplicates of ones in it.
+        assert_eq!(report.created.len(), 1);
+        let tasks = db.get_tasks_in(workspace).await.unwrap();
+        assert_eq!(tasks[0].workspace.as_deref(), workspace);
+    }
+
+    #[test]
+    fn test_parse_todotxt_line() {
+        let record = parse_todotxt_line("(A) 2023-04-02 call mom +family due:2023-04-09").unwrap();
+        assert_eq!(record.title, "call mom +family");
+        assert!(!record.completed);
+        assert_eq!(record.priority, Some(1));
+        assert_eq!(record.due, Some("2023-04-09T00:00:00Z".parse().unwrap()));
+        assert_eq!(
+            record.created_at,
+            Some("2023-04-02T00:00:00Z".parse().unwrap())
+        );
+
+        // A single date on a completed task is its completion date.
+        let record = parse_todotxt_line("x 2023-04-05 file taxes").unwrap();
+        assert!(record.completed);
+        assert_eq!(record.created_at, None);
+
+        assert!(parse_todotxt_line("x 2023-04-05 2023-04-02 ").is_err());
+    }
+}
@@ -1,2 +1,4 @@
+pub mod crypto;
 pub mod macros;
 pub mod try_froms;
+pub mod value;
@@ -1,5 +1,8 @@
+use crate::db::Task;
 use crate::prelude::{Error, W};
-use surrealdb::sql::{Array, Object, Value};
+use crate::utils::value::from_value;
+use chrono::{DateTime, Utc};
+use surrealdb::sql::{Array, Number, Object, Value};
 
 impl TryFrom<W<Value>> for Object {
     type Error = Error;
@@ -21,16 +24,47 @@ impl TryFrom<W<Value>> for Array {
     }
 }
 
+/// Only whole numbers convert; `1.5` is an error rather than `1`.
 impl TryFrom<W<Value>> for i64 {
     type Error = Error;
     fn try_from(val: W<Value>) -> Result<i64, Error> {
+        // i64::MAX rounds up to 2^63 as a float, so the upper bound is open.
+        let range = (i64::MIN as f64)..(i64::MAX as f64);
         match val.0 {
-            Value::Number(obj) => Ok(obj.as_int()),
+            Value::Number(Number::Int(int)) => Ok(int),
+            // Floats never count as integers, so whole ones are found here.
+            Value::Number(Number::Float(float))
+                if float.fract() == 0.0 && range.contains(&float) =>
+            {
+                Ok(float as i64)
+            }
+            // `as_float` takes the number by value, so the guard checks a copy.
+            Value::Number(obj) if obj.is_integer() && range.contains(&obj.clone().as_float()) => {
+                Ok(obj.as_int())
+            }
             _ => Err(Error::XValueNotOfType("i64")),
         }
     }
 }
 
+impl TryFrom<W<Value>> for u8 {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<u8, Error> {
+        let int: i64 = val.try_into()?;
+        u8::try_from(int).map_err(|_| Error::XValueNotOfType("u8"))
+    }
+}
+
+impl TryFrom<W<Value>> for f64 {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<f64, Error> {
+        match val.0 {
+            Value::Number(obj) => Ok(obj.as_float()),
+            _ => Err(Error::XValueNotOfType("f64")),
+        }
+    }
+}
+
 impl TryFrom<W<Value>> for bool {
     type Error = Error;
     fn try_from(val: W<Value>) -> Result<bool, Error> {
@@ -52,3 +86,192 @@ impl TryFrom<W<Value>> for String {
         }
     }
 }
+
+impl TryFrom<W<Value>> for DateTime<Utc> {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<DateTime<Utc>, Error> {
+        match val.0 {
+            Value::Datetime(dt) => Ok(dt.0),
+            _ => Err(Error::XValueNotOfType("Datetime")),
+        }
+    }
+}
+
+/// `NONE` and `NULL` both read as `None`.
+impl<T> TryFrom<W<Value>> for Option<T>
+where
+    T: TryFrom<W<Value>, Error = Error>,
+{
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Option<T>, Error> {
+        match val.0 {
+            Value::None | Value::Null => Ok(None),
+            v => Ok(Some(W(v).try_into()?)),
+        }
+    }
+}
+
+impl<T> TryFrom<W<Value>> for Vec<T>
+where
+    T: TryFrom<W<Value>, Error = Error>,
+{
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Vec<T>, Error> {
+        let array: Array = val.try_into()?;
+        array.into_iter().map(|v| W(v).try_into()).collect()
+    }
+}
+
+impl TryFrom<W<Object>> for Task {
+    type Error = Error;
+    fn try_from(val: W<Object>) -> Result<Task, Error> {
+        from_value(Value::Object(val.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::DB;
+    use chrono::TimeZone;
+    use proptest::prelude::*;
+    use std::sync::Arc;
+    use surrealdb::{dbs::Session, kvs::Datastore};
+
+    fn datetime() -> impl Strategy<Value = DateTime<Utc>> {
+        // Years 1970 to 2099, to the nanosecond.
+        (0i64..4_102_444_800, 0u32..1_000_000_000)
+            .prop_map(|(secs, nanos)| Utc.timestamp_opt(secs, nanos).unwrap())
+    }
+
+    prop_compose! {
+        fn task()(
+            key in "[a-z0-9]{1,20}",
+            title in any::<String>(),
+            completed in any::<bool>(),
+            created_at in datetime(),
+            due in proptest::option::of(datetime()),
+            priority in proptest::option::of(1u8..=9),
+            remind_at in proptest::option::of(datetime()),
+            rank in "[0-9a-z]{0,8}",
+            status in "[a-z_]{0,12}",
+            workspace in proptest::option::of("workspaces:[a-z0-9]{1,20}"),
+            assignee in proptest::option::of("user:[0-9a-f]{16}"),
+        ) -> Task {
+            Task { id: format!("tasks:{key}"), title, completed, created_at, due, priority, remind_at, rank, status, workspace, assignee }
+        }
+    }
+
+    /// Evaluates a SurrealQL expression, for values that have no `From`.
+    async fn eval(expr: &str) -> Value {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_ns", "my_db");
+        let db = DB { ds, sesh };
+        let res = db.execute(&format!("RETURN {expr}"), None).await.unwrap();
+        res.into_iter().next().unwrap().result.unwrap()
+    }
+
+    proptest! {
+        #[test]
+        fn test_task_round_trip(task in task()) {
+            let value = Value::try_from(W(task.clone())).unwrap();
+            let object: Object = W(value).try_into().unwrap();
+            let back: Task = W(object).try_into().unwrap();
+            prop_assert_eq!(back, task);
+        }
+
+        #[test]
+        fn test_i64_round_trip(int in any::<i64>()) {
+            prop_assert_eq!(i64::try_from(W(Value::from(int))).unwrap(), int);
+        }
+
+        #[test]
+        fn test_f64_round_trip(float in any::<f64>().prop_filter("NaN", |f| !f.is_nan())) {
+            prop_assert_eq!(f64::try_from(W(Value::from(float))).unwrap(), float);
+        }
+
+        #[test]
+        fn test_fractions_are_not_truncated(int in -1_000_000i64..1_000_000, fract in 0.01f64..0.99) {
+            let float = int as f64 + fract;
+            prop_assert!(i64::try_from(W(Value::from(float))).is_err());
+            prop_assert_eq!(i64::try_from(W(Value::from(int as f64))).unwrap(), int);
+        }
+
+        #[test]
+        fn test_string_and_bool_round_trip(text in any::<String>(), flag in any::<bool>()) {
+            prop_assert_eq!(String::try_from(W(Value::from(text.clone()))).unwrap(), text);
+            prop_assert_eq!(bool::try_from(W(Value::from(flag))).unwrap(), flag);
+        }
+
+        #[test]
+        fn test_vec_and_option(ints in proptest::collection::vec(any::<i64>(), 0..20)) {
+            let value = Value::from(ints.iter().copied().map(Value::from).collect::<Vec<_>>());
+            prop_assert_eq!(Vec::<i64>::try_from(W(value.clone())).unwrap(), ints.clone());
+            prop_assert_eq!(Option::<Vec<i64>>::try_from(W(value)).unwrap(), Some(ints));
+        }
+    }
+
+    #[test]
+    fn test_number_edge_cases() {
+        let int = |v: Value| i64::try_from(W(v));
+
+        assert!(int(Value::from(f64::NAN)).is_err());
+        assert!(int(Value::from(f64::INFINITY)).is_err());
+        assert!(int(Value::from(1e19)).is_err());
+        assert!(int(Value::from(-1e19)).is_err());
+        assert_eq!(int(Value::from(-0.0)).unwrap(), 0);
+        assert_eq!(int(Value::from(i64::MIN as f64)).unwrap(), i64::MIN);
+        assert!(int(Value::from("1")).is_err());
+
+        assert_eq!(u8::try_from(W(Value::from(9i64))).unwrap(), 9);
+        assert!(u8::try_from(W(Value::from(256i64))).is_err());
+        assert!(u8::try_from(W(Value::from(-1i64))).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_decimals() {
+        assert_eq!(i64::try_from(W(eval("<decimal> 3").await)).unwrap(), 3);
+        assert!(i64::try_from(W(eval("<decimal> 2.5").await)).is_err());
+        assert_eq!(f64::try_from(W(eval("<decimal> 2.5").await)).unwrap(), 2.5);
+    }
+
+    #[test]
+    fn test_none_and_null() {
+        assert_eq!(Option::<i64>::try_from(W(Value::None)).unwrap(), None);
+        assert_eq!(Option::<i64>::try_from(W(Value::Null)).unwrap(), None);
+        assert!(Option::<i64>::try_from(W(Value::from(true))).is_err());
+        assert!(Vec::<i64>::try_from(W(Value::None)).is_err());
+        assert!(DateTime::<Utc>::try_from(W(Value::from("2023-04-02"))).is_err());
+    }
+
+    #[test]
+    fn test_task_requires_fields() {
+        let task = Task {
+            id: "tasks:abc".to_string(),
+            title: "test".to_string(),
+            completed: false,
+            created_at: Utc::now(),
+            due: None,
+            priority: None,
+            remind_at: None,
+            rank: "i".to_string(),
+            status: "todo".to_string(),
+            workspace: None,
+            assignee: None,
+        };
+        for key in ["id", "title", "completed", "created_at"] {
+            let mut object: Object = W(Value::try_from(W(task.clone())).unwrap())
+                .try_into()
+                .unwrap();
+            object.remove(key);
+            assert!(matches!(
+                Task::try_from(W(object)),
+                Err(Error::XConversion(message)) if message.contains(key)
+            ));
+        }
+
+        let mut object: Object = W(Value::try_from(W(task)).unwrap()).try_into().unwrap();
+        object.insert("priority".to_string(), Value::from(1.5));
+        assert!(Task::try_from(W(object)).is_err());
+    }
+}
@@ -0,0 +1,622 @@
+//! Converts between SurrealDB values and any `Serialize`/`Deserialize` type,
+//! so models don't need hand-written `From` and `TryFrom` impls.
+//!
+//! Values map to serde's data model the obvious way. On the way out,
+//! datetimes and record ids become strings. On the way in, types choose
+//! how their fields are stored: datetimes serialized with
+//! [`todo_models::datetime`] become SurrealDB datetimes, and strings
+//! serialized with [`thing`] become record ids.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{
+    de::{self, value::MapDeserializer, value::SeqDeserializer, IntoDeserializer, Visitor},
+    ser::{self, Serialize},
+    Deserialize,
+};
+use surrealdb::sql::{thing as parse_thing, Array, Number, Object, Value};
+
+use crate::prelude::{Error, W};
+
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, Error> {
+    value.serialize(Serializer)
+}
+
+pub fn from_value<'de, T: Deserialize<'de>>(value: Value) -> Result<T, Error> {
+    T::deserialize(Deserializer(value))
+}
+
+/// Serializes a `String` field as a record id, such as `tasks:abc`.
+pub mod thing {
+    use serde::Serializer;
+
+    pub const NEWTYPE: &str = "$todo_api::Thing";
+
+    // No stored model has a record-id field yet, only the tests.
+    #[allow(dead_code)]
+    pub fn serialize<S: Serializer>(id: &str, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(NEWTYPE, id)
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::XConversion(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::XConversion(msg.to_string())
+    }
+}
+
+struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Value;
+    type Error = Error;
+
+    type SerializeSeq = SerializeVec;
+    type SerializeTuple = SerializeVec;
+    type SerializeTupleStruct = SerializeVec;
+    type SerializeTupleVariant = SerializeVariant<SerializeVec>;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeVariant<SerializeMap>;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, Error> {
+        let v = i64::try_from(v).map_err(|_| Error::XConversion(format!("{v} is too large")))?;
+        self.serialize_i64(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, Error> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, Error> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, Error> {
+        Ok(v.iter()
+            .map(|b| Value::from(i64::from(*b)))
+            .collect::<Vec<_>>()
+            .into())
+    }
+
+    fn serialize_none(self) -> Result<Value, Error> {
+        Ok(Value::None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, Error> {
+        Ok(Value::None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        let value = value.serialize(self)?;
+        let text = match (name, value) {
+            (todo_models::datetime::NEWTYPE | thing::NEWTYPE, Value::Strand(text)) => {
+                text.as_string()
+            }
+            (_, value) => return Ok(value),
+        };
+
+        if name == thing::NEWTYPE {
+            let thing = parse_thing(&text)
+                .map_err(|_| Error::XConversion(format!("invalid record id '{text}'")))?;
+            return Ok(thing.into());
+        }
+        let datetime: DateTime<Utc> = text
+            .parse()
+            .map_err(|_| Error::XConversion(format!("invalid datetime '{text}'")))?;
+        Ok(datetime.into())
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        let mut map = BTreeMap::new();
+        map.insert(variant.to_string(), value.serialize(self)?);
+        Ok(map.into())
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeVec, Error> {
+        Ok(SerializeVec(Vec::with_capacity(len.unwrap_or(0))))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeVec, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeVec, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeVec>, Error> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeMap, Error> {
+        Ok(SerializeMap {
+            map: BTreeMap::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeMap, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeMap>, Error> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+struct SerializeVec(Vec<Value>);
+
+impl ser::SerializeSeq for SerializeVec {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.0.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(self.0.into())
+    }
+}
+
+impl ser::SerializeTuple for SerializeVec {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeVec {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct SerializeMap {
+    map: BTreeMap<String, Value>,
+    key: Option<String>,
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        let key = match key.serialize(Serializer)? {
+            Value::Strand(key) => key.as_string(),
+            Value::Number(key) => key.to_string(),
+            _ => return Err(Error::XConversion("map keys must be strings".into())),
+        };
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.key.take().expect("serialize_key is called first");
+        self.map.insert(key, value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(self.map.into())
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.map
+            .insert(key.to_string(), value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+/// Enum variants with data are written as `{ variant: data }`.
+struct SerializeVariant<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl<S> SerializeVariant<S> {
+    fn wrap(variant: &'static str, value: Value) -> Value {
+        let mut map = BTreeMap::new();
+        map.insert(variant.to_string(), value);
+        map.into()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeVec> {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(&mut self.inner, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        let value = ser::SerializeSeq::end(self.inner)?;
+        Ok(Self::wrap(self.variant, value))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeMap> {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        ser::SerializeStruct::serialize_field(&mut self.inner, key, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        let value = ser::SerializeMap::end(self.inner)?;
+        Ok(Self::wrap(self.variant, value))
+    }
+}
+
+struct Deserializer(Value);
+
+impl<'de> IntoDeserializer<'de, Error> for Deserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::None | Value::Null => visitor.visit_unit(),
+            Value::False => visitor.visit_bool(false),
+            Value::True => visitor.visit_bool(true),
+            Value::Number(Number::Int(v)) => visitor.visit_i64(v),
+            Value::Number(number) if number.is_integer() => {
+                // Whole decimals and floats outside the i64 range stay floats.
+                match i64::try_from(W(Value::Number(number.clone()))) {
+                    Ok(v) => visitor.visit_i64(v),
+                    Err(_) => visitor.visit_f64(number.as_float()),
+                }
+            }
+            Value::Number(number) => visitor.visit_f64(number.as_float()),
+            Value::Strand(strand) => visitor.visit_string(strand.as_string()),
+            Value::Datetime(datetime) => {
+                visitor.visit_string(datetime.0.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+            }
+            Value::Thing(thing) => visitor.visit_string(thing.to_string()),
+            Value::Array(array) => visit_array(array, visitor),
+            Value::Object(object) => visit_object(object, visitor),
+            other => Err(Error::XConversion(format!("cannot deserialize '{other}'"))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::None | Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let (variant, value) = match self.0 {
+            Value::Strand(variant) => (variant.as_string(), None),
+            Value::Object(object) if object.len() == 1 => {
+                let (variant, value) = object.0.into_iter().next().expect("one entry");
+                (variant, Some(value))
+            }
+            other => {
+                return Err(Error::XConversion(format!(
+                    "expected an enum variant, got '{other}'"
+                )))
+            }
+        };
+        visitor.visit_enum(EnumDeserializer { variant, value })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+fn visit_array<'de, V: Visitor<'de>>(array: Array, visitor: V) -> Result<V::Value, Error> {
+    let mut seq = SeqDeserializer::new(array.into_iter().map(Deserializer));
+    let value = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+    Ok(value)
+}
+
+fn visit_object<'de, V: Visitor<'de>>(object: Object, visitor: V) -> Result<V::Value, Error> {
+    let mut map = MapDeserializer::new(object.0.into_iter().map(|(k, v)| (k, Deserializer(v))));
+    let value = visitor.visit_map(&mut map)?;
+    map.end()?;
+    Ok(value)
+}
+
+struct EnumDeserializer {
+    variant: String,
+    value: Option<Value>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = Error;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, VariantDeserializer), Error> {
+        let variant: de::value::StringDeserializer<Error> = self.variant.into_deserializer();
+        let variant = seed.deserialize(variant)?;
+        Ok((variant, VariantDeserializer(self.value)))
+    }
+}
+
+struct VariantDeserializer(Option<Value>);
+
+impl<'de> de::VariantAccess<'de> for VariantDeserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        match self.0 {
+            None | Some(Value::None) | Some(Value::Null) => Ok(()),
+            Some(_) => Err(Error::XConversion("expected a unit variant".into())),
+        }
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        match self.0 {
+            Some(value) => seed.deserialize(Deserializer(value)),
+            None => Err(Error::XConversion("expected a newtype variant".into())),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Some(Value::Array(array)) => visit_array(array, visitor),
+            _ => Err(Error::XConversion("expected a tuple variant".into())),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.0 {
+            Some(Value::Object(object)) => visit_object(object, visitor),
+            _ => Err(Error::XConversion("expected a struct variant".into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Kind {
+        Plain,
+        Weighted(u8),
+        Span { from: i64, to: i64 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Nested {
+        #[serde(serialize_with = "thing::serialize")]
+        owner: String,
+        #[serde(serialize_with = "todo_models::datetime::serialize")]
+        at: DateTime<Utc>,
+        tags: Vec<String>,
+        kinds: Vec<Kind>,
+        ratio: f64,
+        note: Option<String>,
+        inner: Option<Box<Nested>>,
+    }
+
+    fn nested() -> Nested {
+        Nested {
+            owner: "users:ada".to_string(),
+            at: "2023-04-02T10:30:00.123456789Z".parse().unwrap(),
+            tags: vec!["a".to_string(), "b".to_string()],
+            kinds: vec![
+                Kind::Plain,
+                Kind::Weighted(3),
+                Kind::Span { from: -1, to: 1 },
+            ],
+            ratio: 0.5,
+            note: None,
+            inner: Some(Box::new(Nested {
+                owner: "users:bob".to_string(),
+                at: "2023-04-03T00:00:00Z".parse().unwrap(),
+                tags: vec![],
+                kinds: vec![],
+                ratio: 2.0,
+                note: Some("hi".to_string()),
+                inner: None,
+            })),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let value = to_value(&nested()).unwrap();
+        assert_eq!(from_value::<Nested>(value).unwrap(), nested());
+    }
+
+    #[test]
+    fn test_special_types_are_kept() {
+        let mut object: Object = W(to_value(&nested()).unwrap()).try_into().unwrap();
+        assert!(matches!(object.remove("owner"), Some(Value::Thing(_))));
+        assert!(matches!(object.remove("at"), Some(Value::Datetime(_))));
+        assert!(matches!(
+            object.remove("ratio"),
+            Some(Value::Number(Number::Float(_)))
+        ));
+        assert!(matches!(object.remove("note"), Some(Value::None)));
+
+        let kinds: Array = W(object.remove("kinds").unwrap()).try_into().unwrap();
+        assert_eq!(kinds[0], Value::from("Plain"));
+        assert!(matches!(&kinds[1], Value::Object(o) if o.contains_key("Weighted")));
+    }
+
+    #[test]
+    fn test_errors() {
+        #[derive(Debug, Serialize)]
+        struct BadThing {
+            #[serde(serialize_with = "thing::serialize")]
+            id: String,
+        }
+        let bad = BadThing {
+            id: "not a thing".to_string(),
+        };
+        assert!(matches!(to_value(&bad), Err(Error::XConversion(_))));
+        assert!(to_value(&u64::MAX).is_err());
+
+        assert!(from_value::<u8>(Value::from(300i64)).is_err());
+        assert!(from_value::<String>(Value::from(1i64)).is_err());
+        assert!(from_value::<Nested>(Value::from("x")).is_err());
+    }
+}
@@ -0,0 +1,43 @@
+//! Rejects invalid task input with `422 Unprocessable Entity` and the errors
+//! of every field. The rules themselves live in `todo_models::validation`,
+//! so the web app checks input the same way before sending it.
+
+use rocket::serde::json::Json;
+use todo_models::ValidationErrors;
+
+#[derive(Responder, Debug)]
+pub enum Rejection {
+    #[response(status = 422)]
+    Invalid(Json<ValidationErrors>),
+    /// Input that could not be read at all, such as a malformed import.
+    #[response(status = 400)]
+    BadRequest(String),
+    /// Changes to something the caller does not own, and anything their
+    /// workspace role does not allow.
+    #[response(status = 403)]
+    Forbidden(String),
+    /// Records that never existed, for routes that otherwise answer for
+    /// them with something else.
+    #[response(status = 404)]
+    NotFound(String),
+    /// Changes that would leave something inconsistent, such as a
+    /// workspace without an owner.
+    #[response(status = 409)]
+    Conflict(String),
+    /// Uploads over the configured size limit.
+    #[response(status = 413)]
+    TooLarge(String),
+    Failed(std::io::Error),
+}
+
+impl From<ValidationErrors> for Rejection {
+    fn from(errors: ValidationErrors) -> Self {
+        Rejection::Invalid(Json(errors))
+    }
+}
+
+impl From<std::io::Error> for Rejection {
+    fn from(error: std::io::Error) -> Self {
+        Rejection::Failed(error)
+    }
+}
@@ -0,0 +1,712 @@
+//! Outgoing webhooks.
+//!
+//! A change to a task that a webhook subscribes to is queued as a delivery
+//! in the same transaction as the change; see
+//! [`ChangeLog`](crate::db::ChangeLog). The worker started by the
+//! [`Webhooks`] fairing posts due deliveries, retrying failed ones with
+//! exponential backoff, and each delivery keeps the outcome of its last
+//! attempt, which makes up the webhook's delivery log. Webhooks are only
+//! posted to public addresses, and redirects are not followed.
+//!
+//! A webhook belongs to a workspace, and only gets changes to its tasks;
+//! its owners manage it. Webhooks outside any workspace get changes to the
+//! tasks outside any, and are managed by whoever added them.
+
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use hyper::client::connect::dns::Name;
+use reqwest::{
+    dns::{Addrs, Resolve, Resolving},
+    redirect,
+};
+use rocket::{
+    fairing::{self, Fairing, Info, Kind},
+    futures::stream::{self, StreamExt},
+    serde::{Deserialize, Serialize},
+    Build, Orbit, Rocket,
+};
+use surrealdb::sql::Value;
+use tokio::time::Instant;
+
+use crate::actor::ANONYMOUS;
+use crate::db::{AffectedRows, TaskAction, DB};
+use crate::error::Error;
+use crate::query::{Order, Query, Return, Target};
+use crate::utils::{
+    crypto::{hex, hmac},
+    value::to_value,
+};
+use crate::workspaces::Role;
+use todo_models::is_public_ip;
+pub use todo_models::{DeliveryStatus, NewWebhook, Webhook, WebhookDelivery};
+
+/// How many deliveries the worker posts per check, and how many the log
+/// lists.
+const BATCH: u64 = 100;
+
+/// How many deliveries the worker posts at once.
+const CONCURRENCY: usize = 10;
+
+/// Retries wait at most this long, however many attempts came before.
+const MAX_BACKOFF: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A webhook as stored, with the secret its payloads are signed with.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Subscription {
+    pub id: String,
+    pub url: String,
+    pub secret: String,
+    /// Who added the webhook. Webhooks added before they had owners have
+    /// none, and can only be managed through their workspace.
+    #[serde(default)]
+    pub owner: String,
+    /// The workspace whose changes are delivered, or `None` for those to
+    /// tasks outside any.
+    #[serde(default)]
+    pub workspace: Option<String>,
+}
+
+/// A delivery as the worker reads it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct Queued {
+    id: String,
+    webhook: String,
+    /// As sent in the `X-Todo-Event` header.
+    event: String,
+    payload: String,
+    attempts: u32,
+}
+
+/// Queues `payload` for `webhook`, due at once.
+pub fn delivery_query(webhook: &str, event: TaskAction, payload: &str) -> Result<Query, Error> {
+    let now = Utc::now();
+    Ok(Query::create("webhook_deliveries")
+        .set("webhook", webhook)
+        .set("event", to_value(&event)?)
+        .set("payload", payload)
+        .set("status", to_value(&DeliveryStatus::Pending)?)
+        .set("attempts", 0_i64)
+        .set("created_at", now)
+        .set("next_attempt_at", now))
+}
+
+impl DB {
+    /// The webhooks of a workspace, which only its owners may list, or
+    /// `user`'s outside any with `None`, oldest first.
+    pub async fn get_webhooks(
+        &self,
+        workspace: Option<String>,
+        user: &str,
+    ) -> Result<Vec<Webhook>, Error> {
+        if user == ANONYMOUS {
+            return Err(Error::XAnonymous);
+        }
+        self.authorize(workspace.as_deref(), user, Role::Owner)
+            .await?;
+        let mut query = Query::select("webhooks").where_eq(
+            "workspace",
+            workspace.clone().map_or(Value::None, Value::from),
+        );
+        if workspace.is_none() {
+            query = query.where_eq("owner", user);
+        }
+        self.fetch(query.order_by("created_at", Order::Asc)).await
+    }
+
+    async fn get_subscription(&self, id: String) -> Result<Subscription, Error> {
+        // Any record id parses, so other tables are turned away here.
+        if !id.starts_with("webhooks:") {
+            return Err(Error::XRecordNotFound(id));
+        }
+        let query = Query::select(Target::record(&id)?);
+        self.fetch_one(query)
+            .await?
+            .ok_or(Error::XRecordNotFound(id))
+    }
+
+    /// Checks that `user` may manage the webhook: an owner of its
+    /// workspace, or whoever added it for webhooks outside any. Missing
+    /// webhooks pass, so routes answer for them as they did before.
+    pub async fn authorize_webhook(&self, id: &str, user: &str) -> Result<(), Error> {
+        if user == ANONYMOUS {
+            return Err(Error::XAnonymous);
+        }
+        let webhook = match self.get_subscription(id.to_string()).await {
+            Ok(webhook) => webhook,
+            Err(Error::XRecordNotFound(_)) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        match webhook.workspace {
+            Some(workspace) => self.authorize(Some(&workspace), user, Role::Owner).await,
+            None if webhook.owner == user => Ok(()),
+            None => Err(Error::XForbidden(id.to_string())),
+        }
+    }
+
+    /// Webhooks with their secrets, for queuing and signing deliveries.
+    pub async fn get_subscriptions(&self) -> Result<Vec<Subscription>, Error> {
+        self.fetch(Query::select("webhooks")).await
+    }
+
+    /// The webhooks of a workspace, or those outside any with `None`, that
+    /// subscribe to `event`, with their secrets.
+    pub async fn get_subscriptions_to(
+        &self,
+        workspace: Option<&str>,
+        event: TaskAction,
+    ) -> Result<Vec<Subscription>, Error> {
+        let query = Query::select("webhooks")
+            .where_eq("workspace", workspace.map_or(Value::None, Value::from))
+            .where_cmp("events", "CONTAINS", to_value(&event)?);
+        self.fetch(query).await
+    }
+
+    /// Subscribes a URL to changes to the tasks of a workspace, which only
+    /// its owners may, or to those outside any with `None`.
+    pub async fn add_webhook(
+        &self,
+        webhook: NewWebhook,
+        workspace: Option<String>,
+        user: &str,
+    ) -> Result<Webhook, Error> {
+        if user == ANONYMOUS {
+            return Err(Error::XAnonymous);
+        }
+        self.authorize(workspace.as_deref(), user, Role::Owner)
+            .await?;
+        let query = Query::create("webhooks")
+            .set("url", webhook.url)
+            .set("secret", webhook.secret)
+            .set("events", to_value(&webhook.events)?)
+            .set("owner", user)
+            .set("workspace", workspace.map_or(Value::None, Value::from))
+            .set("created_at", Utc::now());
+        self.fetch_one(query).await?.ok_or(Error::XNoResponse)
+    }
+
+    /// Deletes a webhook with its deliveries, sent or not. Missing webhooks
+    /// affect no rows.
+    pub async fn delete_webhook(&self, id: String, user: &str) -> Result<AffectedRows, Error> {
+        self.authorize_webhook(&id, user).await?;
+        match self.get_subscription(id.clone()).await {
+            Ok(_) => {}
+            Err(Error::XRecordNotFound(_)) => return Ok(AffectedRows { affected_rows: 0 }),
+            Err(e) => return Err(e),
+        }
+        let webhook = Query::delete(Target::record(&id)?);
+        let deliveries = Query::delete("webhook_deliveries").where_eq("webhook", id);
+        self.transaction(|tx| async move {
+            tx.add(webhook);
+            tx.add(deliveries);
+            Ok(())
+        })
+        .await?;
+        Ok(AffectedRows { affected_rows: 1 })
+    }
+
+    /// The webhook's latest deliveries, newest first.
+    pub async fn get_deliveries(
+        &self,
+        webhook: String,
+        user: &str,
+    ) -> Result<Vec<WebhookDelivery>, Error> {
+        self.authorize_webhook(&webhook, user).await?;
+        self.get_subscription(webhook.clone()).await?;
+        let query = Query::select("webhook_deliveries")
+            .where_eq("webhook", webhook)
+            .order_by("created_at", Order::Desc)
+            .limit(BATCH);
+        self.fetch(query).await
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct DeliveryConfig {
+    /// Seconds between checks for due deliveries.
+    pub interval: u64,
+    /// Attempts before a delivery is given up on.
+    pub max_attempts: u32,
+    /// Seconds before the first retry. Each later retry waits twice as
+    /// long as the one before, up to a day.
+    pub backoff: u64,
+    /// Seconds to wait for a webhook to answer.
+    pub timeout: u64,
+}
+
+impl Default for DeliveryConfig {
+    fn default() -> Self {
+        DeliveryConfig {
+            interval: 5,
+            max_attempts: 6,
+            backoff: 30,
+            timeout: 10,
+        }
+    }
+}
+
+/// Resolves webhook hosts to their public addresses only, so a name can't
+/// be used to reach the server's own network. Addresses written in the URL
+/// are checked when the webhook is added.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((name.as_str(), 0)).await?;
+            let addrs: Vec<SocketAddr> = addrs.filter(|addr| is_public_ip(addr.ip())).collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// The outcome of one post.
+struct Attempt {
+    response_status: Option<u16>,
+    error: Option<String>,
+}
+
+/// Posts queued deliveries, managed by [`Webhooks`].
+#[derive(Clone)]
+pub struct Deliverer {
+    client: reqwest::Client,
+    interval: Duration,
+    max_attempts: u32,
+    backoff: Duration,
+}
+
+impl Deliverer {
+    pub fn new(config: DeliveryConfig) -> Result<Self, String> {
+        if config.interval == 0 || config.max_attempts == 0 {
+            return Err("`interval` and `max_attempts` must be at least 1".to_string());
+        }
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout))
+            .dns_resolver(Arc::new(PublicResolver))
+            .redirect(redirect::Policy::none())
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Deliverer {
+            client,
+            interval: Duration::from_secs(config.interval),
+            max_attempts: config.max_attempts,
+            backoff: Duration::from_secs(config.backoff),
+        })
+    }
+
+    /// Posts the deliveries due by `now`, the oldest first and several at
+    /// once, and returns how many were tried.
+    pub async fn deliver_due(&self, db: &DB, now: DateTime<Utc>) -> Result<usize, Error> {
+        let query = Query::select("webhook_deliveries")
+            .where_eq("status", to_value(&DeliveryStatus::Pending)?)
+            .where_cmp("next_attempt_at", "<=", now)
+            .order_by("next_attempt_at", Order::Asc)
+            .limit(BATCH);
+        let queued: Vec<Queued> = db.fetch(query).await?;
+
+        let webhooks = db.get_subscriptions().await?;
+        let tried = queued.len();
+        let deliveries: Vec<(Subscription, Queued)> = queued
+            .into_iter()
+            .filter_map(|delivery| {
+                // Deleted since the delivery was read.
+                let webhook = webhooks.iter().find(|w| w.id == delivery.webhook)?;
+                Some((webhook.clone(), delivery))
+            })
+            .collect();
+        stream::iter(deliveries)
+            .map(|(webhook, delivery)| async move {
+                self.deliver(db, &webhook, &delivery, now).await
+            })
+            .buffer_unordered(CONCURRENCY)
+            .for_each(|()| async {})
+            .await;
+        Ok(tried)
+    }
+
+    /// Posts one delivery and records the outcome. A failure to record it
+    /// is logged rather than returned, so the rest of the batch still goes
+    /// out, and the delivery is posted again on a later check.
+    async fn deliver(
+        &self,
+        db: &DB,
+        webhook: &Subscription,
+        delivery: &Queued,
+        now: DateTime<Utc>,
+    ) {
+        let attempt = self.post(webhook, delivery).await;
+        let recorded = match self.outcome_query(delivery, attempt, now) {
+            Ok(query) => db.run(query).await.map(drop),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = recorded {
+            warn!("failed to record delivery {}: {}", delivery.id, e);
+        }
+    }
+
+    async fn post(&self, webhook: &Subscription, delivery: &Queued) -> Attempt {
+        let signature = hex(&hmac(
+            webhook.secret.as_bytes(),
+            delivery.payload.as_bytes(),
+        ));
+        let response = self
+            .client
+            .post(&webhook.url)
+            .header("Content-Type", "application/json")
+            .header("X-Todo-Event", &delivery.event)
+            .header("X-Todo-Delivery", &delivery.id)
+            .header("X-Todo-Signature", format!("sha256={signature}"))
+            .body(delivery.payload.clone())
+            .send()
+            .await;
+        match response {
+            Ok(response) => Attempt {
+                response_status: Some(response.status().as_u16()),
+                error: (!response.status().is_success())
+                    .then(|| format!("webhook answered {}", response.status())),
+            },
+            Err(e) => Attempt {
+                response_status: None,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+
+    /// Records an attempt, and when to try again if it failed.
+    fn outcome_query(
+        &self,
+        delivery: &Queued,
+        attempt: Attempt,
+        now: DateTime<Utc>,
+    ) -> Result<Query, Error> {
+        let attempts = delivery.attempts + 1;
+        let query = Query::update(Target::record(&delivery.id)?)
+            .set("attempts", i64::from(attempts))
+            .set(
+                "response_status",
+                attempt
+                    .response_status
+                    .map_or(Value::None, |status| i64::from(status).into()),
+            )
+            .set(
+                "error",
+                attempt.error.clone().map_or(Value::None, Value::from),
+            )
+            .returning(Return::None);
+        let query = match attempt.error {
+            None => query
+                .set("status", to_value(&DeliveryStatus::Delivered)?)
+                .set("delivered_at", now)
+                .set_expr("next_attempt_at", "NONE"),
+            Some(_) if attempts >= self.max_attempts => query
+                .set("status", to_value(&DeliveryStatus::Failed)?)
+                .set_expr("next_attempt_at", "NONE"),
+            Some(_) => query.set("next_attempt_at", now + self.retry_delay(attempts)),
+        };
+        Ok(query)
+    }
+
+    /// How long to wait after the `attempts`th failed attempt.
+    fn retry_delay(&self, attempts: u32) -> chrono::Duration {
+        let delay = self
+            .backoff
+            .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+            .min(MAX_BACKOFF);
+        chrono::Duration::from_std(delay).expect("delays are at most a day")
+    }
+}
+
+/// Reads the `webhooks` config at ignition and starts the delivery worker
+/// at liftoff. It stops when the server shuts down.
+pub struct Webhooks;
+
+#[rocket::async_trait]
+impl Fairing for Webhooks {
+    fn info(&self) -> Info {
+        Info {
+            name: "Webhook deliveries",
+            kind: Kind::Ignite | Kind::Liftoff,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let config = match rocket.figment().find_value("webhooks") {
+            Ok(_) => rocket
+                .figment()
+                .extract_inner::<DeliveryConfig>("webhooks")
+                .map_err(|e| e.to_string()),
+            Err(_) => Ok(DeliveryConfig::default()),
+        };
+        match config.and_then(Deliverer::new) {
+            Ok(deliverer) => Ok(rocket.manage(deliverer)),
+            Err(e) => {
+                error!("invalid webhooks config: {}", e);
+                Err(rocket)
+            }
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let (Some(db), Some(deliverer)) = (rocket.state::<DB>(), rocket.state::<Deliverer>())
+        else {
+            return;
+        };
+        let db = db.clone();
+        let deliverer = deliverer.clone();
+        let interval = deliverer.interval;
+        let mut ticks = tokio::time::interval_at(Instant::now() + interval, interval);
+        let shutdown = rocket.shutdown();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = ticks.tick() => {}
+                    _ = shutdown.clone() => break,
+                }
+                if let Err(e) = deliverer.deliver_due(&db, Utc::now()).await {
+                    warn!("failed to deliver webhooks: {}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::WebhookPayload;
+    use std::sync::Arc;
+    use surrealdb::{dbs::Session, kvs::Datastore};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        sync::mpsc,
+    };
+
+    const ADA: &str = "user:ada";
+
+    async fn db() -> DB {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        DB { ds, sesh }
+    }
+
+    fn new_webhook(url: &str, events: Vec<TaskAction>) -> NewWebhook {
+        NewWebhook {
+            url: url.to_string(),
+            secret: "0123456789abcdef".to_string(),
+            events,
+        }
+    }
+
+    fn deliverer(max_attempts: u32) -> Deliverer {
+        Deliverer::new(DeliveryConfig {
+            max_attempts,
+            backoff: 1,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    /// A webhook receiver that answers with `statuses` in turn and sends
+    /// back each request's head and body.
+    async fn receiver(statuses: Vec<&'static str>) -> (String, mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buf = [0; 4096];
+                let head_len = loop {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    if let Some(i) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break i + 4;
+                    }
+                };
+                let head = String::from_utf8(request[..head_len].to_vec()).unwrap();
+                let len: usize = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length: "))
+                    .map_or(0, |len| len.parse().unwrap());
+                while request.len() < head_len + len {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let response = format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\n\r\n");
+                stream.write_all(response.as_bytes()).await.unwrap();
+                tx.send(String::from_utf8(request).unwrap()).unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    #[tokio::test]
+    async fn test_changes_queue_deliveries() {
+        let db = db().await;
+        let events = vec![TaskAction::Created, TaskAction::Deleted];
+        let webhook = db
+            .add_webhook(new_webhook("http://127.0.0.1:1/hook", events), None, ADA)
+            .await
+            .unwrap();
+        let other = db
+            .add_webhook(
+                new_webhook("http://127.0.0.1:1/other", vec![TaskAction::Toggled]),
+                None,
+                ADA,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_webhooks(None, ADA).await.unwrap(),
+            [webhook.clone(), other.clone()]
+        );
+        // Changes only load the webhooks of their workspace and event.
+        let subscribed = db
+            .get_subscriptions_to(None, TaskAction::Toggled)
+            .await
+            .unwrap();
+        assert_eq!(subscribed.len(), 1);
+        assert_eq!(subscribed[0].id, other.id);
+        assert!(db
+            .get_subscriptions_to(Some("workspaces:a"), TaskAction::Created)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(db.get_webhooks(None, "user:bob").await.unwrap().is_empty());
+        let res = db
+            .add_webhook(
+                new_webhook("http://127.0.0.1:1/hook", vec![]),
+                None,
+                ANONYMOUS,
+            )
+            .await;
+        assert!(matches!(res, Err(Error::XAnonymous)));
+
+        let task = db.add_task("test".to_string(), ANONYMOUS).await.unwrap();
+        db.toggle_task(task.id.clone(), ANONYMOUS).await.unwrap();
+        db.delete_task(task.id.clone(), ANONYMOUS).await.unwrap();
+
+        let deliveries = db.get_deliveries(webhook.id.clone(), ADA).await.unwrap();
+        let events: Vec<TaskAction> = deliveries.iter().map(|d| d.event).collect();
+        assert_eq!(events, [TaskAction::Deleted, TaskAction::Created]);
+        assert!(deliveries
+            .iter()
+            .all(|d| d.status == DeliveryStatus::Pending && d.attempts == 0));
+
+        let queued: Vec<Queued> = db
+            .fetch(Query::select("webhook_deliveries").where_eq("webhook", webhook.id.clone()))
+            .await
+            .unwrap();
+        let payload: WebhookPayload = rocket::serde::json::from_str(&queued[0].payload).unwrap();
+        assert_eq!(payload.task.id, task.id);
+        assert_eq!(payload.actor, ANONYMOUS);
+
+        let res = db.get_deliveries(webhook.id.clone(), "user:bob").await;
+        assert!(matches!(res, Err(Error::XForbidden(_))));
+        let res = db.delete_webhook(webhook.id.clone(), "user:bob").await;
+        assert!(matches!(res, Err(Error::XForbidden(_))));
+        let res = db.delete_webhook(webhook.id.clone(), ADA).await.unwrap();
+        assert_eq!(res.affected_rows, 1);
+        let res = db.get_deliveries(webhook.id, ADA).await;
+        assert!(matches!(res, Err(Error::XRecordNotFound(_))));
+        let left: Vec<Queued> = db.fetch(Query::select("webhook_deliveries")).await.unwrap();
+        assert_eq!(left.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_deliveries_are_signed_and_retried() {
+        let db = db().await;
+        let (url, mut requests) =
+            receiver(vec!["500 Internal Server Error", "204 No Content"]).await;
+        let webhook = db
+            .add_webhook(new_webhook(&url, vec![TaskAction::Created]), None, ADA)
+            .await
+            .unwrap();
+        db.add_task("test".to_string(), ANONYMOUS).await.unwrap();
+        let deliverer = deliverer(3);
+
+        let now = Utc::now();
+        assert_eq!(deliverer.deliver_due(&db, now).await.unwrap(), 1);
+        let request = requests.recv().await.unwrap();
+        let (head, body) = request.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("POST /hook HTTP/1.1\r\n"));
+        assert!(head.contains("x-todo-event: created\r\n"));
+        let signature = hex(&hmac(b"0123456789abcdef", body.as_bytes()));
+        assert!(head.contains(&format!("x-todo-signature: sha256={signature}\r\n")));
+
+        let deliveries = db.get_deliveries(webhook.id.clone(), ADA).await.unwrap();
+        assert_eq!(deliveries[0].status, DeliveryStatus::Pending);
+        assert_eq!(deliveries[0].response_status, Some(500));
+        assert_eq!(
+            deliveries[0].next_attempt_at,
+            Some(now + chrono::Duration::seconds(1))
+        );
+
+        // Not due again until the backoff has passed.
+        assert_eq!(deliverer.deliver_due(&db, now).await.unwrap(), 0);
+        let later = now + chrono::Duration::seconds(1);
+        assert_eq!(deliverer.deliver_due(&db, later).await.unwrap(), 1);
+        requests.recv().await.unwrap();
+
+        let deliveries = db.get_deliveries(webhook.id, ADA).await.unwrap();
+        assert_eq!(deliveries[0].status, DeliveryStatus::Delivered);
+        assert_eq!(deliveries[0].attempts, 2);
+        assert_eq!(deliveries[0].response_status, Some(204));
+        assert_eq!(deliveries[0].error, None);
+        assert_eq!(deliveries[0].delivered_at, Some(later));
+    }
+
+    #[tokio::test]
+    async fn test_deliveries_are_given_up() {
+        let db = db().await;
+        let webhook = db
+            .add_webhook(
+                new_webhook("http://127.0.0.1:1/hook", vec![TaskAction::Created]),
+                None,
+                ADA,
+            )
+            .await
+            .unwrap();
+        db.add_task("test".to_string(), ANONYMOUS).await.unwrap();
+        let deliverer = deliverer(2);
+
+        let now = Utc::now();
+        deliverer.deliver_due(&db, now).await.unwrap();
+        let later = now + chrono::Duration::seconds(1);
+        deliverer.deliver_due(&db, later).await.unwrap();
+
+        let deliveries = db.get_deliveries(webhook.id, ADA).await.unwrap();
+        assert_eq!(deliveries[0].status, DeliveryStatus::Failed);
+        assert_eq!(deliveries[0].attempts, 2);
+        assert_eq!(deliveries[0].response_status, None);
+        assert!(deliveries[0].error.is_some());
+        assert_eq!(deliveries[0].next_attempt_at, None);
+        assert_eq!(deliverer.deliver_due(&db, later).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_only_public_addresses_are_resolved() {
+        let name: Name = "localhost".parse().unwrap();
+        let Err(e) = PublicResolver.resolve(name).await else {
+            panic!("localhost resolved");
+        };
+        assert!(e.to_string().contains("no public address"));
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let deliverer = deliverer(40);
+        let seconds = |attempts| deliverer.retry_delay(attempts).num_seconds();
+        assert_eq!([seconds(1), seconds(2), seconds(3)], [1, 2, 4]);
+        assert_eq!(seconds(39), 24 * 60 * 60);
+    }
+}
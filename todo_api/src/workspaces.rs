@@ -0,0 +1,507 @@
+//! Workspaces share tasks between their members. A task in a workspace can
+//! only be read by its members and only changed by its editors and owners,
+//! as checked by [`DB::authorize`] in every task route. Tasks outside any
+//! workspace stay open to everyone, as they were before workspaces.
+//!
+//! Members are the actors recorded in the task history, so only callers
+//! with a bearer token can join. New members join through single-use
+//! invitation tokens made by an owner.
+
+use chrono::{Duration, Utc};
+use rocket::serde::Deserialize;
+use surrealdb::sql::Value;
+
+use crate::actor::ANONYMOUS;
+use crate::db::{new_record_id, AffectedRows, DB};
+use crate::error::Error;
+use crate::query::{Order, Query, Return, Target};
+use crate::utils::value::{from_value, to_value};
+use crate::validation::Rejection;
+pub use todo_models::{Invitation, Member, NewWorkspace, Role, SetRole, Workspace};
+
+/// How long an invitation can be accepted for.
+const INVITATION_DAYS: i64 = 7;
+
+/// The workspace a task event recorded: `Some(None)` for tasks outside any,
+/// and `None` for events made before it was recorded.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct EventWorkspace {
+    #[serde(default, deserialize_with = "todo_models::double_option::deserialize")]
+    workspace: Option<Option<String>>,
+}
+
+impl DB {
+    /// The workspaces `user` is a member of, oldest first.
+    pub async fn get_workspaces(&self, user: &str) -> Result<Vec<Workspace>, Error> {
+        let query = Query::select("members")
+            .where_eq("user", user)
+            .order_by("joined_at", Order::Asc);
+        let members: Vec<Member> = self.fetch(query).await?;
+        let mut workspaces = Vec::with_capacity(members.len());
+        for member in members {
+            match self.get_workspace(member.workspace).await {
+                Ok(workspace) => workspaces.push(workspace),
+                Err(Error::XRecordNotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(workspaces)
+    }
+
+    pub async fn get_workspace(&self, id: String) -> Result<Workspace, Error> {
+        // Any record id parses, so other tables are turned away here.
+        if !id.starts_with("workspaces:") {
+            return Err(Error::XRecordNotFound(id));
+        }
+        let query = Query::select(Target::record(&id)?);
+        self.fetch_one(query)
+            .await?
+            .ok_or(Error::XRecordNotFound(id))
+    }
+
+    /// Creates a workspace with `user` as its owner.
+    pub async fn create_workspace(
+        &self,
+        workspace: NewWorkspace,
+        user: &str,
+    ) -> Result<Workspace, Error> {
+        if user == ANONYMOUS {
+            return Err(Error::XAnonymous);
+        }
+        let id = new_record_id("workspaces");
+        let create = Query::create(Target::record(&id)?)
+            .set("name", workspace.name)
+            .set("created_at", Utc::now());
+        let owner = member_query(&id, user, Role::Owner)?;
+        let results = self
+            .transaction(|tx| async move {
+                tx.add(create);
+                tx.add(owner);
+                Ok(())
+            })
+            .await?;
+        let created = results.into_iter().next().map(|value| value.first());
+        from_value(created.ok_or(Error::XNoResponse)?)
+    }
+
+    /// `user`'s role in the workspace, if they are a member.
+    pub async fn get_role(&self, workspace: &str, user: &str) -> Result<Option<Role>, Error> {
+        let query = Query::select("members")
+            .where_eq("workspace", workspace)
+            .where_eq("user", user)
+            .limit(1);
+        let member: Option<Member> = self.fetch_one(query).await?;
+        Ok(member.map(|member| member.role))
+    }
+
+    /// Checks that `user` has at least `role` in `workspace`. Anything
+    /// outside a workspace needs no role.
+    pub async fn authorize(
+        &self,
+        workspace: Option<&str>,
+        user: &str,
+        role: Role,
+    ) -> Result<(), Error> {
+        let Some(workspace) = workspace else {
+            return Ok(());
+        };
+        match self.get_role(workspace, user).await? {
+            Some(has) if has.allows(role) => Ok(()),
+            _ => Err(Error::XRoleRequired(role)),
+        }
+    }
+
+    /// Checks `user`'s role in the task's workspace. Deleted tasks are
+    /// checked against the workspace their history last recorded, and are
+    /// denied when it recorded none. Tasks that never existed, including
+    /// ids of other tables, are not found.
+    pub async fn authorize_task(&self, id: &str, user: &str, role: Role) -> Result<(), Error> {
+        match self.get_task(id.to_string()).await {
+            Ok(task) => return self.authorize(task.workspace.as_deref(), user, role).await,
+            Err(Error::XRecordNotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+        let query = Query::select("task_events")
+            .where_eq("task", id)
+            .order_by("at", Order::Desc)
+            .limit(1);
+        match self.fetch_one::<EventWorkspace>(query).await? {
+            None => Err(Error::XRecordNotFound(id.to_string())),
+            Some(EventWorkspace {
+                workspace: Some(workspace),
+            }) => self.authorize(workspace.as_deref(), user, role).await,
+            Some(EventWorkspace { workspace: None }) => Err(Error::XRoleRequired(role)),
+        }
+    }
+
+    /// Checks `user`'s role in the workspace of the comment's task.
+    pub async fn authorize_comment(&self, id: &str, user: &str, role: Role) -> Result<(), Error> {
+        match self.get_comment(id.to_string()).await {
+            Ok(comment) => self.authorize_task(&comment.task, user, role).await,
+            Err(Error::XRecordNotFound(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Checks `user`'s role in the workspace of the attachment's task.
+    pub async fn authorize_attachment(
+        &self,
+        id: &str,
+        user: &str,
+        role: Role,
+    ) -> Result<(), Error> {
+        match self.get_attachment(id.to_string()).await {
+            Ok(attachment) => self.authorize_task(&attachment.task, user, role).await,
+            Err(Error::XRecordNotFound(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The workspace's members, in the order they joined. Any member may
+    /// list them.
+    pub async fn get_members(&self, workspace: String, user: &str) -> Result<Vec<Member>, Error> {
+        self.get_workspace(workspace.clone()).await?;
+        self.authorize(Some(&workspace), user, Role::Viewer).await?;
+        let query = Query::select("members")
+            .where_eq("workspace", workspace)
+            .order_by("joined_at", Order::Asc);
+        self.fetch(query).await
+    }
+
+    pub async fn get_member(&self, id: String) -> Result<Member, Error> {
+        if !id.starts_with("members:") {
+            return Err(Error::XRecordNotFound(id));
+        }
+        let query = Query::select(Target::record(&id)?);
+        self.fetch_one(query)
+            .await?
+            .ok_or(Error::XRecordNotFound(id))
+    }
+
+    /// Changes a member's role. Only owners may, and the last owner stays
+    /// one.
+    pub async fn set_member_role(
+        &self,
+        id: String,
+        role: Role,
+        user: &str,
+    ) -> Result<Member, Error> {
+        let member = self.get_member(id.clone()).await?;
+        self.authorize(Some(&member.workspace), user, Role::Owner)
+            .await?;
+        if member.role == Role::Owner && role != Role::Owner {
+            self.keep_an_owner(&member.workspace).await?;
+        }
+        let query = Query::update(Target::record(&id)?)
+            .set("role", to_value(&role)?)
+            .returning(Return::After);
+        self.fetch_one(query)
+            .await?
+            .ok_or(Error::XRecordNotFound(id))
+    }
+
+    /// Removes a member. Owners may remove anyone and members may leave,
+    /// but the last owner stays. Missing members affect no rows.
+    pub async fn remove_member(&self, id: String, user: &str) -> Result<AffectedRows, Error> {
+        let member = match self.get_member(id.clone()).await {
+            Ok(member) => member,
+            Err(Error::XRecordNotFound(_)) => return Ok(AffectedRows { affected_rows: 0 }),
+            Err(e) => return Err(e),
+        };
+        if member.user != user {
+            self.authorize(Some(&member.workspace), user, Role::Owner)
+                .await?;
+        }
+        if member.role == Role::Owner {
+            self.keep_an_owner(&member.workspace).await?;
+        }
+        let _ = self.run(Query::delete(Target::record(&id)?)).await?;
+        Ok(AffectedRows { affected_rows: 1 })
+    }
+
+    /// Fails if the workspace has only one owner, who is about to go.
+    async fn keep_an_owner(&self, workspace: &str) -> Result<(), Error> {
+        let query = Query::select("members")
+            .where_eq("workspace", workspace)
+            .where_eq("role", to_value(&Role::Owner)?)
+            .limit(2);
+        let owners: Vec<Member> = self.fetch(query).await?;
+        match owners.len() {
+            0 | 1 => Err(Error::XLastOwner),
+            _ => Ok(()),
+        }
+    }
+
+    /// Makes an invitation to the workspace. Only owners may.
+    pub async fn create_invitation(
+        &self,
+        workspace: String,
+        role: Role,
+        user: &str,
+    ) -> Result<Invitation, Error> {
+        self.get_workspace(workspace.clone()).await?;
+        self.authorize(Some(&workspace), user, Role::Owner).await?;
+        let now = Utc::now();
+        let query = Query::create("invitations")
+            .set_expr("token", "rand::string(40)")
+            .set("workspace", workspace)
+            .set("role", to_value(&role)?)
+            .set("created_at", now)
+            .set("expires_at", now + Duration::days(INVITATION_DAYS));
+        self.fetch_one(query).await?.ok_or(Error::XNoResponse)
+    }
+
+    /// Makes `user` a member with the invitation's role, and uses the
+    /// invitation up. Members keep the role they have. The invitation is
+    /// deleted in the same transaction that reads it, so only one request
+    /// can use it; the others find nothing to accept.
+    pub async fn accept_invitation(&self, token: String, user: &str) -> Result<Member, Error> {
+        if user == ANONYMOUS {
+            return Err(Error::XAnonymous);
+        }
+        let used = Query::delete("invitations")
+            .where_eq("token", token.clone())
+            .where_cmp("expires_at", ">", Utc::now())
+            .returning(Return::Before);
+        let existing = Query::select("members")
+            .where_expr("workspace", "=", "$invitation[0].workspace")
+            .where_eq("user", user)
+            .limit(1);
+        let join = Query::create("members")
+            .set_expr("workspace", "$invitation[0].workspace")
+            .set("user", user)
+            .set_expr("role", "$invitation[0].role")
+            .set("joined_at", Utc::now());
+        let results = self
+            .transaction(|tx| async move {
+                tx.bind("invitation", used);
+                tx.bind("member", existing);
+                tx.add_if_else(
+                    "array::len($invitation) > 0 AND array::len($member) = 0",
+                    join,
+                    "$member",
+                );
+                Ok(())
+            })
+            .await?;
+
+        // Nothing when the invitation was already used, expired or never
+        // existed.
+        match results.get(2).map(Value::first) {
+            Some(Value::None) | None => Err(Error::XRecordNotFound(token)),
+            Some(member) => from_value(member),
+        }
+    }
+}
+
+fn member_query(workspace: &str, user: &str, role: Role) -> Result<Query, Error> {
+    Ok(Query::create("members")
+        .set("workspace", workspace)
+        .set("user", user)
+        .set("role", to_value(&role)?)
+        .set("joined_at", Utc::now()))
+}
+
+/// Answers a failed role check with `403 Forbidden`, and one for a record
+/// that never existed with `404 Not Found`.
+pub fn denied(error: Error) -> Rejection {
+    match error {
+        Error::XRoleRequired(_) | Error::XAnonymous => Rejection::Forbidden(error.to_string()),
+        Error::XRecordNotFound(_) => Rejection::NotFound(error.to_string()),
+        _ => std::io::Error::other("Error checking role").into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use surrealdb::{dbs::Session, kvs::Datastore};
+
+    async fn db() -> DB {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::for_db("my_db", "my_ns");
+        DB { ds, sesh }
+    }
+
+    fn new_workspace() -> NewWorkspace {
+        NewWorkspace {
+            name: "Team".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invitations() {
+        let db = db().await;
+        let workspace = db
+            .create_workspace(new_workspace(), "user:a")
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_workspaces("user:a").await.unwrap(),
+            std::slice::from_ref(&workspace)
+        );
+        assert!(db.get_workspaces("user:b").await.unwrap().is_empty());
+
+        let res = db
+            .create_invitation(workspace.id.clone(), Role::Viewer, "user:b")
+            .await;
+        assert!(matches!(res, Err(Error::XRoleRequired(Role::Owner))));
+        let invitation = db
+            .create_invitation(workspace.id.clone(), Role::Viewer, "user:a")
+            .await
+            .unwrap();
+        assert_eq!(invitation.token.len(), 40);
+
+        let res = db
+            .accept_invitation(invitation.token.clone(), ANONYMOUS)
+            .await;
+        assert!(matches!(res, Err(Error::XAnonymous)));
+        let member = db
+            .accept_invitation(invitation.token.clone(), "user:b")
+            .await
+            .unwrap();
+        assert_eq!(member.role, Role::Viewer);
+        assert_eq!(
+            db.get_role(&workspace.id, "user:b").await.unwrap(),
+            Some(Role::Viewer)
+        );
+
+        // Invitations are single-use.
+        let res = db.accept_invitation(invitation.token, "user:c").await;
+        assert!(matches!(res, Err(Error::XRecordNotFound(_))));
+
+        let members = db
+            .get_members(workspace.id.clone(), "user:b")
+            .await
+            .unwrap();
+        assert_eq!(members.len(), 2);
+        let res = db.get_members(workspace.id, "user:c").await;
+        assert!(matches!(res, Err(Error::XRoleRequired(Role::Viewer))));
+    }
+
+    #[tokio::test]
+    async fn test_invitation_is_accepted_once() {
+        let db = db().await;
+        let workspace = db
+            .create_workspace(new_workspace(), "user:a")
+            .await
+            .unwrap();
+        let invitation = db
+            .create_invitation(workspace.id.clone(), Role::Editor, "user:a")
+            .await
+            .unwrap();
+
+        let (b, c) = tokio::join!(
+            db.accept_invitation(invitation.token.clone(), "user:b"),
+            db.accept_invitation(invitation.token.clone(), "user:c")
+        );
+        assert_ne!(b.is_ok(), c.is_ok());
+        assert!(matches!(
+            b.err().or(c.err()),
+            Some(Error::XRecordNotFound(_))
+        ));
+        let members = db.get_members(workspace.id, "user:a").await.unwrap();
+        assert_eq!(members.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_roles() {
+        let db = db().await;
+        let workspace = db
+            .create_workspace(new_workspace(), "user:a")
+            .await
+            .unwrap();
+        let owner = db
+            .get_members(workspace.id.clone(), "user:a")
+            .await
+            .unwrap()[0]
+            .clone();
+
+        let res = db
+            .set_member_role(owner.id.clone(), Role::Editor, "user:a")
+            .await;
+        assert!(matches!(res, Err(Error::XLastOwner)));
+        let res = db.remove_member(owner.id.clone(), "user:a").await;
+        assert!(matches!(res, Err(Error::XLastOwner)));
+
+        let ws = Some(workspace.id.as_str());
+        db.authorize(ws, "user:a", Role::Owner).await.unwrap();
+        let res = db.authorize(ws, "user:b", Role::Viewer).await;
+        assert!(matches!(res, Err(Error::XRoleRequired(Role::Viewer))));
+        db.authorize(None, "user:b", Role::Owner).await.unwrap();
+
+        let invitation = db
+            .create_invitation(workspace.id.clone(), Role::Editor, "user:a")
+            .await
+            .unwrap();
+        let editor = db
+            .accept_invitation(invitation.token, "user:b")
+            .await
+            .unwrap();
+        let res = db
+            .set_member_role(owner.id.clone(), Role::Viewer, "user:b")
+            .await;
+        assert!(matches!(res, Err(Error::XRoleRequired(Role::Owner))));
+        let promoted = db
+            .set_member_role(editor.id.clone(), Role::Owner, "user:a")
+            .await
+            .unwrap();
+        assert_eq!(promoted.role, Role::Owner);
+
+        // With a second owner, the first may leave.
+        let res = db.remove_member(owner.id, "user:a").await.unwrap();
+        assert_eq!(res.affected_rows, 1);
+        let res = db.authorize(ws, "user:a", Role::Viewer).await;
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_workspace_tasks() {
+        let db = db().await;
+        let workspace = db
+            .create_workspace(new_workspace(), "user:a")
+            .await
+            .unwrap();
+        let shared = db
+            .add_task_in("shared".to_string(), Some(workspace.id.clone()), "user:a")
+            .await
+            .unwrap();
+        let open = db.add_task("open".to_string(), ANONYMOUS).await.unwrap();
+
+        let tasks = db.get_tasks_in(Some(&workspace.id)).await.unwrap();
+        assert_eq!(tasks, std::slice::from_ref(&shared));
+        assert_eq!(
+            db.get_tasks_in(None).await.unwrap(),
+            std::slice::from_ref(&open)
+        );
+
+        db.authorize_task(&shared.id, "user:a", Role::Editor)
+            .await
+            .unwrap();
+        let res = db.authorize_task(&shared.id, "user:b", Role::Viewer).await;
+        assert!(matches!(res, Err(Error::XRoleRequired(Role::Viewer))));
+        db.authorize_task(&open.id, "user:b", Role::Editor)
+            .await
+            .unwrap();
+        // A record of another table is no task either.
+        for id in ["tasks:missing", workspace.id.as_str()] {
+            let res = db.authorize_task(id, "user:b", Role::Editor).await;
+            assert!(matches!(res, Err(Error::XRecordNotFound(_))));
+        }
+
+        // Only members are assigned workspace tasks.
+        let res = db
+            .assign_task(shared.id.clone(), Some("user:b".to_string()), "user:a")
+            .await;
+        assert!(matches!(
+            res,
+            Err(Error::Invalid(todo_models::Error::AssigneeNotMember(_)))
+        ));
+        db.assign_task(shared.id.clone(), Some("user:a".to_string()), "user:a")
+            .await
+            .unwrap();
+        assert_eq!(db.get_assignees(shared.id).await.unwrap(), ["user:a"]);
+    }
+}
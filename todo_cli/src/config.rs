@@ -0,0 +1,88 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+pub const DEFAULT_SERVER: &str = "http://localhost:8080";
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/todo/config.toml` or the platform equivalent.
+    pub fn default_path() -> Result<PathBuf, Error> {
+        let dir = dirs::config_dir().ok_or(Error::NoConfigDir)?;
+        Ok(dir.join("todo").join("config.toml"))
+    }
+
+    /// Reads the config at `path`, treating a missing file as empty.
+    pub fn load(path: &Path) -> Result<Config, Error> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(toml::from_str(&contents)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = toml::to_string(self).expect("config is always representable as toml");
+        fs::write(path, contents)?;
+        restrict_permissions(path)
+    }
+
+    pub fn server(&self) -> &str {
+        self.server.as_deref().unwrap_or(DEFAULT_SERVER)
+    }
+}
+
+/// The config holds an API token, so keep it private to the current user.
+#[cfg(unix)]
+fn restrict_permissions(path: &Path) -> Result<(), Error> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path) -> Result<(), Error> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_missing_file_is_empty() {
+        let path = std::env::temp_dir().join("todo_cli_missing_config.toml");
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config, Config::default());
+        assert_eq!(config.server(), DEFAULT_SERVER);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir()
+            .join(format!("todo_cli_{}", std::process::id()))
+            .join("config.toml");
+        let config = Config {
+            server: Some("http://example.com".to_string()),
+            token: Some("secret".to_string()),
+        };
+        config.save(&path).unwrap();
+        assert_eq!(Config::load(&path).unwrap(), config);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
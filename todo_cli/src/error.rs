@@ -0,0 +1,17 @@
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("no config directory found, pass --config explicitly")]
+    NoConfigDir,
+
+    #[error("invalid config file: {0}")]
+    Config(#[from] toml::de::Error),
+
+    #[error(transparent)]
+    Client(#[from] todo_client::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+}
@@ -0,0 +1,207 @@
+use std::{fs::File, io, path::PathBuf};
+
+use clap::{ArgGroup, Parser, Subcommand};
+use todo_client::{Client, Task, UpdateTask};
+
+use config::Config;
+use error::Error;
+use output::{ExportFormat, Output};
+
+mod config;
+mod error;
+mod output;
+
+/// Manage todo_api tasks from the terminal.
+#[derive(Parser)]
+#[command(name = "todo", version)]
+struct Cli {
+    /// Server URL, overriding the config file.
+    #[arg(long, global = true, env = "TODO_SERVER")]
+    server: Option<String>,
+
+    /// Path of the config file holding the server URL and token.
+    #[arg(long, global = true, env = "TODO_CONFIG")]
+    config: Option<PathBuf>,
+
+    #[arg(long, global = true, value_enum, default_value_t = Output::Table)]
+    output: Output,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a task.
+    Add { title: Vec<String> },
+
+    /// List tasks.
+    #[command(group(ArgGroup::new("status").args(["done", "pending"])))]
+    Ls {
+        /// Only completed tasks.
+        #[arg(long)]
+        done: bool,
+        /// Only tasks that are not completed.
+        #[arg(long)]
+        pending: bool,
+        /// Only tasks whose title contains this text, ignoring case.
+        #[arg(long)]
+        search: Option<String>,
+        /// Only tasks assigned to you, as identified by the API token.
+        #[arg(long)]
+        mine: bool,
+    },
+
+    /// Mark a task as completed.
+    Done { id: String },
+
+    /// Mark a task as not completed.
+    Undo { id: String },
+
+    /// Delete a task.
+    Rm { id: String },
+
+    /// Change the title of a task.
+    Edit { id: String, title: Vec<String> },
+
+    /// Write every task to stdout or a file.
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        #[arg(long, short = 'f')]
+        file: Option<PathBuf>,
+    },
+
+    /// Store the server URL and API token in the config file.
+    Config {
+        #[arg(long)]
+        server: Option<String>,
+        #[arg(long)]
+        token: Option<String>,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Error> {
+    let config_path = match cli.config {
+        Some(path) => path,
+        None => Config::default_path()?,
+    };
+    let mut config = Config::load(&config_path)?;
+
+    if let Command::Config { server, token } = cli.command {
+        if server.is_none() && token.is_none() {
+            println!("config: {}", config_path.display());
+            println!("server: {}", config.server());
+            let token = match config.token {
+                Some(_) => "set",
+                None => "not set",
+            };
+            println!("token:  {token}");
+            return Ok(());
+        }
+        config.server = server.or(config.server);
+        config.token = token.or(config.token);
+        return config.save(&config_path);
+    }
+
+    let server = cli.server.as_deref().unwrap_or(config.server());
+    let mut builder = Client::builder(server);
+    if let Some(token) = &config.token {
+        builder = builder.token(token);
+    }
+    let client = builder.build()?;
+    let mut stdout = io::stdout().lock();
+
+    match cli.command {
+        Command::Add { title } => {
+            let task = client.create_task(&title.join(" ")).await?;
+            output::print_tasks(&mut stdout, &[task], cli.output)?;
+        }
+        Command::Ls {
+            done,
+            pending,
+            search,
+            mine,
+        } => {
+            let search = search.map(|s| s.to_lowercase());
+            let tasks = match mine {
+                true => client.get_my_tasks().await?,
+                false => client.get_tasks().await?,
+            };
+            let tasks: Vec<Task> = tasks
+                .into_iter()
+                .filter(|task| !done || task.completed)
+                .filter(|task| !pending || !task.completed)
+                .filter(|task| match &search {
+                    Some(search) => task.title.to_lowercase().contains(search),
+                    None => true,
+                })
+                .collect();
+            output::print_tasks(&mut stdout, &tasks, cli.output)?;
+        }
+        Command::Done { id } => {
+            let update = UpdateTask {
+                completed: Some(true),
+                ..Default::default()
+            };
+            let task = client.update_task(&output::task_id(&id), &update).await?;
+            output::print_tasks(&mut stdout, &[task], cli.output)?;
+        }
+        Command::Undo { id } => {
+            let update = UpdateTask {
+                completed: Some(false),
+                ..Default::default()
+            };
+            let task = client.update_task(&output::task_id(&id), &update).await?;
+            output::print_tasks(&mut stdout, &[task], cli.output)?;
+        }
+        Command::Rm { id } => {
+            client.delete_task(&output::task_id(&id)).await?;
+        }
+        Command::Edit { id, title } => {
+            let update = UpdateTask {
+                title: Some(title.join(" ")),
+                ..Default::default()
+            };
+            let task = client.update_task(&output::task_id(&id), &update).await?;
+            output::print_tasks(&mut stdout, &[task], cli.output)?;
+        }
+        Command::Export { format, file } => {
+            let tasks = client.get_tasks().await?;
+            match file {
+                Some(path) => output::export_tasks(&mut File::create(path)?, &tasks, format)?,
+                None => output::export_tasks(&mut stdout, &tasks, format)?,
+            }
+        }
+        Command::Config { .. } => unreachable!("handled before connecting"),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_ls_filters_conflict() {
+        assert!(Cli::try_parse_from(["todo", "ls", "--done", "--pending"]).is_err());
+        assert!(Cli::try_parse_from(["todo", "ls", "--done", "--output", "json"]).is_ok());
+        assert!(Cli::try_parse_from(["todo", "ls", "--mine", "--pending"]).is_ok());
+    }
+}
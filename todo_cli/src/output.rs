@@ -0,0 +1,149 @@
+use std::io::Write;
+
+use clap::ValueEnum;
+use todo_client::Task;
+
+use crate::error::Error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    Table,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+/// Accepts both `tasks:abc` and the bare `abc` shown in tables.
+pub fn task_id(id: &str) -> String {
+    match id.contains(':') {
+        true => id.to_string(),
+        false => format!("tasks:{id}"),
+    }
+}
+
+fn short_id(id: &str) -> &str {
+    id.strip_prefix("tasks:").unwrap_or(id)
+}
+
+pub fn print_tasks(out: &mut impl Write, tasks: &[Task], output: Output) -> Result<(), Error> {
+    match output {
+        Output::Json => {
+            serde_json::to_writer_pretty(&mut *out, tasks)?;
+            writeln!(out)?;
+        }
+        Output::Table => {
+            let width = tasks
+                .iter()
+                .map(|task| short_id(&task.id).len())
+                .max()
+                .unwrap_or(0)
+                .max("ID".len());
+            writeln!(out, "{:<width$}  DONE  {:<16}  TITLE", "ID", "CREATED")?;
+            for task in tasks {
+                writeln!(
+                    out,
+                    "{:<width$}  {:<4}  {:<16}  {}",
+                    short_id(&task.id),
+                    if task.completed { "[x]" } else { "[ ]" },
+                    task.created_at.format("%Y-%m-%d %H:%M"),
+                    task.title,
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn export_tasks(
+    out: &mut impl Write,
+    tasks: &[Task],
+    format: ExportFormat,
+) -> Result<(), Error> {
+    match format {
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, tasks)?;
+            writeln!(out)?;
+        }
+        ExportFormat::Csv => {
+            writeln!(out, "id,title,completed,created_at")?;
+            for task in tasks {
+                writeln!(
+                    out,
+                    "{},{},{},{}",
+                    csv_field(&task.id),
+                    csv_field(&task.title),
+                    task.completed,
+                    task.created_at.to_rfc3339(),
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: &str, title: &str, completed: bool) -> Task {
+        Task {
+            id: id.to_string(),
+            title: title.to_string(),
+            completed,
+            created_at: "2023-04-02T10:00:00Z".parse().unwrap(),
+            due: None,
+            priority: None,
+            remind_at: None,
+            rank: String::new(),
+            status: String::new(),
+            workspace: None,
+            assignee: None,
+        }
+    }
+
+    #[test]
+    fn test_task_id() {
+        assert_eq!(task_id("abc"), "tasks:abc");
+        assert_eq!(task_id("tasks:abc"), "tasks:abc");
+    }
+
+    #[test]
+    fn test_print_table() {
+        let tasks = vec![
+            task("tasks:abc", "buy milk", true),
+            task("tasks:d", "walk", false),
+        ];
+        let mut out = vec![];
+        print_tasks(&mut out, &tasks, Output::Table).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "ID   DONE  CREATED           TITLE\n\
+             abc  [x]   2023-04-02 10:00  buy milk\n\
+             d    [ ]   2023-04-02 10:00  walk\n"
+        );
+    }
+
+    #[test]
+    fn test_export_csv_quotes_fields() {
+        let tasks = vec![task("tasks:abc", "milk, \"oat\"", false)];
+        let mut out = vec![];
+        export_tasks(&mut out, &tasks, ExportFormat::Csv).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "id,title,completed,created_at\n\
+             tasks:abc,\"milk, \"\"oat\"\"\",false,2023-04-02T10:00:00+00:00\n"
+        );
+    }
+}
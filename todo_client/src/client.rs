@@ -0,0 +1,869 @@
+use std::time::Duration;
+
+use reqwest::{Method, Response, StatusCode, Url};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    AffectedRows, ApiToken, Assignment, Attachment, Comment, EditComment, Error, Invitation,
+    IssuedToken, Member, MoveTask, NewApiToken, NewComment, NewTask, NewWebhook, NewWorkspace,
+    Notification, Role, SetRole, Task, TaskEvent, UpdateTask, ValidationErrors, Webhook,
+    WebhookDelivery, Workflow, Workspace,
+};
+
+pub const DEFAULT_RETRIES: u32 = 2;
+pub const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+pub struct ClientBuilder {
+    base_url: String,
+    token: Option<String>,
+    timeout: Option<Duration>,
+    retries: u32,
+    retry_backoff: Duration,
+}
+
+impl ClientBuilder {
+    /// Bearer token sent in the `Authorization` header of every request.
+    pub fn token(mut self, token: impl Into<String>) -> ClientBuilder {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Total time allowed per attempt. Ignored on wasm, where the browser
+    /// owns request timeouts.
+    pub fn timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// How many times an idempotent request is resent after a retryable
+    /// failure. The delay doubles after each attempt, starting at `backoff`.
+    pub fn retries(mut self, retries: u32, backoff: Duration) -> ClientBuilder {
+        self.retries = retries;
+        self.retry_backoff = backoff;
+        self
+    }
+
+    pub fn build(self) -> Result<Client, Error> {
+        let base_url = Url::parse(&self.base_url)
+            .ok()
+            .filter(|url| !url.cannot_be_a_base())
+            .ok_or_else(|| Error::InvalidUrl(self.base_url.clone()))?;
+
+        #[allow(unused_mut)]
+        let mut http = reqwest::Client::builder();
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(timeout) = self.timeout {
+            http = http.timeout(timeout);
+        }
+
+        Ok(Client {
+            http: http.build()?,
+            base_url,
+            token: self.token,
+            retries: self.retries,
+            retry_backoff: self.retry_backoff,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: Url,
+    token: Option<String>,
+    retries: u32,
+    retry_backoff: Duration,
+}
+
+impl Client {
+    pub fn new(base_url: impl Into<String>) -> Result<Client, Error> {
+        Client::builder(base_url).build()
+    }
+
+    pub fn builder(base_url: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            base_url: base_url.into(),
+            token: None,
+            timeout: None,
+            retries: DEFAULT_RETRIES,
+            retry_backoff: DEFAULT_RETRY_BACKOFF,
+        }
+    }
+
+    pub fn base_url(&self) -> &Url {
+        &self.base_url
+    }
+
+    pub async fn get_tasks(&self) -> Result<Vec<Task>, Error> {
+        self.send(Method::GET, &["tasks"]).await
+    }
+
+    /// The tasks of a workspace. [`Client::get_tasks`] lists those outside
+    /// any workspace.
+    pub async fn get_workspace_tasks(&self, workspace: &str) -> Result<Vec<Task>, Error> {
+        let mut url = self.url(&["tasks"]);
+        url.query_pairs_mut().append_pair("workspace", workspace);
+        self.send_to(Method::GET, url, None::<&()>).await
+    }
+
+    /// The tasks outside any workspace that are assigned to the token's
+    /// user.
+    pub async fn get_my_tasks(&self) -> Result<Vec<Task>, Error> {
+        let mut url = self.url(&["tasks"]);
+        url.query_pairs_mut().append_pair("mine", "true");
+        self.send_to(Method::GET, url, None::<&()>).await
+    }
+
+    pub async fn get_task(&self, id: &str) -> Result<Task, Error> {
+        self.send(Method::GET, &["task", id]).await
+    }
+
+    pub async fn create_task(&self, title: &str) -> Result<Task, Error> {
+        let new_task = NewTask::new(title);
+        new_task.validate()?;
+        self.send(Method::POST, &["task", &new_task.title]).await
+    }
+
+    /// Creates a task in a workspace, which needs the editor role there.
+    pub async fn create_workspace_task(&self, workspace: &str, title: &str) -> Result<Task, Error> {
+        let new_task = NewTask::new(title);
+        new_task.validate()?;
+        let mut url = self.url(&["task", &new_task.title]);
+        url.query_pairs_mut().append_pair("workspace", workspace);
+        self.send_to(Method::POST, url, None::<&()>).await
+    }
+
+    pub async fn update_task(&self, id: &str, update: &UpdateTask) -> Result<Task, Error> {
+        update.validate()?;
+        self.send_json(Method::PUT, &["task", id], Some(update))
+            .await
+    }
+
+    /// Moves a task just before or after another one.
+    pub async fn move_task(&self, id: &str, to: &MoveTask) -> Result<Task, Error> {
+        self.send_json(Method::PUT, &["task", id, "move"], Some(to))
+            .await
+    }
+
+    /// Moves a task to another status of the workflow.
+    pub async fn set_task_status(&self, id: &str, status: &str) -> Result<Task, Error> {
+        self.send(Method::PUT, &["task", id, "status", status])
+            .await
+    }
+
+    /// Assigns a task to a user, such as `user:<key>`.
+    pub async fn assign_task(&self, id: &str, assignee: &str) -> Result<Task, Error> {
+        let assignment = Assignment {
+            assignee: assignee.to_string(),
+        }
+        .sanitize()
+        .map_err(Error::Invalid)?;
+        let segments = ["task", id, "assignee"];
+        self.send_json(Method::PUT, &segments, Some(&assignment))
+            .await
+    }
+
+    pub async fn unassign_task(&self, id: &str) -> Result<Task, Error> {
+        self.send(Method::DELETE, &["task", id, "assignee"]).await
+    }
+
+    /// The users a task can be assigned to.
+    pub async fn get_assignees(&self, id: &str) -> Result<Vec<String>, Error> {
+        self.send(Method::GET, &["task", id, "assignees"]).await
+    }
+
+    pub async fn get_statuses(&self) -> Result<Workflow, Error> {
+        self.send(Method::GET, &["statuses"]).await
+    }
+
+    pub async fn set_statuses(&self, workflow: &Workflow) -> Result<Workflow, Error> {
+        self.send_json(Method::PUT, &["statuses"], Some(workflow))
+            .await
+    }
+
+    /// The statuses of a workspace. [`Client::get_statuses`] gets those of
+    /// the tasks outside any workspace.
+    pub async fn get_workspace_statuses(&self, workspace: &str) -> Result<Workflow, Error> {
+        let mut url = self.url(&["statuses"]);
+        url.query_pairs_mut().append_pair("workspace", workspace);
+        self.send_to(Method::GET, url, None::<&()>).await
+    }
+
+    /// Replaces the statuses of a workspace, which needs the owner role
+    /// there.
+    pub async fn set_workspace_statuses(
+        &self,
+        workspace: &str,
+        workflow: &Workflow,
+    ) -> Result<Workflow, Error> {
+        let mut url = self.url(&["statuses"]);
+        url.query_pairs_mut().append_pair("workspace", workspace);
+        self.send_to(Method::PUT, url, Some(workflow)).await
+    }
+
+    pub async fn toggle_task(&self, id: &str) -> Result<AffectedRows, Error> {
+        self.send(Method::PATCH, &["task", id]).await
+    }
+
+    pub async fn delete_task(&self, id: &str) -> Result<AffectedRows, Error> {
+        self.send(Method::DELETE, &["task", id]).await
+    }
+
+    /// Every change made to a task, oldest first.
+    pub async fn get_task_history(&self, id: &str) -> Result<Vec<TaskEvent>, Error> {
+        self.send(Method::GET, &["task", id, "history"]).await
+    }
+
+    /// The task's comments, oldest first. Replies name their parent.
+    pub async fn get_comments(&self, task: &str) -> Result<Vec<Comment>, Error> {
+        self.send(Method::GET, &["task", task, "comments"]).await
+    }
+
+    pub async fn add_comment(&self, task: &str, comment: NewComment) -> Result<Comment, Error> {
+        let comment = comment.sanitize().map_err(Error::Invalid)?;
+        self.send_json(Method::POST, &["task", task, "comments"], Some(&comment))
+            .await
+    }
+
+    /// Only the comment's author may edit it.
+    pub async fn edit_comment(&self, id: &str, edit: EditComment) -> Result<Comment, Error> {
+        let edit = edit.sanitize().map_err(Error::Invalid)?;
+        self.send_json(Method::PUT, &["comment", id], Some(&edit))
+            .await
+    }
+
+    pub async fn delete_comment(&self, id: &str) -> Result<AffectedRows, Error> {
+        self.send(Method::DELETE, &["comment", id]).await
+    }
+
+    pub async fn get_attachments(&self, task: &str) -> Result<Vec<Attachment>, Error> {
+        self.send(Method::GET, &["task", task, "attachments"]).await
+    }
+
+    /// Uploads a file and attaches it to a task. Uploads are not retried.
+    pub async fn add_attachment(
+        &self,
+        task: &str,
+        name: &str,
+        content_type: &str,
+        bytes: Vec<u8>,
+    ) -> Result<Attachment, Error> {
+        let (boundary, body) = multipart("file", name, content_type, bytes);
+        let mut request = self
+            .http
+            .post(self.url(&["task", task, "attachments"]))
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(body);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        read(request.send().await?).await
+    }
+
+    /// Where the attachment's contents are served, for links and downloads.
+    pub fn attachment_url(&self, id: &str) -> Url {
+        self.url(&["attachment", id])
+    }
+
+    pub async fn delete_attachment(&self, id: &str) -> Result<AffectedRows, Error> {
+        self.send(Method::DELETE, &["attachment", id]).await
+    }
+
+    /// Reminders kept in the app, newest first.
+    pub async fn get_notifications(&self) -> Result<Vec<Notification>, Error> {
+        self.send(Method::GET, &["notifications"]).await
+    }
+
+    pub async fn read_notification(&self, id: &str) -> Result<Notification, Error> {
+        self.send(Method::PUT, &["notification", id, "read"]).await
+    }
+
+    pub async fn get_webhooks(&self) -> Result<Vec<Webhook>, Error> {
+        self.send(Method::GET, &["webhooks"]).await
+    }
+
+    /// Subscribes `webhook.url` to task changes, signed with `webhook.secret`.
+    pub async fn add_webhook(&self, webhook: NewWebhook) -> Result<Webhook, Error> {
+        let webhook = webhook.sanitize().map_err(Error::Invalid)?;
+        self.send_json(Method::POST, &["webhooks"], Some(&webhook))
+            .await
+    }
+
+    pub async fn delete_webhook(&self, id: &str) -> Result<AffectedRows, Error> {
+        self.send(Method::DELETE, &["webhook", id]).await
+    }
+
+    /// The webhook's latest deliveries, newest first.
+    pub async fn get_webhook_deliveries(&self, id: &str) -> Result<Vec<WebhookDelivery>, Error> {
+        self.send(Method::GET, &["webhook", id, "deliveries"]).await
+    }
+
+    /// The workspaces the token's user is a member of.
+    pub async fn get_workspaces(&self) -> Result<Vec<Workspace>, Error> {
+        self.send(Method::GET, &["workspaces"]).await
+    }
+
+    /// Creates a workspace owned by the token's user.
+    pub async fn create_workspace(&self, workspace: NewWorkspace) -> Result<Workspace, Error> {
+        let workspace = workspace.sanitize().map_err(Error::Invalid)?;
+        self.send_json(Method::POST, &["workspaces"], Some(&workspace))
+            .await
+    }
+
+    pub async fn get_workspace_members(&self, workspace: &str) -> Result<Vec<Member>, Error> {
+        self.send(Method::GET, &["workspace", workspace, "members"])
+            .await
+    }
+
+    /// Makes a single-use invitation to the workspace. Only owners may.
+    pub async fn create_invitation(
+        &self,
+        workspace: &str,
+        role: Role,
+    ) -> Result<Invitation, Error> {
+        let segments = ["workspace", workspace, "invitations"];
+        self.send_json(Method::POST, &segments, Some(&SetRole { role }))
+            .await
+    }
+
+    pub async fn accept_invitation(&self, token: &str) -> Result<Member, Error> {
+        self.send(Method::POST, &["invitation", token]).await
+    }
+
+    /// Changes a member's role. Only owners may.
+    pub async fn set_member_role(&self, id: &str, role: Role) -> Result<Member, Error> {
+        self.send_json(Method::PUT, &["member", id], Some(&SetRole { role }))
+            .await
+    }
+
+    /// Removes a member, or leaves when `id` is the token user's own.
+    pub async fn remove_member(&self, id: &str) -> Result<AffectedRows, Error> {
+        self.send(Method::DELETE, &["member", id]).await
+    }
+
+    /// The token user's personal access tokens, oldest first.
+    pub async fn get_tokens(&self) -> Result<Vec<ApiToken>, Error> {
+        self.send(Method::GET, &["tokens"]).await
+    }
+
+    /// Issues a personal access token acting as the token's user. The
+    /// returned secret cannot be fetched again.
+    pub async fn create_token(&self, token: NewApiToken) -> Result<IssuedToken, Error> {
+        let token = token.sanitize().map_err(Error::Invalid)?;
+        self.send_json(Method::POST, &["tokens"], Some(&token))
+            .await
+    }
+
+    pub async fn revoke_token(&self, id: &str) -> Result<AffectedRows, Error> {
+        self.send(Method::DELETE, &["token", id]).await
+    }
+
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .expect("base url checked in ClientBuilder::build")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        segments: &[&str],
+    ) -> Result<T, Error> {
+        self.send_json(method, segments, None::<&()>).await
+    }
+
+    async fn send_json<B: Serialize, T: DeserializeOwned>(
+        &self,
+        method: Method,
+        segments: &[&str],
+        body: Option<&B>,
+    ) -> Result<T, Error> {
+        self.send_to(method, self.url(segments), body).await
+    }
+
+    async fn send_to<B: Serialize, T: DeserializeOwned>(
+        &self,
+        method: Method,
+        url: Url,
+        body: Option<&B>,
+    ) -> Result<T, Error> {
+        // Creating and toggling are not safe to repeat, so only reads,
+        // full updates and deletes are retried.
+        let idempotent = matches!(method, Method::GET | Method::PUT | Method::DELETE);
+
+        let mut attempt = 0;
+        loop {
+            let mut request = self.http.request(method.clone(), url.clone());
+            if let Some(token) = &self.token {
+                request = request.bearer_auth(token);
+            }
+            if let Some(body) = body {
+                request = request.json(body);
+            }
+
+            let result = match request.send().await {
+                Ok(response) => read(response).await,
+                Err(e) => Err(Error::Http(e)),
+            };
+
+            match result {
+                Err(e) if idempotent && attempt < self.retries && e.is_retryable() => {
+                    sleep(self.retry_backoff * 2u32.pow(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Encodes one file as the `field` of a `multipart/form-data` body, and
+/// returns the boundary with the body.
+fn multipart(field: &str, name: &str, content_type: &str, bytes: Vec<u8>) -> (String, Vec<u8>) {
+    let mut boundary = "todo-client-boundary".to_string();
+    while bytes
+        .windows(boundary.len())
+        .any(|window| window == boundary.as_bytes())
+    {
+        boundary.push('x');
+    }
+    let name: String = name
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| if c == '"' { '\'' } else { c })
+        .collect();
+
+    let mut body = format!(
+        "--{boundary}\r\n\
+         Content-Disposition: form-data; name=\"{field}\"; filename=\"{name}\"\r\n\
+         Content-Type: {content_type}\r\n\r\n"
+    )
+    .into_bytes();
+    body.extend(bytes);
+    body.extend(format!("\r\n--{boundary}--\r\n").into_bytes());
+    (boundary, body)
+}
+
+async fn read<T: DeserializeOwned>(response: Response) -> Result<T, Error> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response.json().await?);
+    }
+
+    let message = response.text().await.unwrap_or_default();
+    if status == StatusCode::UNPROCESSABLE_ENTITY {
+        if let Ok(errors) = serde_json::from_str::<ValidationErrors>(&message) {
+            return Err(Error::Invalid(errors));
+        }
+    }
+    Err(Error::Status {
+        status: status.as_u16(),
+        message,
+    })
+}
+
+#[cfg(feature = "native")]
+async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+#[cfg(all(feature = "wasm", not(feature = "native")))]
+async fn sleep(duration: Duration) {
+    gloo_timers::future::sleep(duration).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Scope, TaskAction};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        task::JoinHandle,
+    };
+
+    const TASK: &str = r#"{"id":"tasks:abc","title":"buy milk","completed":false,"created_at":"2023-04-02T10:00:00Z"}"#;
+
+    fn response(status: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    /// Answers one connection per canned response and returns the request
+    /// heads it received.
+    async fn serve(responses: Vec<String>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let mut requests = vec![];
+            for response in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = vec![];
+                let mut chunk = [0u8; 1024];
+                while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = stream.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..n]);
+                }
+                requests.push(String::from_utf8(buf).unwrap());
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+            requests
+        });
+        (base_url, handle)
+    }
+
+    #[tokio::test]
+    async fn test_get_tasks_sends_token() {
+        let (base_url, server) = serve(vec![response("200 OK", &format!("[{TASK}]"))]).await;
+        let client = Client::builder(base_url).token("secret").build().unwrap();
+
+        let tasks = client.get_tasks().await.unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title, "buy milk");
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("GET /tasks HTTP/1.1"));
+        assert!(requests[0]
+            .to_lowercase()
+            .contains("authorization: bearer secret"));
+    }
+
+    #[tokio::test]
+    async fn test_create_task_encodes_title() {
+        let (base_url, server) = serve(vec![response("200 OK", TASK)]).await;
+        let client = Client::new(base_url).unwrap();
+
+        let task = client.create_task("buy milk").await.unwrap();
+        assert_eq!(task.id, "tasks:abc");
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("POST /task/buy%20milk HTTP/1.1"));
+    }
+
+    #[tokio::test]
+    async fn test_get_task_history() {
+        let event = r#"{"id":"task_events:1","task":"tasks:abc","actor":"anonymous","action":"created","at":"2023-04-02T10:00:00Z","changes":[{"field":"title","from":null,"to":"buy milk"}]}"#;
+        let (base_url, server) = serve(vec![response("200 OK", &format!("[{event}]"))]).await;
+        let client = Client::new(base_url).unwrap();
+
+        let history = client.get_task_history("tasks:abc").await.unwrap();
+        assert_eq!(history[0].changes[0].to.as_deref(), Some("buy milk"));
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("GET /task/tasks:abc/history HTTP/1.1"));
+    }
+
+    #[tokio::test]
+    async fn test_create_task_validates_before_sending() {
+        let client = Client::new("http://127.0.0.1:1").unwrap();
+        let err = client.create_task("  ").await.unwrap_err();
+        assert!(matches!(err, Error::Validation(_)));
+    }
+
+    #[tokio::test]
+    async fn test_get_retries_on_unavailable() {
+        let (base_url, server) = serve(vec![
+            response("503 Service Unavailable", ""),
+            response("200 OK", TASK),
+        ])
+        .await;
+        let client = Client::builder(base_url)
+            .retries(1, Duration::from_millis(1))
+            .build()
+            .unwrap();
+
+        let task = client.get_task("tasks:abc").await.unwrap();
+        assert_eq!(task.title, "buy milk");
+        assert_eq!(server.await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_update_task_sends_json() {
+        let (base_url, server) = serve(vec![response("200 OK", TASK)]).await;
+        let client = Client::new(base_url).unwrap();
+
+        let update = UpdateTask {
+            completed: Some(true),
+            ..Default::default()
+        };
+        client.update_task("tasks:abc", &update).await.unwrap();
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("PUT /task/tasks:abc HTTP/1.1"));
+        assert!(requests[0].contains("content-type: application/json"));
+    }
+
+    #[tokio::test]
+    async fn test_move_task_sends_target() {
+        let (base_url, server) = serve(vec![response("200 OK", TASK)]).await;
+        let client = Client::new(base_url).unwrap();
+
+        let to = MoveTask::Before("tasks:def".to_string());
+        client.move_task("tasks:abc", &to).await.unwrap();
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("PUT /task/tasks:abc/move HTTP/1.1"));
+        assert!(requests[0].ends_with(r#"{"before":"tasks:def"}"#));
+    }
+
+    #[tokio::test]
+    async fn test_set_task_status() {
+        let (base_url, server) = serve(vec![response("200 OK", TASK)]).await;
+        let client = Client::new(base_url).unwrap();
+
+        client
+            .set_task_status("tasks:abc", "in progress")
+            .await
+            .unwrap();
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("PUT /task/tasks:abc/status/in%20progress HTTP/1.1"));
+    }
+
+    #[tokio::test]
+    async fn test_add_comment() {
+        let comment = r#"{"id":"comments:1","task":"tasks:abc","author":"anonymous","body":"hi","created_at":"2023-04-02T10:00:00Z"}"#;
+        let (base_url, server) = serve(vec![response("200 OK", comment)]).await;
+        let client = Client::new(base_url).unwrap();
+
+        let new_comment = NewComment {
+            body: " hi ".to_string(),
+            parent: None,
+        };
+        let comment = client.add_comment("tasks:abc", new_comment).await.unwrap();
+        assert!(!comment.deleted);
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("POST /task/tasks:abc/comments HTTP/1.1"));
+        assert!(requests[0].ends_with(r#"{"body":"hi"}"#));
+
+        let empty = NewComment {
+            body: "\n".to_string(),
+            parent: None,
+        };
+        let err = client.add_comment("tasks:abc", empty).await.unwrap_err();
+        assert!(matches!(err, Error::Invalid(_)));
+    }
+
+    #[tokio::test]
+    async fn test_read_notification() {
+        let notification = r#"{"id":"notifications:1","task":"tasks:abc","message":"Reminder: buy milk","created_at":"2023-04-02T10:00:00Z","read":true}"#;
+        let (base_url, server) = serve(vec![response("200 OK", notification)]).await;
+        let client = Client::new(base_url).unwrap();
+
+        let notification = client.read_notification("notifications:1").await.unwrap();
+        assert!(notification.read);
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("PUT /notification/notifications:1/read HTTP/1.1"));
+    }
+
+    #[tokio::test]
+    async fn test_workspace_tasks() {
+        let task = r#"{"id":"tasks:abc","title":"plan","completed":false,"created_at":"2023-04-02T10:00:00Z","workspace":"workspaces:team"}"#;
+        let tasks = format!("[{task}]");
+        let (base_url, server) =
+            serve(vec![response("200 OK", task), response("200 OK", &tasks)]).await;
+        let client = Client::new(base_url).unwrap();
+
+        let task = client
+            .create_workspace_task("workspaces:team", "plan")
+            .await
+            .unwrap();
+        assert_eq!(task.workspace.as_deref(), Some("workspaces:team"));
+        let tasks = client.get_workspace_tasks("workspaces:team").await.unwrap();
+        assert_eq!(tasks, [task]);
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("POST /task/plan?workspace=workspaces%3Ateam HTTP/1.1"));
+        assert!(requests[1].starts_with("GET /tasks?workspace=workspaces%3Ateam HTTP/1.1"));
+    }
+
+    #[tokio::test]
+    async fn test_workspace_statuses() {
+        let workflow =
+            r#"[{"id":"open","name":"Open"},{"id":"closed","name":"Closed","done":true}]"#;
+        let (base_url, server) = serve(vec![
+            response("200 OK", workflow),
+            response("200 OK", workflow),
+        ])
+        .await;
+        let client = Client::new(base_url).unwrap();
+
+        let workflow = client
+            .get_workspace_statuses("workspaces:team")
+            .await
+            .unwrap();
+        assert_eq!(workflow.statuses[0].id, "open");
+        let saved = client
+            .set_workspace_statuses("workspaces:team", &workflow)
+            .await
+            .unwrap();
+        assert_eq!(saved, workflow);
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("GET /statuses?workspace=workspaces%3Ateam HTTP/1.1"));
+        assert!(requests[1].starts_with("PUT /statuses?workspace=workspaces%3Ateam HTTP/1.1"));
+    }
+
+    #[tokio::test]
+    async fn test_assign_task() {
+        let task = r#"{"id":"tasks:abc","title":"review","completed":false,"created_at":"2023-04-02T10:00:00Z","assignee":"user:0f3a"}"#;
+        let tasks = format!("[{task}]");
+        let (base_url, server) =
+            serve(vec![response("200 OK", task), response("200 OK", &tasks)]).await;
+        let client = Client::new(base_url).unwrap();
+
+        let task = client
+            .assign_task("tasks:abc", " user:0f3a ")
+            .await
+            .unwrap();
+        assert_eq!(task.assignee.as_deref(), Some("user:0f3a"));
+        assert_eq!(client.get_my_tasks().await.unwrap(), [task]);
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("PUT /task/tasks:abc/assignee HTTP/1.1"));
+        assert!(requests[0].ends_with(r#"{"assignee":"user:0f3a"}"#));
+        assert!(requests[1].starts_with("GET /tasks?mine=true HTTP/1.1"));
+
+        let err = client
+            .assign_task("tasks:abc", "anonymous")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Invalid(_)));
+    }
+
+    #[tokio::test]
+    async fn test_add_webhook() {
+        let webhook = r#"{"id":"webhooks:1","url":"https://ci.example.com/todo","events":["created"],"created_at":"2023-04-02T10:00:00Z"}"#;
+        let (base_url, server) = serve(vec![response("200 OK", webhook)]).await;
+        let client = Client::new(base_url).unwrap();
+
+        let new_webhook = NewWebhook {
+            url: "https://ci.example.com/todo".to_string(),
+            secret: "0123456789abcdef".to_string(),
+            events: vec![TaskAction::Created, TaskAction::Created],
+        };
+        let webhook = client.add_webhook(new_webhook.clone()).await.unwrap();
+        assert_eq!(webhook.events, [TaskAction::Created]);
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("POST /webhooks HTTP/1.1"));
+        assert!(requests[0].ends_with(r#""events":["created"]}"#));
+
+        let short = NewWebhook {
+            secret: "short".to_string(),
+            ..new_webhook
+        };
+        let err = client.add_webhook(short).await.unwrap_err();
+        assert!(matches!(err, Error::Invalid(_)));
+    }
+
+    #[tokio::test]
+    async fn test_create_token() {
+        let issued = r#"{"token":"todo_pat_abcd1234","id":"api_tokens:1","name":"ci","user":"user:0f3a","scopes":["read","write"],"prefix":"todo_pat_abcd","created_at":"2023-04-02T10:00:00Z"}"#;
+        let (base_url, server) = serve(vec![response("200 OK", issued)]).await;
+        let client = Client::builder(base_url).token("secret").build().unwrap();
+
+        let new_token = NewApiToken {
+            name: " ci ".to_string(),
+            scopes: vec![Scope::Write, Scope::Read],
+        };
+        let issued = client.create_token(new_token.clone()).await.unwrap();
+        assert_eq!(issued.token, "todo_pat_abcd1234");
+        assert!(issued.api_token.allows(Scope::Write));
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("POST /tokens HTTP/1.1"));
+        assert!(requests[0].ends_with(r#"{"name":"ci","scopes":["read","write"]}"#));
+
+        let unscoped = NewApiToken {
+            scopes: vec![],
+            ..new_token
+        };
+        let err = client.create_token(unscoped).await.unwrap_err();
+        assert!(matches!(err, Error::Invalid(_)));
+    }
+
+    #[tokio::test]
+    async fn test_add_attachment_sends_multipart() {
+        let attachment = r#"{"id":"attachments:1","task":"tasks:abc","name":"a.txt","content_type":"text/plain","size":2,"author":"anonymous","created_at":"2023-04-02T10:00:00Z"}"#;
+        let (base_url, server) = serve(vec![response("200 OK", attachment)]).await;
+        let client = Client::new(base_url).unwrap();
+
+        let attachment = client
+            .add_attachment("tasks:abc", "a\".txt", "text/plain", b"hi".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(attachment.size, 2);
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("POST /task/tasks:abc/attachments HTTP/1.1"));
+        assert!(requests[0]
+            .contains("content-type: multipart/form-data; boundary=todo-client-boundary\r\n"));
+        assert!(requests[0].ends_with(
+            "--todo-client-boundary\r\n\
+             Content-Disposition: form-data; name=\"file\"; filename=\"a'.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\n\
+             hi\r\n\
+             --todo-client-boundary--\r\n"
+        ));
+    }
+
+    #[test]
+    fn test_multipart_boundary_is_not_in_the_file() {
+        let (boundary, _) = multipart("file", "a", "text/plain", b"todo-client-boundary".to_vec());
+        assert_eq!(boundary, "todo-client-boundaryx");
+    }
+
+    #[tokio::test]
+    async fn test_field_errors_are_parsed() {
+        let body = r#"{"errors":[{"field":"title","message":"title must not be empty"}]}"#;
+        let (base_url, _) = serve(vec![response("422 Unprocessable Entity", body)]).await;
+        let client = Client::new(base_url).unwrap();
+
+        let update = UpdateTask {
+            title: Some("x".to_string()),
+            ..Default::default()
+        };
+        match client.update_task("tasks:abc", &update).await.unwrap_err() {
+            Error::Invalid(errors) => {
+                assert_eq!(errors.message("title"), Some("title must not be empty"))
+            }
+            err => panic!("unexpected error {err:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_toggle_is_not_retried() {
+        let (base_url, server) = serve(vec![response("503 Service Unavailable", "busy")]).await;
+        let client = Client::builder(base_url)
+            .retries(3, Duration::from_millis(1))
+            .build()
+            .unwrap();
+
+        let err = client.toggle_task("tasks:abc").await.unwrap_err();
+        assert!(matches!(err, Error::Status { status: 503, ref message } if message == "busy"));
+        assert_eq!(server.await.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_base_url() {
+        assert!(matches!(
+            Client::new("not a url"),
+            Err(Error::InvalidUrl(_))
+        ));
+        assert!(matches!(
+            Client::new("mailto:me@example.com"),
+            Err(Error::InvalidUrl(_))
+        ));
+    }
+}
@@ -0,0 +1,33 @@
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("invalid base url '{0}'")]
+    InvalidUrl(String),
+
+    #[error("server responded with {status}: {message}")]
+    Status { status: u16, message: String },
+
+    #[error(transparent)]
+    Validation(#[from] todo_models::Error),
+
+    /// The server rejected the input, with an error for each field.
+    #[error("invalid input: {0}")]
+    Invalid(todo_models::ValidationErrors),
+
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+}
+
+impl Error {
+    /// Whether the request may succeed if sent again unchanged.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Http(e) => e.is_timeout() || e.is_request(),
+            Error::Status { status, .. } => matches!(status, 429 | 502 | 503 | 504),
+            _ => false,
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Error::Status { status: 404, .. })
+    }
+}
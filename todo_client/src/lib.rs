@@ -0,0 +1,19 @@
+//! Typed async client for the `todo_api` HTTP interface.
+//!
+//! Native programs use the default `native` feature, which runs on tokio.
+//! Browser builds disable default features and enable `wasm` instead.
+
+mod client;
+mod error;
+
+pub use client::*;
+pub use error::Error;
+pub use todo_models::{
+    AffectedRows, ApiToken, Assignment, Attachment, Comment, DeliveryStatus, EditComment,
+    Invitation, IssuedToken, Member, MoveTask, NewApiToken, NewComment, NewTask, NewWebhook,
+    NewWorkspace, Notification, Role, RowId, Scope, SetRole, Status, Task, TaskAction, TaskEvent,
+    UpdateTask, ValidationErrors, Webhook, WebhookDelivery, Workflow, Workspace,
+};
+
+#[cfg(not(any(feature = "native", feature = "wasm")))]
+compile_error!("enable either the `native` or the `wasm` feature of todo_client");
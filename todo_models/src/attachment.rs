@@ -0,0 +1,57 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::datetime;
+
+/// Maximum number of characters kept from an uploaded file's name.
+pub const ATTACHMENT_NAME_MAX_LEN: usize = 255;
+
+/// A file attached to a task. Only the metadata lives here; the API keeps
+/// the contents in its configured storage.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: String,
+    pub task: String,
+    pub name: String,
+    pub content_type: String,
+    /// Size in bytes.
+    pub size: u64,
+    pub author: String,
+    #[serde(serialize_with = "datetime::serialize")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// Cleans the name a client gave an uploaded file. Directories, control
+/// characters and quotes are dropped, so the name is safe to show and to put
+/// in a `Content-Disposition` header. Nameless files are called "file".
+pub fn attachment_name(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"')
+        .take(ATTACHMENT_NAME_MAX_LEN)
+        .collect();
+    match cleaned.trim() {
+        "" | "." | ".." => "file".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attachment_name() {
+        assert_eq!(attachment_name("screenshot.png"), "screenshot.png");
+        assert_eq!(attachment_name("../../etc/passwd"), "passwd");
+        assert_eq!(attachment_name("C:\\Users\\ada\\notes.txt"), "notes.txt");
+        assert_eq!(attachment_name("say \"hi\"\n.txt"), "say hi.txt");
+        assert_eq!(attachment_name("dir/"), "file");
+        assert_eq!(attachment_name(".."), "file");
+        assert_eq!(
+            attachment_name(&"a".repeat(300)).len(),
+            ATTACHMENT_NAME_MAX_LEN
+        );
+    }
+}
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+/// A calendar feed and the path it is served at. Anyone holding the token
+/// can read the tasks, so it is only returned when the feed is created.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CalendarFeed {
+    pub token: String,
+    pub path: String,
+}
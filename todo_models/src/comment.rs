@@ -0,0 +1,156 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{datetime, Error, ValidationErrors};
+
+/// Maximum number of characters in a comment.
+pub const COMMENT_MAX_LEN: usize = 4000;
+
+/// A comment on a task. Replies name the comment they answer as `parent`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Comment {
+    pub id: String,
+    pub task: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    pub author: String,
+    pub body: String,
+    #[serde(serialize_with = "datetime::serialize")]
+    pub created_at: DateTime<Utc>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "datetime::serialize_option"
+    )]
+    pub edited_at: Option<DateTime<Utc>>,
+    /// Deleted comments that have replies stay, without their body, so the
+    /// thread still reads in order.
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+/// Input for commenting on a task.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewComment {
+    pub body: String,
+    /// The comment this one replies to, on the same task.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+}
+
+impl NewComment {
+    pub fn sanitize(self) -> Result<NewComment, ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        let body = errors.check(comment_body(&self.body));
+        errors.into_result(body.map(|body| NewComment {
+            body,
+            parent: self.parent,
+        }))
+    }
+}
+
+/// Input for editing a comment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EditComment {
+    pub body: String,
+}
+
+impl EditComment {
+    pub fn sanitize(self) -> Result<EditComment, ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        let body = errors.check(comment_body(&self.body));
+        errors.into_result(body.map(|body| EditComment { body }))
+    }
+}
+
+/// Cleans a comment like a title, except that line breaks are kept.
+fn comment_body(text: &str) -> Result<String, Error> {
+    let cleaned: String = text
+        .replace("\r\n", "\n")
+        .chars()
+        .filter_map(|c| match c {
+            '\n' => Some('\n'),
+            '\t' | '\r' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect();
+    let cleaned = cleaned.trim();
+
+    if cleaned.is_empty() {
+        return Err(Error::CommentEmpty);
+    }
+    if cleaned.chars().count() > COMMENT_MAX_LEN {
+        return Err(Error::CommentTooLong(COMMENT_MAX_LEN));
+    }
+    Ok(cleaned.to_string())
+}
+
+/// The direct replies to `parent`, or the top-level comments for `None`, in
+/// the order given.
+pub fn replies<'a>(
+    comments: &'a [Comment],
+    parent: Option<&'a str>,
+) -> impl Iterator<Item = &'a Comment> {
+    comments
+        .iter()
+        .filter(move |comment| comment.parent.as_deref() == parent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment(id: &str, parent: Option<&str>) -> Comment {
+        Comment {
+            id: id.to_string(),
+            task: "tasks:abc".to_string(),
+            parent: parent.map(str::to_string),
+            author: "anonymous".to_string(),
+            body: "hi".to_string(),
+            created_at: "2023-04-02T10:00:00Z".parse().unwrap(),
+            edited_at: None,
+            deleted: false,
+        }
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let new_comment = NewComment {
+            body: " first line\r\n\tsecond\u{7} ".to_string(),
+            parent: None,
+        };
+        assert_eq!(new_comment.sanitize().unwrap().body, "first line\n second");
+
+        let empty = EditComment {
+            body: "\n \n".to_string(),
+        };
+        assert_eq!(
+            empty.sanitize().unwrap_err().message("body"),
+            Some("comments must not be empty")
+        );
+
+        let long = EditComment {
+            body: "a".repeat(COMMENT_MAX_LEN + 1),
+        };
+        assert!(long.sanitize().is_err());
+    }
+
+    #[test]
+    fn test_replies() {
+        let comments = [
+            comment("comments:1", None),
+            comment("comments:2", Some("comments:1")),
+            comment("comments:3", None),
+            comment("comments:4", Some("comments:1")),
+        ];
+        let ids = |parent| -> Vec<&str> {
+            replies(&comments, parent)
+                .map(|comment| comment.id.as_str())
+                .collect()
+        };
+        assert_eq!(ids(None), ["comments:1", "comments:3"]);
+        assert_eq!(ids(Some("comments:1")), ["comments:2", "comments:4"]);
+        assert!(ids(Some("comments:2")).is_empty());
+    }
+}
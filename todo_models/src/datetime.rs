@@ -0,0 +1,47 @@
+//! Serializes datetimes as a newtype named [`NEWTYPE`] around their RFC 3339
+//! string. Formats without a datetime type, like JSON, see only the string,
+//! exactly as chrono writes it. Serializers that have one, like the
+//! SurrealDB value bridge in `todo_api`, can recognise the name and keep the
+//! type. Deserialization needs no help, so use these with `serialize_with`.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Serialize, Serializer};
+
+pub const NEWTYPE: &str = "$todo_models::Datetime";
+
+pub fn serialize<S: Serializer>(
+    datetime: &DateTime<Utc>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_newtype_struct(
+        NEWTYPE,
+        &datetime.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+    )
+}
+
+pub fn serialize_option<S: Serializer>(
+    datetime: &Option<DateTime<Utc>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    struct Datetime<'a>(&'a DateTime<Utc>);
+
+    impl Serialize for Datetime<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serialize(self.0, serializer)
+        }
+    }
+
+    match datetime {
+        Some(datetime) => serializer.serialize_some(&Datetime(datetime)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Like [`serialize_option`] for the `Option<Option<_>>` fields of partial
+/// updates; see [`double_option`](crate::double_option).
+pub fn serialize_double_option<S: Serializer>(
+    datetime: &Option<Option<DateTime<Utc>>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serialize_option(&datetime.flatten(), serializer)
+}
@@ -0,0 +1,22 @@
+//! (De)serializes `Option<Option<T>>` fields of partial updates, so a
+//! missing field is `None`, leaving the value as it is, and `null` is
+//! `Some(None)`, clearing it. Use with `#[serde(default, with = ...)]` and
+//! `skip_serializing_if = "Option::is_none"`.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub fn serialize<T: Serialize, S: Serializer>(
+    value: &Option<Option<T>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => value.serialize(serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+pub fn deserialize<'de, T: Deserialize<'de>, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Option<T>>, D::Error> {
+    Option::deserialize(deserializer).map(Some)
+}
@@ -0,0 +1,108 @@
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error("title must not be empty")]
+    TitleEmpty,
+
+    #[error("title must be at most {0} characters")]
+    TitleTooLong(usize),
+
+    #[error("priority must be between 1 and 9, got {0}")]
+    PriorityOutOfRange(u8),
+
+    #[error("due date must be between the years {0} and {1}")]
+    DueOutOfRange(i32, i32),
+
+    #[error("reminders must be between the years {0} and {1}")]
+    ReminderOutOfRange(i32, i32),
+
+    #[error("no status with id {0:?}")]
+    UnknownStatus(String),
+
+    #[error("status id {0:?} must be 1 to 40 lowercase letters, digits, `_` or `-`")]
+    StatusIdInvalid(String),
+
+    #[error("status names must not be empty")]
+    StatusNameEmpty,
+
+    #[error("status names must be at most {0} characters")]
+    StatusNameTooLong(usize),
+
+    #[error("status id {0:?} is used more than once")]
+    DuplicateStatus(String),
+
+    #[error("statuses must include at least one open and one done status")]
+    StatusesIncomplete,
+
+    #[error("comments must not be empty")]
+    CommentEmpty,
+
+    #[error("comments must be at most {0} characters")]
+    CommentTooLong(usize),
+
+    #[error("{0} is not a comment on this task")]
+    CommentParentInvalid(String),
+
+    #[error("attachments must not be empty")]
+    AttachmentEmpty,
+
+    #[error("{0:?} is not an http or https url")]
+    WebhookUrlInvalid(String),
+
+    #[error("{0:?} is not a public host")]
+    WebhookHostNotPublic(String),
+
+    #[error("webhook secrets must be at least {0} characters")]
+    WebhookSecretTooShort(usize),
+
+    #[error("webhooks must subscribe to at least one event")]
+    WebhookEventsEmpty,
+
+    #[error("workspace names must not be empty")]
+    WorkspaceNameEmpty,
+
+    #[error("workspace names must be at most {0} characters")]
+    WorkspaceNameTooLong(usize),
+
+    #[error("token names must not be empty")]
+    TokenNameEmpty,
+
+    #[error("token names must be at most {0} characters")]
+    TokenNameTooLong(usize),
+
+    #[error("tokens must have at least one scope")]
+    TokenScopesEmpty,
+
+    #[error("{0:?} is not a user, such as \"user:<key>\"")]
+    AssigneeInvalid(String),
+
+    #[error("{0} is not a member of the task's workspace")]
+    AssigneeNotMember(String),
+}
+
+impl Error {
+    /// The input field the error is about.
+    pub fn field(&self) -> &'static str {
+        match self {
+            Error::TitleEmpty | Error::TitleTooLong(_) => "title",
+            Error::PriorityOutOfRange(_) => "priority",
+            Error::DueOutOfRange(..) => "due",
+            Error::ReminderOutOfRange(..) => "remind_at",
+            Error::UnknownStatus(_) => "status",
+            Error::StatusIdInvalid(_)
+            | Error::StatusNameEmpty
+            | Error::StatusNameTooLong(_)
+            | Error::DuplicateStatus(_)
+            | Error::StatusesIncomplete => "statuses",
+            Error::CommentEmpty | Error::CommentTooLong(_) => "body",
+            Error::CommentParentInvalid(_) => "parent",
+            Error::AttachmentEmpty => "file",
+            Error::WebhookUrlInvalid(_) | Error::WebhookHostNotPublic(_) => "url",
+            Error::WebhookSecretTooShort(_) => "secret",
+            Error::WebhookEventsEmpty => "events",
+            Error::WorkspaceNameEmpty | Error::WorkspaceNameTooLong(_) => "name",
+            Error::TokenNameEmpty | Error::TokenNameTooLong(_) => "name",
+            Error::TokenScopesEmpty => "scopes",
+            Error::AssigneeInvalid(_) | Error::AssigneeNotMember(_) => "assignee",
+        }
+    }
+}
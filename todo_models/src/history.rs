@@ -0,0 +1,144 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{datetime, Task};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskAction {
+    Created,
+    Updated,
+    Toggled,
+    /// The task moved to another place in the list.
+    Moved,
+    Deleted,
+}
+
+/// A field that changed, with its values written out as text. `None` means
+/// the field was unset, or the task did not exist on that side.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// One change to a task. Events are never edited, and outlive the task they
+/// describe.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskEvent {
+    pub id: String,
+    pub task: String,
+    pub actor: String,
+    pub action: TaskAction,
+    #[serde(serialize_with = "datetime::serialize")]
+    pub at: DateTime<Utc>,
+    #[serde(default)]
+    pub changes: Vec<FieldChange>,
+}
+
+/// The fields that differ between two versions of a task. Pass `None` as
+/// `before` for a new task and as `after` for a deleted one.
+pub fn diff(before: Option<&Task>, after: Option<&Task>) -> Vec<FieldChange> {
+    fn time(datetime: &DateTime<Utc>) -> String {
+        datetime.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+    }
+
+    let fields = |task: Option<&Task>| -> [(&'static str, Option<String>); 8] {
+        [
+            ("title", task.map(|t| t.title.clone())),
+            ("completed", task.map(|t| t.completed.to_string())),
+            ("created_at", task.map(|t| time(&t.created_at))),
+            ("due", task.and_then(|t| t.due.as_ref().map(time))),
+            (
+                "priority",
+                task.and_then(|t| t.priority.map(|p| p.to_string())),
+            ),
+            (
+                "remind_at",
+                task.and_then(|t| t.remind_at.as_ref().map(time)),
+            ),
+            (
+                "status",
+                task.map(|t| t.status.clone()).filter(|s| !s.is_empty()),
+            ),
+            ("assignee", task.and_then(|t| t.assignee.clone())),
+        ]
+    };
+
+    fields(before)
+        .into_iter()
+        .zip(fields(after))
+        .filter(|((_, from), (_, to))| from != to)
+        .map(|((field, from), (_, to))| FieldChange {
+            field: field.to_string(),
+            from,
+            to,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task() -> Task {
+        Task {
+            id: "tasks:abc".to_string(),
+            title: "buy milk".to_string(),
+            completed: false,
+            created_at: "2023-04-02T10:00:00Z".parse().unwrap(),
+            due: None,
+            priority: Some(2),
+            remind_at: None,
+            rank: "i".to_string(),
+            status: "todo".to_string(),
+            workspace: None,
+            assignee: None,
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = task();
+        let after = Task {
+            completed: true,
+            due: Some("2023-04-05T00:00:00Z".parse().unwrap()),
+            ..task()
+        };
+        assert_eq!(
+            diff(Some(&before), Some(&after)),
+            [
+                FieldChange {
+                    field: "completed".to_string(),
+                    from: Some("false".to_string()),
+                    to: Some("true".to_string()),
+                },
+                FieldChange {
+                    field: "due".to_string(),
+                    from: None,
+                    to: Some("2023-04-05T00:00:00Z".to_string()),
+                },
+            ]
+        );
+        assert!(diff(Some(&before), Some(&before)).is_empty());
+
+        let created: Vec<String> = diff(None, Some(&before))
+            .into_iter()
+            .map(|change| change.field)
+            .collect();
+        assert_eq!(
+            created,
+            ["title", "completed", "created_at", "priority", "status"]
+        );
+        assert!(diff(Some(&before), None).iter().all(|c| c.to.is_none()));
+    }
+
+    #[test]
+    fn test_event_json() {
+        let json = r#"{"id":"task_events:1","task":"tasks:abc","actor":"anonymous","action":"toggled","at":"2023-04-02T10:00:00Z"}"#;
+        let event: TaskEvent = serde_json::from_str(json).unwrap();
+        assert_eq!(event.action, TaskAction::Toggled);
+        assert!(event.changes.is_empty());
+    }
+}
@@ -0,0 +1,65 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{datetime, validation, ValidationErrors};
+
+/// A task as written to and read from export files. Ids are not portable
+/// between datastores, so they are left out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskRecord {
+    pub title: String,
+    #[serde(default)]
+    pub completed: bool,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "datetime::serialize_option"
+    )]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "datetime::serialize_option"
+    )]
+    pub due: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u8>,
+}
+
+impl TaskRecord {
+    /// Cleans the record by the same rules as tasks created through the API.
+    pub fn sanitize(self) -> Result<TaskRecord, ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        let title = errors.check(validation::TITLE.apply(&self.title));
+        let due = self
+            .due
+            .and_then(|due| errors.check(validation::DUE.apply(due)));
+        let priority = self
+            .priority
+            .and_then(|priority| errors.check(validation::priority(priority)));
+        errors.into_result(title.map(|title| TaskRecord {
+            title,
+            due,
+            priority,
+            ..self
+        }))
+    }
+}
+
+/// A record of an import file that was rejected, with its 1-based line
+/// (its position in the array for JSON imports).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvalidRecord {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Outcome of an import. On a dry run nothing is written, and `created`
+/// lists what would have been.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub created: Vec<TaskRecord>,
+    pub duplicates: Vec<TaskRecord>,
+    pub invalid: Vec<InvalidRecord>,
+}
@@ -0,0 +1,35 @@
+//! Data transfer objects shared by `todo_api` and `todo_web`.
+//!
+//! Both crates serialize and deserialize these exact types, so a change to
+//! the wire format on one side fails to compile on the other.
+
+mod attachment;
+mod calendar;
+mod comment;
+pub mod datetime;
+pub mod double_option;
+mod error;
+mod history;
+mod import;
+mod notification;
+pub mod rank;
+mod status;
+mod task;
+mod token;
+pub mod validation;
+mod webhook;
+mod workspace;
+
+pub use attachment::*;
+pub use calendar::*;
+pub use comment::*;
+pub use error::Error;
+pub use history::*;
+pub use import::*;
+pub use notification::*;
+pub use status::*;
+pub use task::*;
+pub use token::*;
+pub use validation::ValidationErrors;
+pub use webhook::*;
+pub use workspace::*;
@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{datetime, Task};
+
+/// A reminder shown in the app, kept until it is read.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: String,
+    /// Who the notification is for, as an actor such as `user:<key>`.
+    /// Notifications made before they had one are shown to no one.
+    #[serde(default)]
+    pub user: String,
+    pub task: String,
+    pub message: String,
+    #[serde(serialize_with = "datetime::serialize")]
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub read: bool,
+}
+
+/// The text of a task's reminder, the same for every way it is sent.
+pub fn reminder_message(task: &Task) -> String {
+    const FORMAT: &str = "%Y-%m-%d %H:%M UTC";
+    match task.due {
+        Some(due) => format!("Reminder: {} (due {})", task.title, due.format(FORMAT)),
+        None => format!("Reminder: {}", task.title),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reminder_message() {
+        let mut task: Task = serde_json::from_str(
+            r#"{"id":"tasks:abc","title":"call mom","completed":false,"created_at":"2023-04-02T10:00:00Z"}"#,
+        )
+        .unwrap();
+        assert_eq!(reminder_message(&task), "Reminder: call mom");
+
+        task.due = Some("2023-04-05T17:00:00Z".parse().unwrap());
+        assert_eq!(
+            reminder_message(&task),
+            "Reminder: call mom (due 2023-04-05 17:00 UTC)"
+        );
+    }
+}
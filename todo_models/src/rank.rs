@@ -0,0 +1,130 @@
+//! Ranks order tasks by position. A rank is a string of base-36 digits that
+//! sorts byte by byte, and there is always room for another rank between
+//! two different ones, so moving a task only rewrites that task.
+//!
+//! Ranks never end in `0`, which is what keeps the room: nothing sorts
+//! between `a` and `a0`, but `a` and `a1` have `a0i` between them.
+
+const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+fn digit(c: u8) -> Option<usize> {
+    DIGITS.iter().position(|&d| d == c)
+}
+
+fn is_valid(rank: &str) -> bool {
+    !rank.ends_with('0') && rank.bytes().all(|c| digit(c).is_some())
+}
+
+/// A rank that sorts after `before` and before `after`, where `None` means
+/// the start or end of the list. Returns `None` when the bounds are out of
+/// order or not valid ranks, which happens when tasks share a rank and must
+/// be ranked afresh.
+pub fn between(before: Option<&str>, after: Option<&str>) -> Option<String> {
+    let before = before.unwrap_or("");
+    if !is_valid(before) || !after.is_none_or(|after| is_valid(after) && before < after) {
+        return None;
+    }
+    Some(midpoint(before.as_bytes(), after.map(str::as_bytes)))
+}
+
+/// `count` ranks in order, for ranking a whole list afresh.
+pub fn sequence(count: usize) -> Vec<String> {
+    let mut ranks: Vec<String> = Vec::with_capacity(count);
+    for _ in 0..count {
+        let rank =
+            between(ranks.last().map(String::as_str), None).expect("ranks made in order are valid");
+        ranks.push(rank);
+    }
+    ranks
+}
+
+/// Fractional indexing: `a < b`, `a` may be empty and `b` absent.
+fn midpoint(a: &[u8], b: Option<&[u8]>) -> String {
+    if let Some(b) = b {
+        // Keep the common prefix, reading a missing digit of `a` as zero.
+        let common = b
+            .iter()
+            .enumerate()
+            .take_while(|&(i, &c)| a.get(i).copied().unwrap_or(b'0') == c)
+            .count();
+        if common > 0 {
+            let rest = midpoint(a.get(common..).unwrap_or(&[]), Some(&b[common..]));
+            return format!("{}{rest}", String::from_utf8_lossy(&b[..common]));
+        }
+    }
+
+    let low = a.first().and_then(|&c| digit(c)).unwrap_or(0);
+    let high = b
+        .and_then(|b| b.first())
+        .and_then(|&c| digit(c))
+        .unwrap_or(DIGITS.len());
+    if high - low > 1 {
+        return (DIGITS[(low + high).div_ceil(2)] as char).to_string();
+    }
+    match b {
+        Some(b) if b.len() > 1 => (b[0] as char).to_string(),
+        _ => format!(
+            "{}{}",
+            DIGITS[low] as char,
+            midpoint(a.get(1..).unwrap_or(&[]), None)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_between(before: Option<&str>, after: Option<&str>) -> String {
+        let rank = between(before, after).unwrap();
+        assert!(is_valid(&rank), "{rank}");
+        assert!(
+            before.is_none_or(|b| b < rank.as_str()),
+            "{before:?} < {rank}"
+        );
+        assert!(
+            after.is_none_or(|a| rank.as_str() < a),
+            "{rank} < {after:?}"
+        );
+        rank
+    }
+
+    #[test]
+    fn test_between() {
+        assert_eq!(between(None, None).as_deref(), Some("i"));
+        assert_between(Some("a"), Some("b"));
+        assert_between(Some("a"), Some("a1"));
+        assert_between(Some("az"), Some("b"));
+        assert_between(Some("zz"), None);
+        assert_between(None, Some("01"));
+
+        assert_eq!(between(Some("b"), Some("a")), None);
+        assert_eq!(between(Some("a"), Some("a")), None);
+        assert_eq!(between(Some(""), Some("")), None);
+        assert_eq!(between(Some("a0"), None), None);
+        assert_eq!(between(Some("A"), None), None);
+    }
+
+    #[test]
+    fn test_repeated_inserts_keep_order() {
+        // Always inserting at the front, the back, or just after the first.
+        let mut front = vec![assert_between(None, None)];
+        let mut back = front.clone();
+        let mut middle = vec![front[0].clone(), assert_between(Some(&front[0]), None)];
+        for _ in 0..200 {
+            front.insert(0, assert_between(None, Some(&front[0])));
+            back.push(assert_between(back.last().map(String::as_str), None));
+            middle.insert(1, assert_between(Some(&middle[0]), Some(&middle[1])));
+        }
+        for ranks in [front, back, middle] {
+            assert!(ranks.windows(2).all(|w| w[0] < w[1]));
+        }
+    }
+
+    #[test]
+    fn test_sequence() {
+        let ranks = sequence(50);
+        assert_eq!(ranks.len(), 50);
+        assert!(ranks.windows(2).all(|w| w[0] < w[1]));
+    }
+}
@@ -0,0 +1,241 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Task, UpdateTask, ValidationErrors};
+
+/// Maximum number of characters in a status id or name.
+pub const STATUS_MAX_LEN: usize = 40;
+
+/// A stage of the workflow, shown as a column on the board.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Status {
+    /// Stored on tasks, so a status can be renamed without moving them.
+    pub id: String,
+    pub name: String,
+    /// Tasks in a done status count as completed.
+    #[serde(default)]
+    pub done: bool,
+}
+
+impl Status {
+    fn new(id: &str, name: &str, done: bool) -> Status {
+        Status {
+            id: id.to_string(),
+            name: name.to_string(),
+            done,
+        }
+    }
+}
+
+/// The statuses tasks move through, in board order, written as a plain
+/// array. New and reopened tasks go to the first open status, and completed
+/// ones to the first done status, so `completed` and toggling keep working.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Workflow {
+    pub statuses: Vec<Status>,
+}
+
+impl Default for Workflow {
+    fn default() -> Self {
+        Workflow {
+            statuses: vec![
+                Status::new("todo", "Todo", false),
+                Status::new("in_progress", "In progress", false),
+                Status::new("review", "Review", false),
+                Status::new("done", "Done", true),
+            ],
+        }
+    }
+}
+
+impl Workflow {
+    pub fn get(&self, id: &str) -> Option<&Status> {
+        self.statuses.iter().find(|status| status.id == id)
+    }
+
+    /// The first status that is open, or done if `done` is set.
+    pub fn first(&self, done: bool) -> &Status {
+        self.statuses
+            .iter()
+            .find(|status| status.done == done)
+            .expect("sanitized workflows have open and done statuses")
+    }
+
+    /// The status a task is in. Tasks made before statuses existed, or whose
+    /// status was since removed, are placed by whether they are completed.
+    pub fn status_of(&self, task: &Task) -> &Status {
+        self.get(&task.status)
+            .unwrap_or_else(|| self.first(task.completed))
+    }
+
+    /// Completes the update so status and `completed` agree: a new status
+    /// decides whether the task is completed, and completing or reopening a
+    /// task moves it out of a status that no longer fits.
+    pub fn settle(&self, task: &Task, update: UpdateTask) -> Result<UpdateTask, Error> {
+        if let Some(id) = &update.status {
+            let status = self
+                .get(id)
+                .ok_or_else(|| Error::UnknownStatus(id.clone()))?;
+            return Ok(UpdateTask {
+                completed: Some(status.done),
+                ..update
+            });
+        }
+        match update.completed {
+            Some(completed) if self.status_of(task).done != completed => Ok(UpdateTask {
+                status: Some(self.first(completed).id.clone()),
+                ..update
+            }),
+            _ => Ok(update),
+        }
+    }
+
+    /// Trims ids and names and checks that ids are unique slugs and that
+    /// there is somewhere for both open and done tasks to go.
+    pub fn sanitize(self) -> Result<Workflow, ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        let mut statuses: Vec<Status> = vec![];
+        for status in self.statuses {
+            let id = errors.check(status_id(&status.id));
+            let name = errors.check(status_name(&status.name));
+            if let (Some(id), Some(name)) = (id, name) {
+                if statuses.iter().any(|s| s.id == id) {
+                    errors.check::<()>(Err(Error::DuplicateStatus(id)));
+                } else {
+                    statuses.push(Status {
+                        id,
+                        name,
+                        done: status.done,
+                    });
+                }
+            }
+        }
+        let has = |done: bool| statuses.iter().any(|status| status.done == done);
+        if errors.errors.is_empty() && !(has(false) && has(true)) {
+            errors.check::<()>(Err(Error::StatusesIncomplete));
+        }
+        errors.into_result(Some(Workflow { statuses }))
+    }
+}
+
+fn status_id(id: &str) -> Result<String, Error> {
+    let id = id.trim();
+    let valid = (1..=STATUS_MAX_LEN).contains(&id.len())
+        && id
+            .bytes()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'_' || c == b'-');
+    match valid {
+        true => Ok(id.to_string()),
+        false => Err(Error::StatusIdInvalid(id.to_string())),
+    }
+}
+
+fn status_name(name: &str) -> Result<String, Error> {
+    let name = name.trim();
+    match name.chars().count() {
+        0 => Err(Error::StatusNameEmpty),
+        len if len > STATUS_MAX_LEN => Err(Error::StatusNameTooLong(STATUS_MAX_LEN)),
+        _ => Ok(name.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(status: &str, completed: bool) -> Task {
+        Task {
+            id: "tasks:abc".to_string(),
+            title: "test".to_string(),
+            completed,
+            created_at: "2023-04-02T10:00:00Z".parse().unwrap(),
+            due: None,
+            priority: None,
+            remind_at: None,
+            rank: String::new(),
+            status: status.to_string(),
+            workspace: None,
+            assignee: None,
+        }
+    }
+
+    #[test]
+    fn test_status_of() {
+        let workflow = Workflow::default();
+        assert_eq!(workflow.status_of(&task("review", false)).id, "review");
+        assert_eq!(workflow.status_of(&task("", false)).id, "todo");
+        assert_eq!(workflow.status_of(&task("removed", true)).id, "done");
+    }
+
+    #[test]
+    fn test_settle() {
+        let workflow = Workflow::default();
+        let review = task("review", false);
+
+        let update = UpdateTask {
+            status: Some("done".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            workflow.settle(&review, update).unwrap().completed,
+            Some(true)
+        );
+
+        let complete = UpdateTask {
+            completed: Some(true),
+            ..Default::default()
+        };
+        let settled = workflow.settle(&review, complete).unwrap();
+        assert_eq!(settled.status.as_deref(), Some("done"));
+
+        // Reopening a task that is already open leaves it where it is.
+        let reopen = UpdateTask {
+            completed: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(workflow.settle(&review, reopen).unwrap().status, None);
+
+        let unknown = UpdateTask {
+            status: Some("archived".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            workflow.settle(&review, unknown),
+            Err(Error::UnknownStatus("archived".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let json = r#"[{"id":" todo ","name":" Todo "},{"id":"done","name":"Done","done":true}]"#;
+        let workflow: Workflow = serde_json::from_str(json).unwrap();
+        let workflow = workflow.sanitize().unwrap();
+        assert_eq!(workflow.statuses[0], Status::new("todo", "Todo", false));
+
+        let invalid = Workflow {
+            statuses: vec![
+                Status::new("Todo", "Todo", false),
+                Status::new("done", "", true),
+                Status::new("doing", "Doing", false),
+                Status::new("doing", "Doing again", false),
+            ],
+        };
+        let messages: Vec<String> = invalid
+            .sanitize()
+            .unwrap_err()
+            .errors
+            .into_iter()
+            .map(|e| e.message)
+            .collect();
+        assert_eq!(messages.len(), 3);
+        assert!(messages[2].contains("doing"));
+
+        let open_only = Workflow {
+            statuses: vec![Status::new("todo", "Todo", false)],
+        };
+        assert_eq!(
+            open_only.sanitize().unwrap_err().errors[0].field,
+            "statuses"
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{datetime, double_option, validation, Error, ValidationErrors};
+
+/// Maximum number of characters allowed in a task title.
+pub const TITLE_MAX_LEN: usize = 256;
+
+/// Priorities follow iCalendar: 1 is the highest and 9 the lowest.
+pub const PRIORITY_RANGE: std::ops::RangeInclusive<u8> = 1..=9;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Task {
+    pub id: String,
+    pub title: String,
+    pub completed: bool,
+    #[serde(serialize_with = "datetime::serialize")]
+    pub created_at: DateTime<Utc>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "datetime::serialize_option"
+    )]
+    pub due: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u8>,
+    /// When to remind about the task. The API clears it once the reminder
+    /// is sent.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "datetime::serialize_option"
+    )]
+    pub remind_at: Option<DateTime<Utc>>,
+    /// Position in the list, compared as text; see [`rank`](crate::rank).
+    /// Tasks made before ranks existed have an empty one.
+    #[serde(default)]
+    pub rank: String,
+    /// Id of the task's [`Status`](crate::Status). Tasks made before
+    /// statuses existed have an empty one; see
+    /// [`Workflow::status_of`](crate::Workflow::status_of).
+    #[serde(default)]
+    pub status: String,
+    /// Id of the [`Workspace`](crate::Workspace) sharing the task. Tasks
+    /// without one are open to everyone, as before workspaces existed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<String>,
+    /// The user the task is assigned to, as an actor such as `user:<key>`.
+    /// Tasks in a workspace are only assigned to its members.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<String>,
+}
+
+/// Input for creating a task.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewTask {
+    pub title: String,
+}
+
+impl NewTask {
+    pub fn new(title: impl Into<String>) -> NewTask {
+        NewTask {
+            title: title.into(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        validation::TITLE.apply(&self.title).map(drop)
+    }
+
+    /// Cleans the input by the rules in [`validation`], reporting every
+    /// invalid field.
+    pub fn sanitize(self) -> Result<NewTask, ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        let title = errors.check(validation::TITLE.apply(&self.title));
+        errors.into_result(title.map(|title| NewTask { title }))
+    }
+}
+
+/// Partial update of a task. Fields left as `None` are not changed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UpdateTask {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed: Option<bool>,
+    /// `Some(None)`, written as `null`, clears the due date; the same goes
+    /// for `priority` and `remind_at`.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "datetime::serialize_double_option",
+        deserialize_with = "double_option::deserialize"
+    )]
+    pub due: Option<Option<DateTime<Utc>>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "double_option"
+    )]
+    pub priority: Option<Option<u8>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "datetime::serialize_double_option",
+        deserialize_with = "double_option::deserialize"
+    )]
+    pub remind_at: Option<Option<DateTime<Utc>>>,
+    /// Checked against the workflow by the API, which also keeps
+    /// `completed` in step; see [`Workflow::settle`](crate::Workflow::settle).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+}
+
+impl UpdateTask {
+    pub fn validate(&self) -> Result<(), Error> {
+        if let Some(title) = &self.title {
+            validation::TITLE.apply(title)?;
+        }
+        if let Some(Some(due)) = self.due {
+            validation::DUE.apply(due)?;
+        }
+        if let Some(Some(priority)) = self.priority {
+            validation::priority(priority)?;
+        }
+        if let Some(Some(remind_at)) = self.remind_at {
+            validation::reminder(remind_at)?;
+        }
+        Ok(())
+    }
+
+    /// Cleans the set fields by the rules in [`validation`], reporting every
+    /// invalid one.
+    pub fn sanitize(self) -> Result<UpdateTask, ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        let title = self
+            .title
+            .and_then(|title| errors.check(validation::TITLE.apply(&title)));
+        let due = match self.due {
+            Some(Some(due)) => errors.check(validation::DUE.apply(due)).map(Some),
+            due => due,
+        };
+        let priority = match self.priority {
+            Some(Some(priority)) => errors.check(validation::priority(priority)).map(Some),
+            priority => priority,
+        };
+        let remind_at = match self.remind_at {
+            Some(Some(remind_at)) => errors.check(validation::reminder(remind_at)).map(Some),
+            remind_at => remind_at,
+        };
+        errors.into_result(Some(UpdateTask {
+            title,
+            completed: self.completed,
+            due,
+            priority,
+            remind_at,
+            status: self.status,
+        }))
+    }
+
+    /// The task as it is after this update.
+    pub fn apply(&self, task: &Task) -> Task {
+        let task = task.clone();
+        Task {
+            title: self.title.clone().unwrap_or(task.title),
+            completed: self.completed.unwrap_or(task.completed),
+            due: self.due.unwrap_or(task.due),
+            priority: self.priority.unwrap_or(task.priority),
+            remind_at: self.remind_at.unwrap_or(task.remind_at),
+            status: self.status.clone().unwrap_or(task.status),
+            ..task
+        }
+    }
+}
+
+/// Where to move a task: just before or just after another task, written
+/// as `{"before": id}` or `{"after": id}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MoveTask {
+    Before(String),
+    After(String),
+}
+
+impl MoveTask {
+    /// The id of the task to move next to.
+    pub fn target(&self) -> &str {
+        match self {
+            MoveTask::Before(id) | MoveTask::After(id) => id,
+        }
+    }
+}
+
+/// Input for assigning a task to a user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Assignment {
+    pub assignee: String,
+}
+
+impl Assignment {
+    /// Trims the assignee, which must be a signed-in user's actor, as
+    /// recorded in the task history.
+    pub fn sanitize(self) -> Result<Assignment, ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        let assignee = errors.check(assignee(&self.assignee));
+        errors.into_result(assignee.map(|assignee| Assignment { assignee }))
+    }
+}
+
+fn assignee(assignee: &str) -> Result<String, Error> {
+    let assignee = assignee.trim();
+    let valid = assignee.strip_prefix("user:").is_some_and(|key| {
+        !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    });
+    match valid {
+        true => Ok(assignee.to_string()),
+        false => Err(Error::AssigneeInvalid(assignee.to_string())),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AffectedRows {
+    pub affected_rows: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RowId {
+    pub id: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_json_round_trip() {
+        let json = r#"{"id":"tasks:abc","title":"test","completed":false,"created_at":"2023-04-02T10:00:00Z"}"#;
+        let task: Task = serde_json::from_str(json).unwrap();
+        assert_eq!(task.id, "tasks:abc");
+        assert_eq!(task.title, "test");
+        assert_eq!(task.due, None);
+        assert_eq!(task.rank, "");
+
+        let json = serde_json::to_string(&task).unwrap();
+        assert!(json.contains(r#""created_at":"2023-04-02T10:00:00Z""#));
+        let back: Task = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, task);
+    }
+
+    #[test]
+    fn test_move_task_json() {
+        let json = r#"{"after":"tasks:abc"}"#;
+        let move_task: MoveTask = serde_json::from_str(json).unwrap();
+        assert_eq!(move_task, MoveTask::After("tasks:abc".to_string()));
+        assert_eq!(move_task.target(), "tasks:abc");
+        assert_eq!(serde_json::to_string(&move_task).unwrap(), json);
+
+        assert!(serde_json::from_str::<MoveTask>("{}").is_err());
+    }
+
+    #[test]
+    fn test_new_task_validate() {
+        assert!(NewTask::new("test").validate().is_ok());
+        assert_eq!(NewTask::new("   ").validate(), Err(Error::TitleEmpty));
+        assert_eq!(
+            NewTask::new("a".repeat(TITLE_MAX_LEN + 1)).validate(),
+            Err(Error::TitleTooLong(TITLE_MAX_LEN))
+        );
+    }
+
+    #[test]
+    fn test_update_task_skips_unset_fields() {
+        let update = UpdateTask {
+            completed: Some(true),
+            ..Default::default()
+        };
+        assert!(update.validate().is_ok());
+        assert_eq!(
+            serde_json::to_string(&update).unwrap(),
+            r#"{"completed":true}"#
+        );
+
+        let update: UpdateTask = serde_json::from_str(r#"{"title":""}"#).unwrap();
+        assert_eq!(update.validate(), Err(Error::TitleEmpty));
+
+        let update: UpdateTask = serde_json::from_str(r#"{"priority":10}"#).unwrap();
+        assert_eq!(update.validate(), Err(Error::PriorityOutOfRange(10)));
+    }
+
+    #[test]
+    fn test_update_task_clears_fields() {
+        let update: UpdateTask =
+            serde_json::from_str(r#"{"due":null,"priority":null,"remind_at":null}"#).unwrap();
+        assert_eq!(update.due, Some(None));
+        assert_eq!(update.priority, Some(None));
+        assert_eq!(update.remind_at, Some(None));
+        assert_eq!(
+            serde_json::to_string(&update).unwrap(),
+            r#"{"due":null,"priority":null,"remind_at":null}"#
+        );
+        let update = update.sanitize().unwrap();
+
+        let json = r#"{"id":"tasks:abc","title":"test","completed":false,"created_at":"2023-04-02T10:00:00Z","due":"2100-01-01T00:00:00Z","priority":1}"#;
+        let task: Task = serde_json::from_str(json).unwrap();
+        let cleared = update.apply(&task);
+        assert_eq!((cleared.due, cleared.priority), (None, None));
+        assert_eq!(UpdateTask::default().apply(&task), task);
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let new_task = NewTask::new(" call\nmom ").sanitize().unwrap();
+        assert_eq!(new_task.title, "call mom");
+
+        let update: UpdateTask =
+            serde_json::from_str(r#"{"title":" ","due":"1970-01-01T00:00:00Z","completed":true,"remind_at":"2100-01-01T00:00:00Z"}"#)
+                .unwrap();
+        let errors = update.sanitize().unwrap_err();
+        let fields: Vec<&str> = errors.errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["title", "due", "remind_at"]);
+    }
+
+    #[test]
+    fn test_assignment() {
+        let assignment = Assignment {
+            assignee: " user:0f3a ".to_string(),
+        };
+        assert_eq!(assignment.sanitize().unwrap().assignee, "user:0f3a");
+
+        for assignee in ["anonymous", "user:", "user:a b", "0f3a"] {
+            let errors = Assignment {
+                assignee: assignee.to_string(),
+            }
+            .sanitize()
+            .unwrap_err();
+            assert_eq!(errors.errors[0].field, "assignee");
+        }
+    }
+}
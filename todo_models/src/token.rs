@@ -0,0 +1,155 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{datetime, validation::TextRule, Error, ValidationErrors};
+
+/// Maximum number of characters in a token name.
+pub const TOKEN_NAME_MAX_LEN: usize = 100;
+
+/// What a personal access token may do. Each scope may do everything the
+/// ones before it may.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Reads tasks and everything about them.
+    Read,
+    /// Also creates, changes and deletes them.
+    Write,
+    /// Also manages tokens, webhooks and the workflow.
+    Admin,
+}
+
+impl Scope {
+    /// Whether a token with this scope may do what needs `scope`.
+    pub fn allows(self, scope: Scope) -> bool {
+        self >= scope
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Admin => "admin",
+        })
+    }
+}
+
+/// A personal access token, as listed. The token itself is only shown
+/// once, when it is issued; the API keeps just a hash of it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    /// The user the token acts as, such as `user:<key>`.
+    pub user: String,
+    pub scopes: Vec<Scope>,
+    /// The start of the token, to tell tokens apart.
+    pub prefix: String,
+    #[serde(serialize_with = "datetime::serialize")]
+    pub created_at: DateTime<Utc>,
+    /// Roughly when the token was last used, to the minute.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "datetime::serialize_option"
+    )]
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl ApiToken {
+    /// Whether any of the token's scopes allows `scope`.
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.iter().any(|has| has.allows(scope))
+    }
+}
+
+/// Input for issuing a token to the caller.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewApiToken {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+impl NewApiToken {
+    /// Cleans the name like a title, and sorts the scopes, dropping
+    /// repeats.
+    pub fn sanitize(self) -> Result<NewApiToken, ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        let name = errors.check(token_name(&self.name));
+        let mut scopes = self.scopes;
+        scopes.sort();
+        scopes.dedup();
+        let scopes = errors.check(match scopes.is_empty() {
+            true => Err(Error::TokenScopesEmpty),
+            false => Ok(scopes),
+        });
+        let token = match (name, scopes) {
+            (Some(name), Some(scopes)) => Some(NewApiToken { name, scopes }),
+            _ => None,
+        };
+        errors.into_result(token)
+    }
+}
+
+fn token_name(name: &str) -> Result<String, Error> {
+    const RULE: TextRule = TextRule {
+        required: true,
+        max_len: TOKEN_NAME_MAX_LEN,
+    };
+    RULE.apply(name).map_err(|e| match e {
+        Error::TitleEmpty => Error::TokenNameEmpty,
+        _ => Error::TokenNameTooLong(RULE.max_len),
+    })
+}
+
+/// A newly issued token, with the secret to send as `Authorization: Bearer
+/// <token>`. It cannot be shown again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IssuedToken {
+    pub token: String,
+    #[serde(flatten)]
+    pub api_token: ApiToken,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scopes() {
+        assert!(Scope::Admin.allows(Scope::Write));
+        assert!(!Scope::Read.allows(Scope::Write));
+        assert_eq!(serde_json::to_string(&Scope::Admin).unwrap(), r#""admin""#);
+
+        let json = r#"{"id":"api_tokens:1","name":"ci","user":"user:0f3a","scopes":["read"],"prefix":"todo_pat_ab","created_at":"2023-04-02T10:00:00Z"}"#;
+        let token: ApiToken = serde_json::from_str(json).unwrap();
+        assert!(token.allows(Scope::Read));
+        assert!(!token.allows(Scope::Write));
+        assert_eq!(token.last_used_at, None);
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let token = NewApiToken {
+            name: " CI ".to_string(),
+            scopes: vec![Scope::Write, Scope::Read, Scope::Write],
+        }
+        .sanitize()
+        .unwrap();
+        assert_eq!(token.name, "CI");
+        assert_eq!(token.scopes, [Scope::Read, Scope::Write]);
+
+        let errors = NewApiToken {
+            name: String::new(),
+            scopes: vec![],
+        }
+        .sanitize()
+        .unwrap_err();
+        let fields: Vec<&str> = errors.errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["name", "scopes"]);
+    }
+}
@@ -0,0 +1,178 @@
+//! Rules for task input, applied by the API to every create and update, and
+//! by the web app before anything is sent.
+
+use chrono::{DateTime, Datelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, PRIORITY_RANGE, TITLE_MAX_LEN};
+
+/// How a text field is cleaned and checked. Control characters are dropped,
+/// except for tabs and line breaks which become spaces, and the result is
+/// trimmed before its length is checked.
+pub struct TextRule {
+    pub required: bool,
+    /// In characters.
+    pub max_len: usize,
+}
+
+/// Accepted range for a date field, by year.
+pub struct DateRule {
+    pub min_year: i32,
+    pub max_year: i32,
+}
+
+pub const TITLE: TextRule = TextRule {
+    required: true,
+    max_len: TITLE_MAX_LEN,
+};
+
+pub const DUE: DateRule = DateRule {
+    min_year: 2000,
+    max_year: 2099,
+};
+
+impl TextRule {
+    /// Returns the cleaned text.
+    pub fn apply(&self, text: &str) -> Result<String, Error> {
+        let cleaned: String = text
+            .chars()
+            .filter_map(|c| match c {
+                '\t' | '\n' | '\r' => Some(' '),
+                c if c.is_control() => None,
+                c => Some(c),
+            })
+            .collect();
+        let cleaned = cleaned.trim();
+
+        if self.required && cleaned.is_empty() {
+            return Err(Error::TitleEmpty);
+        }
+        if cleaned.chars().count() > self.max_len {
+            return Err(Error::TitleTooLong(self.max_len));
+        }
+        Ok(cleaned.to_string())
+    }
+}
+
+impl DateRule {
+    pub fn apply(&self, date: DateTime<Utc>) -> Result<DateTime<Utc>, Error> {
+        match (self.min_year..=self.max_year).contains(&date.year()) {
+            true => Ok(date),
+            false => Err(Error::DueOutOfRange(self.min_year, self.max_year)),
+        }
+    }
+}
+
+/// Reminders fall in the same years as due dates.
+pub fn reminder(at: DateTime<Utc>) -> Result<DateTime<Utc>, Error> {
+    DUE.apply(at)
+        .map_err(|_| Error::ReminderOutOfRange(DUE.min_year, DUE.max_year))
+}
+
+pub fn priority(priority: u8) -> Result<u8, Error> {
+    match PRIORITY_RANGE.contains(&priority) {
+        true => Ok(priority),
+        false => Err(Error::PriorityOutOfRange(priority)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl From<Error> for FieldError {
+    fn from(error: Error) -> Self {
+        FieldError {
+            field: error.field().to_string(),
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Every field that failed validation, as sent in `422` responses.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationErrors {
+    pub errors: Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    /// Records the error of `result`, if any, and passes on its value.
+    pub fn check<T>(&mut self, result: Result<T, Error>) -> Option<T> {
+        result.map_err(|e| self.errors.push(e.into())).ok()
+    }
+
+    /// Message for `field`, for showing next to its input.
+    pub fn message(&self, field: &str) -> Option<&str> {
+        self.errors
+            .iter()
+            .find(|e| e.field == field)
+            .map(|e| e.message.as_str())
+    }
+
+    pub fn into_result<T>(self, value: Option<T>) -> Result<T, ValidationErrors> {
+        match (self.errors.is_empty(), value) {
+            (true, Some(value)) => Ok(value),
+            _ => Err(self),
+        }
+    }
+}
+
+impl From<Error> for ValidationErrors {
+    fn from(error: Error) -> Self {
+        ValidationErrors {
+            errors: vec![error.into()],
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let messages: Vec<&str> = self.errors.iter().map(|e| e.message.as_str()).collect();
+        write!(f, "{}", messages.join("; "))
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_title_is_cleaned() {
+        assert_eq!(TITLE.apply("  buy\tmilk\u{0}\n").unwrap(), "buy milk");
+        assert_eq!(TITLE.apply("\u{7}\u{1b} "), Err(Error::TitleEmpty));
+        assert_eq!(
+            TITLE.apply(&"é".repeat(TITLE_MAX_LEN + 1)),
+            Err(Error::TitleTooLong(TITLE_MAX_LEN))
+        );
+        assert!(TITLE.apply(&"é".repeat(TITLE_MAX_LEN)).is_ok());
+    }
+
+    #[test]
+    fn test_due_range() {
+        let date = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        assert!(DUE.apply(date("2099-12-31T23:59:59Z")).is_ok());
+        assert_eq!(
+            DUE.apply(date("1999-12-31T00:00:00Z")),
+            Err(Error::DueOutOfRange(2000, 2099))
+        );
+    }
+
+    #[test]
+    fn test_errors_are_collected_per_field() {
+        let mut errors = ValidationErrors::default();
+        assert_eq!(errors.check(TITLE.apply("")), None);
+        assert_eq!(errors.check(priority(0)), None);
+        assert_eq!(errors.check(priority(3)), Some(3));
+
+        assert_eq!(errors.message("title"), Some("title must not be empty"));
+        assert_eq!(
+            errors.message("priority"),
+            Some("priority must be between 1 and 9, got 0")
+        );
+        assert_eq!(errors.into_result(Some(())).unwrap_err().errors.len(), 2);
+    }
+}
@@ -0,0 +1,298 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{datetime, Error, FieldChange, Task, TaskAction, ValidationErrors};
+
+/// Shortest secret accepted for signing payloads.
+pub const WEBHOOK_SECRET_MIN_LEN: usize = 16;
+
+/// Longest URL accepted for a webhook.
+pub const WEBHOOK_URL_MAX_LEN: usize = 2048;
+
+/// A subscription to task changes. Its secret is never sent back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    /// The changes that are delivered.
+    pub events: Vec<TaskAction>,
+    /// Id of the [`Workspace`](crate::Workspace) whose tasks' changes are
+    /// delivered. Webhooks without one get changes to tasks outside any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<String>,
+    #[serde(serialize_with = "datetime::serialize")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// Input for subscribing a URL to task changes. Payloads are signed with
+/// `secret`; see [`WebhookPayload`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewWebhook {
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<TaskAction>,
+}
+
+impl NewWebhook {
+    /// Checks every field, dropping repeated events.
+    pub fn sanitize(self) -> Result<NewWebhook, ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        let url = errors.check(webhook_url(self.url.trim()));
+        let secret = errors.check(match self.secret.chars().count() {
+            n if n < WEBHOOK_SECRET_MIN_LEN => {
+                Err(Error::WebhookSecretTooShort(WEBHOOK_SECRET_MIN_LEN))
+            }
+            _ => Ok(self.secret),
+        });
+        let mut events: Vec<TaskAction> = vec![];
+        for event in self.events {
+            if !events.contains(&event) {
+                events.push(event);
+            }
+        }
+        let events = errors.check(match events.is_empty() {
+            true => Err(Error::WebhookEventsEmpty),
+            false => Ok(events),
+        });
+        let webhook = match (url, secret, events) {
+            (Some(url), Some(secret), Some(events)) => Some(NewWebhook {
+                url,
+                secret,
+                events,
+            }),
+            _ => None,
+        };
+        errors.into_result(webhook)
+    }
+}
+
+/// Only absolute `http` and `https` URLs with a public host are accepted,
+/// so webhooks can't be pointed at the server's own network.
+fn webhook_url(url: &str) -> Result<String, Error> {
+    let invalid = || Error::WebhookUrlInvalid(url.to_string());
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .ok_or_else(invalid)?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority.rsplit('@').next().unwrap_or_default();
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    if host.is_empty()
+        || url.len() > WEBHOOK_URL_MAX_LEN
+        || url.chars().any(|c| c.is_whitespace() || c.is_control())
+    {
+        return Err(invalid());
+    }
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    let public = match host.parse::<IpAddr>() {
+        Ok(ip) => is_public_ip(ip),
+        // URL parsers read hosts ending in a number, like `2130706433` or
+        // `127.1`, as IPv4 addresses in shorthand.
+        Err(_) if host.rsplit('.').next().is_some_and(is_numeric_label) => return Err(invalid()),
+        Err(_) => host != "localhost" && !host.ends_with(".localhost"),
+    };
+    match public {
+        true => Ok(url.to_string()),
+        false => Err(Error::WebhookHostNotPublic(host)),
+    }
+}
+
+fn is_numeric_label(label: &str) -> bool {
+    let digits = label
+        .strip_prefix("0x")
+        .or_else(|| label.strip_prefix("0X"))
+        .unwrap_or(label);
+    !label.is_empty()
+        && digits.chars().all(|c| c.is_ascii_hexdigit())
+        && label.chars().next().is_some_and(|c| c.is_ascii_digit())
+}
+
+/// Whether `ip` is reachable on the public internet, rather than being a
+/// loopback, private, link-local or otherwise reserved address. The API also
+/// checks the addresses webhook hosts resolve to with this.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // "This network", shared address space for carrier NAT, and
+        // reserved for future use.
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local, link-local and documentation addresses.
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+        || first == 0x2001 && ip.segments()[1] == 0x0db8)
+}
+
+/// The JSON body posted for a change. It is signed with the webhook's
+/// secret as HMAC-SHA256, sent hex-encoded in the `X-Todo-Signature`
+/// header as `sha256=...`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    pub event: TaskAction,
+    pub actor: String,
+    #[serde(serialize_with = "datetime::serialize")]
+    pub at: DateTime<Utc>,
+    /// The task after the change, or as it was before being deleted.
+    pub task: Task,
+    #[serde(default)]
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Not sent yet, or waiting to be retried.
+    Pending,
+    Delivered,
+    /// Given up on after the last retry.
+    Failed,
+}
+
+/// One payload sent, or to be sent, to a webhook, with the outcome of its
+/// last attempt.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub webhook: String,
+    pub event: TaskAction,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    /// The HTTP status of the last response, if there was one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_status: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(serialize_with = "datetime::serialize")]
+    pub created_at: DateTime<Utc>,
+    /// When a pending delivery is tried next.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "datetime::serialize_option"
+    )]
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "datetime::serialize_option"
+    )]
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_webhook(url: &str, secret: &str, events: Vec<TaskAction>) -> NewWebhook {
+        NewWebhook {
+            url: url.to_string(),
+            secret: secret.to_string(),
+            events,
+        }
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let webhook = new_webhook(
+            " https://chat.example.com/hooks/todo ",
+            "0123456789abcdef",
+            vec![
+                TaskAction::Created,
+                TaskAction::Deleted,
+                TaskAction::Created,
+            ],
+        )
+        .sanitize()
+        .unwrap();
+        assert_eq!(webhook.url, "https://chat.example.com/hooks/todo");
+        assert_eq!(webhook.events, [TaskAction::Created, TaskAction::Deleted]);
+
+        let errors = new_webhook("ftp://example.com", "short", vec![])
+            .sanitize()
+            .unwrap_err();
+        let fields: Vec<&str> = errors.errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["url", "secret", "events"]);
+
+        for url in [
+            "http://",
+            "https:///path",
+            "http://exa mple.com",
+            "http://2130706433/",
+            "http://127.1/",
+            "http://0x7f.0.0.1/",
+        ] {
+            assert_eq!(
+                webhook_url(url),
+                Err(Error::WebhookUrlInvalid(url.to_string()))
+            );
+        }
+        assert!(webhook_url("https://8.8.8.8:8080?a=1").is_ok());
+        assert!(webhook_url("https://[2606:4700::1111]/hook").is_ok());
+        assert!(webhook_url("https://user@hooks.example.com:8443/todo").is_ok());
+    }
+
+    #[test]
+    fn test_webhook_url_must_be_public() {
+        for url in [
+            "http://127.0.0.1:8080?a=1",
+            "http://localhost/hook",
+            "http://LOCALHOST./hook",
+            "http://api.localhost/hook",
+            "http://10.0.0.1/",
+            "http://172.16.5.4/",
+            "http://192.168.1.1/",
+            "http://169.254.169.254/latest/meta-data",
+            "http://100.64.0.1/",
+            "http://0.0.0.0/",
+            "http://[::1]/",
+            "http://[::]/",
+            "http://[fd00::1]/",
+            "http://[fe80::1]/",
+            "http://[::ffff:127.0.0.1]/",
+            "http://hooks.example.com@127.0.0.1/",
+        ] {
+            assert!(
+                matches!(webhook_url(url), Err(Error::WebhookHostNotPublic(_))),
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_delivery_json() {
+        let json = r#"{"id":"webhook_deliveries:1","webhook":"webhooks:1","event":"toggled","status":"pending","attempts":0,"created_at":"2023-04-02T10:00:00Z","next_attempt_at":"2023-04-02T10:00:00Z"}"#;
+        let delivery: WebhookDelivery = serde_json::from_str(json).unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Pending);
+        assert_eq!(delivery.response_status, None);
+        assert_eq!(serde_json::to_string(&delivery).unwrap(), json);
+    }
+}
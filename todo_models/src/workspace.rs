@@ -0,0 +1,146 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{datetime, validation::TextRule, Error, ValidationErrors};
+
+/// Maximum number of characters in a workspace name.
+pub const WORKSPACE_NAME_MAX_LEN: usize = 100;
+
+/// What a member may do in a workspace. Each role may do everything the
+/// ones before it may.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Reads the workspace's tasks, with their comments, history and
+    /// attachments.
+    Viewer,
+    /// Also creates, changes and deletes tasks, and comments on them.
+    Editor,
+    /// Also invites members and changes their roles.
+    Owner,
+}
+
+impl Role {
+    /// Whether a member with this role may do what needs `role`.
+    pub fn allows(self, role: Role) -> bool {
+        self >= role
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Owner => "owner",
+        })
+    }
+}
+
+/// A list of tasks shared by its members.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Workspace {
+    pub id: String,
+    pub name: String,
+    #[serde(serialize_with = "datetime::serialize")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// Input for creating a workspace. Its creator becomes its first owner.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewWorkspace {
+    pub name: String,
+}
+
+impl NewWorkspace {
+    pub fn sanitize(self) -> Result<NewWorkspace, ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        let name = errors.check(workspace_name(&self.name));
+        errors.into_result(name.map(|name| NewWorkspace { name }))
+    }
+}
+
+/// Workspace names are cleaned like titles.
+fn workspace_name(name: &str) -> Result<String, Error> {
+    const RULE: TextRule = TextRule {
+        required: true,
+        max_len: WORKSPACE_NAME_MAX_LEN,
+    };
+    RULE.apply(name).map_err(|e| match e {
+        Error::TitleEmpty => Error::WorkspaceNameEmpty,
+        _ => Error::WorkspaceNameTooLong(RULE.max_len),
+    })
+}
+
+/// A user's membership of a workspace. Users are the actors recorded in the
+/// task history, such as `user:<key>` for callers with a bearer token.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Member {
+    pub id: String,
+    pub workspace: String,
+    pub user: String,
+    pub role: Role,
+    #[serde(serialize_with = "datetime::serialize")]
+    pub joined_at: DateTime<Utc>,
+}
+
+/// Input for changing a member's role, or the role an invitation grants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SetRole {
+    pub role: Role,
+}
+
+/// A single-use token that makes whoever accepts it a member of the
+/// workspace, with the role given.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Invitation {
+    pub token: String,
+    pub workspace: String,
+    pub role: Role,
+    #[serde(serialize_with = "datetime::serialize")]
+    pub expires_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roles() {
+        assert!(Role::Owner.allows(Role::Editor));
+        assert!(Role::Editor.allows(Role::Editor));
+        assert!(!Role::Viewer.allows(Role::Editor));
+        assert_eq!(serde_json::to_string(&Role::Viewer).unwrap(), r#""viewer""#);
+        assert_eq!(Role::Owner.to_string(), "owner");
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let workspace = NewWorkspace {
+            name: " Team\tlist ".to_string(),
+        };
+        assert_eq!(workspace.sanitize().unwrap().name, "Team list");
+
+        let errors = NewWorkspace {
+            name: "\n".to_string(),
+        }
+        .sanitize()
+        .unwrap_err();
+        assert_eq!(
+            errors.message("name"),
+            Some("workspace names must not be empty")
+        );
+
+        let errors = NewWorkspace {
+            name: "x".repeat(WORKSPACE_NAME_MAX_LEN + 1),
+        }
+        .sanitize()
+        .unwrap_err();
+        assert_eq!(
+            errors.message("name"),
+            Some("workspace names must be at most 100 characters")
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+
+use crate::{models::Task, todo_api};
+
+#[derive(Properties, PartialEq)]
+pub struct AvatarProps {
+    /// A user, such as `user:<key>`.
+    pub user: String,
+}
+
+/// A round badge standing for a user, coloured and lettered by their key,
+/// which is all the API knows of them.
+#[function_component(Avatar)]
+pub fn avatar(AvatarProps { user }: &AvatarProps) -> Html {
+    let key = user.strip_prefix("user:").unwrap_or(user);
+    let letters: String = key.chars().take(2).collect::<String>().to_uppercase();
+    let hue = key
+        .bytes()
+        .fold(0u32, |hash, b| hash.wrapping_mul(31).wrapping_add(b.into()))
+        % 360;
+    let style = format!("background: hsl({hue}, 55%, 45%)");
+    html! {
+        <span class="avatar" title={user.clone()} {style}>{ letters }</span>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct AssigneeSelectProps {
+    pub task: Task,
+    /// Called with the user picked, or `None` to unassign.
+    pub on_assign: Callback<Option<String>>,
+}
+
+/// The task's assignee, with a select to change it. The users it can be
+/// assigned to are only fetched once the select is used.
+#[function_component(AssigneeSelect)]
+pub fn assignee_select(AssigneeSelectProps { task, on_assign }: &AssigneeSelectProps) -> Html {
+    let users = use_state(|| None::<Vec<String>>);
+
+    let on_focus = {
+        let users = users.clone();
+        let id = task.id.clone();
+        Callback::from(move |_| {
+            if users.is_some() {
+                return;
+            }
+            let (users, id) = (users.clone(), id.clone());
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Ok(fetched) = todo_api::fetch_assignees(id).await {
+                    users.set(Some(fetched));
+                }
+            });
+        })
+    };
+
+    let on_change = {
+        let on_assign = on_assign.clone();
+        Callback::from(move |event: Event| {
+            let select: HtmlSelectElement = event.target_unchecked_into();
+            let value = select.value();
+            on_assign.emit((!value.is_empty()).then_some(value));
+        })
+    };
+
+    // The current assignee is listed before the others load.
+    let mut options: Vec<String> = task.assignee.iter().cloned().collect();
+    for user in users.iter().flatten() {
+        if !options.contains(user) {
+            options.push(user.clone());
+        }
+    }
+
+    html! {
+        <span class="assignee">
+            if let Some(user) = &task.assignee {
+                <Avatar user={user.clone()} />
+            }
+            <select onfocus={on_focus} onchange={on_change} aria-label="Assignee">
+                <option value="" selected={task.assignee.is_none()}>{ "Unassigned" }</option>
+                { for options.iter().map(|user| html! {
+                    <option
+                        value={user.clone()}
+                        selected={task.assignee.as_ref() == Some(user)}
+                    >
+                        { user }
+                    </option>
+                }) }
+            </select>
+        </span>
+    }
+}
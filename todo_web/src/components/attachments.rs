@@ -0,0 +1,141 @@
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{File, HtmlInputElement};
+use yew::prelude::*;
+
+use crate::{models::Attachment, todo_api};
+
+#[derive(Properties, PartialEq)]
+pub struct AttachmentsProps {
+    pub task_id: String,
+}
+
+/// The files attached to a task, with a picker to attach more. Files open
+/// from the API, which serves them with the type they were uploaded as.
+#[function_component(Attachments)]
+pub fn attachments(AttachmentsProps { task_id }: &AttachmentsProps) -> Html {
+    let attachments = use_state(Vec::<Attachment>::new);
+    let error = use_state(|| None::<String>);
+
+    {
+        let attachments = attachments.clone();
+        let error = error.clone();
+        use_effect_with_deps(
+            move |task_id: &String| {
+                let task_id = task_id.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    match todo_api::fetch_attachments(task_id).await {
+                        Ok(fetched) => attachments.set(fetched),
+                        Err(e) => error.set(Some(e.to_string())),
+                    }
+                });
+                || ()
+            },
+            task_id.clone(),
+        );
+    }
+
+    let on_pick = {
+        let task_id = task_id.clone();
+        let attachments = attachments.clone();
+        let error = error.clone();
+        Callback::from(move |event: Event| {
+            let input: HtmlInputElement = event.target_unchecked_into();
+            let Some(file) = input.files().and_then(|files| files.get(0)) else {
+                return;
+            };
+            input.set_value("");
+            let task_id = task_id.clone();
+            let attachments = attachments.clone();
+            let error = error.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match upload(task_id, file).await {
+                    Ok(attachment) => {
+                        let mut list = (*attachments).clone();
+                        list.push(attachment);
+                        attachments.set(list);
+                        error.set(None);
+                    }
+                    Err(message) => error.set(Some(message)),
+                }
+            });
+        })
+    };
+
+    let on_delete = |id: String| {
+        let attachments = attachments.clone();
+        let error = error.clone();
+        Callback::from(move |_| {
+            let id = id.clone();
+            let attachments = attachments.clone();
+            let error = error.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match todo_api::delete_attachment(id.clone()).await {
+                    Ok(_) => {
+                        let list = attachments
+                            .iter()
+                            .filter(|attachment| attachment.id != id)
+                            .cloned()
+                            .collect();
+                        attachments.set(list);
+                        error.set(None);
+                    }
+                    Err(e) => error.set(Some(e.to_string())),
+                }
+            });
+        })
+    };
+
+    html! {
+        <div class="attachments">
+            if !attachments.is_empty() {
+                <ul>
+                    { for attachments.iter().map(|attachment| html! {
+                        <li key={attachment.id.clone()}>
+                            <a href={todo_api::attachment_url(&attachment.id)} target="_blank">
+                                { &attachment.name }
+                            </a>
+                            <span class="size">{ size(attachment.size) }</span>
+                            <button
+                                onclick={on_delete(attachment.id.clone())}
+                                title={format!("Remove {}", attachment.name)}
+                            >
+                                { "×" }
+                            </button>
+                        </li>
+                    }) }
+                </ul>
+            }
+            <label class="attach">
+                { "Attach file" }
+                <input type="file" onchange={on_pick} />
+            </label>
+            if let Some(message) = &*error {
+                <p class="error">{ message }</p>
+            }
+        </div>
+    }
+}
+
+/// Reads a picked file and uploads it. Files the browser cannot type are
+/// sent untyped, and the API guesses from the name.
+async fn upload(task_id: String, file: File) -> Result<Attachment, String> {
+    let buffer = JsFuture::from(file.array_buffer())
+        .await
+        .map_err(|_| format!("could not read {}", file.name()))?;
+    let bytes = js_sys::Uint8Array::new(&buffer).to_vec();
+    let content_type = match file.type_() {
+        content_type if content_type.is_empty() => "application/octet-stream".to_string(),
+        content_type => content_type,
+    };
+    todo_api::add_attachment(task_id, file.name(), content_type, bytes)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+fn size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{bytes} B"),
+        1024..=1_048_575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
@@ -0,0 +1,98 @@
+use web_sys::DragEvent;
+use yew::{
+    classes, function_component, html, use_state, Callback, Html, Properties, UseStateHandle,
+};
+
+use crate::models::{Status, Task, Workflow};
+
+#[derive(Properties, PartialEq)]
+pub struct BoardProps {
+    pub tasks: Vec<Task>,
+    pub workflow: Workflow,
+    /// A task was dragged to the column of another status.
+    pub on_set_status: Callback<(String, String)>,
+}
+
+/// The tasks as a Kanban board, with a column per status of the workflow.
+#[function_component(Board)]
+pub fn board(
+    BoardProps {
+        tasks,
+        workflow,
+        on_set_status,
+    }: &BoardProps,
+) -> Html {
+    let dragged = use_state(|| None::<String>);
+
+    let column = |status: &Status| {
+        let cards: Vec<&Task> = tasks
+            .iter()
+            .filter(|task| workflow.status_of(task).id == status.id)
+            .collect();
+
+        let on_drop = {
+            let dragged = dragged.clone();
+            let status = status.id.clone();
+            let on_set_status = on_set_status.clone();
+            move |event: DragEvent| {
+                event.prevent_default();
+                if let Some(id) = &*dragged {
+                    on_set_status.emit((id.clone(), status.clone()));
+                }
+            }
+        };
+
+        html! {
+            <section
+                class="column"
+                ondragover={|event: DragEvent| event.prevent_default()}
+                ondrop={on_drop}
+            >
+                <h4>
+                    { &status.name }
+                    <span class="count">{ cards.len() }</span>
+                </h4>
+                <ul>
+                    { for cards.into_iter().map(|task| card(task, &dragged)) }
+                </ul>
+            </section>
+        }
+    };
+
+    html! {
+        <div class="board">
+            { for workflow.statuses.iter().map(column) }
+        </div>
+    }
+}
+
+fn card(task: &Task, dragged: &UseStateHandle<Option<String>>) -> Html {
+    let on_drag_start = {
+        let id = task.id.clone();
+        let dragged = dragged.clone();
+        move |event: DragEvent| {
+            // Firefox only starts a drag that carries data.
+            if let Some(data) = event.data_transfer() {
+                let _ = data.set_data("text/plain", &id);
+            }
+            dragged.set(Some(id.clone()))
+        }
+    };
+
+    let on_drag_end = {
+        let dragged = dragged.clone();
+        move |_| dragged.set(None)
+    };
+
+    let dragging = dragged.as_deref() == Some(task.id.as_str());
+    html! {
+        <li
+            class={classes!("card", task.completed.then_some("completed"), dragging.then_some("dragging"))}
+            draggable="true"
+            ondragstart={on_drag_start}
+            ondragend={on_drag_end}
+        >
+            { &task.title }
+        </li>
+    }
+}
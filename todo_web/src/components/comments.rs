@@ -0,0 +1,295 @@
+use std::future::Future;
+
+use todo_client::Error;
+use web_sys::HtmlTextAreaElement;
+use yew::prelude::*;
+
+use crate::{
+    models::{replies, Comment, EditComment, NewComment},
+    todo_api,
+};
+
+#[derive(Properties, PartialEq)]
+pub struct CommentsProps {
+    pub task_id: String,
+}
+
+/// The discussion on a task, as threads of replies. The list is reloaded
+/// after every change, so deleted comments with replies show up the way the
+/// API keeps them.
+#[function_component(Comments)]
+pub fn comments(CommentsProps { task_id }: &CommentsProps) -> Html {
+    let comments = use_state(|| None::<Result<Vec<Comment>, String>>);
+    let reloads = use_state(|| 0_u32);
+    let replying = use_state(|| None::<String>);
+    let editing = use_state(|| None::<String>);
+    let error = use_state(|| None::<String>);
+
+    {
+        let comments = comments.clone();
+        use_effect_with_deps(
+            move |(task_id, _): &(String, u32)| {
+                let task_id = task_id.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let fetched = todo_api::fetch_comments(task_id).await;
+                    comments.set(Some(fetched.map_err(|e| e.to_string())));
+                });
+                || ()
+            },
+            (task_id.clone(), *reloads),
+        );
+    }
+
+    let after = After {
+        reloads: reloads.clone(),
+        replying: replying.clone(),
+        editing: editing.clone(),
+        error: error.clone(),
+    };
+
+    let on_add = {
+        let after = after.clone();
+        let task_id = task_id.clone();
+        Callback::from(move |(parent, body): (Option<String>, String)| {
+            let comment = NewComment { body, parent };
+            after.run(todo_api::add_comment(task_id.clone(), comment));
+        })
+    };
+
+    let on_save = {
+        let after = after.clone();
+        Callback::from(move |(id, body): (String, String)| {
+            after.run(todo_api::edit_comment(id, EditComment { body }));
+        })
+    };
+
+    let on_delete = Callback::from(move |id: String| {
+        after.run(todo_api::delete_comment(id));
+    });
+
+    let comments = match &*comments {
+        None => return html! { <p class="comments">{ "Loading…" }</p> },
+        Some(Err(message)) => return html! { <p class="comments error">{ message }</p> },
+        Some(Ok(comments)) => comments,
+    };
+
+    let new_comment = {
+        let on_add = on_add.clone();
+        Callback::from(move |body| on_add.emit((None, body)))
+    };
+
+    let thread = Thread {
+        comments,
+        replying: replying.as_deref(),
+        editing: editing.as_deref(),
+        on_reply: {
+            let replying = replying.clone();
+            let editing = editing.clone();
+            Callback::from(move |id| {
+                editing.set(None);
+                replying.set(id);
+            })
+        },
+        on_edit: {
+            let replying = replying.clone();
+            let editing = editing.clone();
+            Callback::from(move |id| {
+                replying.set(None);
+                editing.set(id);
+            })
+        },
+        on_add,
+        on_save,
+        on_delete,
+    };
+
+    html! {
+        <section class="comments">
+            if comments.is_empty() {
+                <p class="empty">{ "No comments yet." }</p>
+            }
+            { thread.list(None) }
+            if let Some(message) = &*error {
+                <p class="error">{ message }</p>
+            }
+            <CommentForm label="Comment" on_submit={new_comment} />
+        </section>
+    }
+}
+
+/// What happens once a change to the thread has been sent.
+#[derive(Clone)]
+struct After {
+    reloads: UseStateHandle<u32>,
+    replying: UseStateHandle<Option<String>>,
+    editing: UseStateHandle<Option<String>>,
+    error: UseStateHandle<Option<String>>,
+}
+
+impl After {
+    /// Sends `request`, then closes any open form and reloads the thread, or
+    /// shows why it failed.
+    fn run<T>(&self, request: impl Future<Output = Result<T, Error>> + 'static) {
+        let after = self.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            match request.await {
+                Ok(_) => {
+                    after.replying.set(None);
+                    after.editing.set(None);
+                    after.error.set(None);
+                    after.reloads.set(*after.reloads + 1);
+                }
+                Err(e) => after.error.set(Some(e.to_string())),
+            }
+        });
+    }
+}
+
+/// Renders comments under their parents, with the reply or edit form open on
+/// at most one of them.
+struct Thread<'a> {
+    comments: &'a [Comment],
+    replying: Option<&'a str>,
+    editing: Option<&'a str>,
+    on_reply: Callback<Option<String>>,
+    on_edit: Callback<Option<String>>,
+    on_add: Callback<(Option<String>, String)>,
+    on_save: Callback<(String, String)>,
+    on_delete: Callback<String>,
+}
+
+impl Thread<'_> {
+    fn list(&self, parent: Option<&str>) -> Html {
+        let mut comments = replies(self.comments, parent).peekable();
+        if comments.peek().is_none() {
+            return html! {};
+        }
+        html! {
+            <ol class="thread">
+                { for comments.map(|comment| self.item(comment)) }
+            </ol>
+        }
+    }
+
+    fn item(&self, comment: &Comment) -> Html {
+        let id = comment.id.clone();
+        let show = |callback: &Callback<Option<String>>| {
+            let (callback, id) = (callback.clone(), id.clone());
+            Callback::from(move |_| callback.emit(Some(id.clone())))
+        };
+        let hide = |callback: &Callback<Option<String>>| {
+            let callback = callback.clone();
+            Callback::from(move |_: MouseEvent| callback.emit(None))
+        };
+
+        let body = if comment.deleted {
+            html! { <p class="deleted">{ "Comment deleted." }</p> }
+        } else if self.editing == Some(comment.id.as_str()) {
+            let (on_save, id) = (self.on_save.clone(), id.clone());
+            html! {
+                <CommentForm
+                    label="Save"
+                    value={comment.body.clone()}
+                    on_submit={Callback::from(move |body| on_save.emit((id.clone(), body)))}
+                    on_cancel={hide(&self.on_edit)}
+                />
+            }
+        } else {
+            let on_delete = {
+                let (on_delete, id) = (self.on_delete.clone(), id.clone());
+                Callback::from(move |_| on_delete.emit(id.clone()))
+            };
+            html! {
+                <>
+                <p class="body">{ &comment.body }</p>
+                <div class="actions">
+                    <button onclick={show(&self.on_reply)}>{ "Reply" }</button>
+                    <button onclick={show(&self.on_edit)}>{ "Edit" }</button>
+                    <button onclick={on_delete}>{ "Delete" }</button>
+                </div>
+                </>
+            }
+        };
+
+        let reply = {
+            let (on_add, id) = (self.on_add.clone(), id.clone());
+            Callback::from(move |body| on_add.emit((Some(id.clone()), body)))
+        };
+
+        const FORMAT: &str = "%Y-%m-%d %H:%M";
+        html! {
+            <li key={comment.id.clone()}>
+                <div class="meta">
+                    <span class="author">{ &comment.author }</span>
+                    <time datetime={comment.created_at.to_rfc3339()}>
+                        { comment.created_at.format(FORMAT).to_string() }
+                    </time>
+                    if comment.edited_at.is_some() && !comment.deleted {
+                        <span class="edited">{ "(edited)" }</span>
+                    }
+                </div>
+                { body }
+                if self.replying == Some(comment.id.as_str()) {
+                    <CommentForm
+                        label="Reply"
+                        on_submit={reply}
+                        on_cancel={hide(&self.on_reply)}
+                    />
+                }
+                { self.list(Some(comment.id.as_str())) }
+            </li>
+        }
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct CommentFormProps {
+    pub label: AttrValue,
+    #[prop_or_default]
+    pub value: String,
+    pub on_submit: Callback<String>,
+    #[prop_or_default]
+    pub on_cancel: Option<Callback<MouseEvent>>,
+}
+
+/// A text box for writing or editing a comment.
+#[function_component(CommentForm)]
+pub fn comment_form(props: &CommentFormProps) -> Html {
+    let input_ref = use_node_ref();
+    let error = use_state(|| None::<String>);
+
+    let on_click = {
+        let input_ref = input_ref.clone();
+        let on_submit = props.on_submit.clone();
+        let error = error.clone();
+        Callback::from(move |_| {
+            if let Some(input) = input_ref.cast::<HtmlTextAreaElement>() {
+                // Same rules as the API, so the comment sent is the one stored.
+                let edit = EditComment {
+                    body: input.value(),
+                };
+                match edit.sanitize() {
+                    Ok(edit) => {
+                        on_submit.emit(edit.body);
+                        input.set_value("");
+                        error.set(None);
+                    }
+                    Err(errors) => error.set(errors.message("body").map(String::from)),
+                }
+            }
+        })
+    };
+
+    html! {
+        <div class="comment-form">
+            <textarea ref={input_ref} rows="3" value={props.value.clone()} />
+            <button onclick={on_click}>{ props.label.clone() }</button>
+            if let Some(on_cancel) = &props.on_cancel {
+                <button onclick={on_cancel.clone()}>{ "Cancel" }</button>
+            }
+            if let Some(message) = &*error {
+                <p class="error">{ message }</p>
+            }
+        </div>
+    }
+}
@@ -1,7 +1,23 @@
+mod assignee;
+mod attachments;
+mod board;
+mod comments;
+mod notifications;
+mod reminder;
 mod task_form;
+mod task_history;
 mod task_item;
 mod task_list;
+mod webhooks;
 
+pub use assignee::*;
+pub use attachments::*;
+pub use board::*;
+pub use comments::*;
+pub use notifications::*;
+pub use reminder::*;
 pub use task_form::*;
+pub use task_history::*;
 pub use task_item::*;
 pub use task_list::*;
+pub use webhooks::*;
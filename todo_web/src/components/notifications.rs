@@ -0,0 +1,109 @@
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::{models::Notification, routes::Route, todo_api};
+
+/// A bell with the number of unread reminders, opening a list of them.
+/// The list is fetched again each time it is opened.
+#[function_component(Notifications)]
+pub fn notifications() -> Html {
+    let notifications = use_state(Vec::<Notification>::new);
+    let open = use_state(|| false);
+    let reloads = use_state(|| 0_u32);
+    let error = use_state(|| None::<String>);
+
+    {
+        let notifications = notifications.clone();
+        let error = error.clone();
+        use_effect_with_deps(
+            move |_| {
+                wasm_bindgen_futures::spawn_local(async move {
+                    match todo_api::fetch_notifications().await {
+                        Ok(fetched) => {
+                            notifications.set(fetched);
+                            error.set(None);
+                        }
+                        Err(e) => error.set(Some(e.to_string())),
+                    }
+                });
+                || ()
+            },
+            *reloads,
+        );
+    }
+
+    let on_toggle = {
+        let open = open.clone();
+        Callback::from(move |_| {
+            if !*open {
+                reloads.set(*reloads + 1);
+            }
+            open.set(!*open);
+        })
+    };
+
+    let on_read = |id: String| {
+        let notifications = notifications.clone();
+        let error = error.clone();
+        Callback::from(move |_| {
+            let id = id.clone();
+            let notifications = notifications.clone();
+            let error = error.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match todo_api::read_notification(id).await {
+                    Ok(read) => {
+                        let list = notifications
+                            .iter()
+                            .map(|notification| match notification.id == read.id {
+                                true => read.clone(),
+                                false => notification.clone(),
+                            })
+                            .collect();
+                        notifications.set(list);
+                    }
+                    Err(e) => error.set(Some(e.to_string())),
+                }
+            });
+        })
+    };
+
+    let unread = notifications.iter().filter(|n| !n.read).count();
+    const FORMAT: &str = "%Y-%m-%d %H:%M";
+    html! {
+        <div class="notifications">
+            <button onclick={on_toggle} title="Reminders">
+                { "🔔" }
+                if unread > 0 {
+                    <span class="badge">{ unread }</span>
+                }
+            </button>
+            if *open {
+                <div class="panel">
+                    if notifications.is_empty() {
+                        <p class="empty">{ "No reminders." }</p>
+                    }
+                    <ul>
+                        { for notifications.iter().map(|notification| html! {
+                            <li key={notification.id.clone()} class={classes!(notification.read.then_some("read"))}>
+                                <Link<Route> to={Route::Task { id: notification.task.clone() }}>
+                                    { &notification.message }
+                                </Link<Route>>
+                                <time datetime={notification.created_at.to_rfc3339()}>
+                                    { notification.created_at.format(FORMAT).to_string() }
+                                </time>
+                                if !notification.read {
+                                    <button onclick={on_read(notification.id.clone())}>
+                                        { "Mark read" }
+                                    </button>
+                                }
+                            </li>
+                        }) }
+                    </ul>
+                    if let Some(message) = &*error {
+                        <p class="error">{ message }</p>
+                    }
+                </div>
+            }
+        </div>
+    }
+}
@@ -0,0 +1,78 @@
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use crate::{
+    models::{Task, UpdateTask},
+    todo_api,
+};
+
+#[derive(Properties, PartialEq)]
+pub struct ReminderFormProps {
+    pub task: Task,
+    /// Called with the task as saved.
+    pub on_change: Callback<Task>,
+}
+
+/// Sets when to be reminded of a task, in UTC like every time the app
+/// shows. The API clears the reminder once it is sent.
+#[function_component(ReminderForm)]
+pub fn reminder_form(ReminderFormProps { task, on_change }: &ReminderFormProps) -> Html {
+    let input_ref = use_node_ref();
+    let error = use_state(|| None::<String>);
+
+    let on_click = {
+        let input_ref = input_ref.clone();
+        let id = task.id.clone();
+        let on_change = on_change.clone();
+        let error = error.clone();
+        Callback::from(move |_| {
+            let Some(input) = input_ref.cast::<HtmlInputElement>() else {
+                return;
+            };
+            let Ok(at) = NaiveDateTime::parse_from_str(&input.value(), INPUT_FORMAT) else {
+                error.set(Some("pick a date and time".to_string()));
+                return;
+            };
+            let update = UpdateTask {
+                remind_at: Some(Some(Utc.from_utc_datetime(&at))),
+                ..Default::default()
+            };
+            // Same rules as the API, so only valid times are sent.
+            let update = match update.sanitize() {
+                Ok(update) => update,
+                Err(errors) => {
+                    error.set(errors.message("remind_at").map(String::from));
+                    return;
+                }
+            };
+            let (id, on_change, error) = (id.clone(), on_change.clone(), error.clone());
+            wasm_bindgen_futures::spawn_local(async move {
+                match todo_api::update_task(id, update).await {
+                    Ok(task) => {
+                        error.set(None);
+                        on_change.emit(task);
+                    }
+                    Err(e) => error.set(Some(e.to_string())),
+                }
+            });
+        })
+    };
+
+    let value = task
+        .remind_at
+        .map(|at| at.format(INPUT_FORMAT).to_string())
+        .unwrap_or_default();
+    html! {
+        <div class="reminder-form">
+            <input type="datetime-local" ref={input_ref} value={value} />
+            <button onclick={on_click}>{ "Set reminder" }</button>
+            if let Some(message) = &*error {
+                <p class="error">{ message }</p>
+            }
+        </div>
+    }
+}
+
+/// What `datetime-local` inputs read and write.
+const INPUT_FORMAT: &str = "%Y-%m-%dT%H:%M";
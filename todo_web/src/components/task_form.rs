@@ -1,5 +1,7 @@
 use web_sys::HtmlInputElement;
-use yew::{function_component, html, Html, use_node_ref, Callback, Properties};
+use yew::{function_component, html, use_node_ref, use_state, Callback, Html, Properties};
+
+use crate::models::NewTask;
 
 #[derive(Properties, PartialEq)]
 pub struct TaskFormProps {
@@ -9,32 +11,56 @@ pub struct TaskFormProps {
 #[function_component(TaskForm)]
 pub fn task_form(TaskFormProps { on_create_task }: &TaskFormProps) -> Html {
     let input_ref = use_node_ref();
+    let error = use_state(|| None::<String>);
 
     let on_click = {
         let input_node_ref = input_ref.clone();
         let on_create_task = on_create_task.clone();
+        let error = error.clone();
 
         Callback::from(move |_| {
             let input = input_node_ref.cast::<HtmlInputElement>();
 
             if let Some(input) = input {
-                on_create_task.emit(input.value());
-                input.set_value("");
+                // Same rules as the API, so the title sent is the one stored.
+                match NewTask::new(input.value()).sanitize() {
+                    Ok(new_task) => {
+                        on_create_task.emit(new_task.title);
+                        input.set_value("");
+                        error.set(None);
+                    }
+                    Err(errors) => error.set(errors.message("title").map(String::from)),
+                }
             }
         })
     };
 
+    let on_input = {
+        let error = error.clone();
+        Callback::from(move |_| error.set(None))
+    };
+
     html! {
         <div>
             <label for="new-task">
                 { "New Task" }
             </label>
             <div class="center">
-                <input ref={input_ref} id="new-task" type="text" />
+                <input
+                    ref={input_ref}
+                    id="new-task"
+                    type="text"
+                    oninput={on_input}
+                    aria-invalid={error.is_some().to_string()}
+                    aria-describedby="new-task-error"
+                />
                 <button onclick={on_click}>
                     { "Add" }
                 </button>
             </div>
+            if let Some(message) = &*error {
+                <p id="new-task-error" class="error">{ message }</p>
+            }
         </div>
     }
 }
@@ -0,0 +1,81 @@
+use yew::{function_component, html, use_effect_with_deps, use_state, Html, Properties};
+
+use crate::{
+    models::{EventAction, FieldChange, TaskEvent},
+    todo_api,
+};
+
+#[derive(Properties, PartialEq)]
+pub struct TaskHistoryProps {
+    pub task_id: String,
+}
+
+/// The changes made to a task, loaded when the panel is opened.
+#[function_component(TaskHistory)]
+pub fn task_history(TaskHistoryProps { task_id }: &TaskHistoryProps) -> Html {
+    let history = use_state(|| None::<Result<Vec<TaskEvent>, String>>);
+
+    {
+        let history = history.clone();
+        use_effect_with_deps(
+            move |task_id: &String| {
+                let task_id = task_id.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let fetched = todo_api::fetch_history(task_id).await;
+                    history.set(Some(fetched.map_err(|e| e.to_string())));
+                });
+                || ()
+            },
+            task_id.clone(),
+        );
+    }
+
+    let events = match &*history {
+        None => return html! { <p class="history">{ "Loading…" }</p> },
+        Some(Err(message)) => return html! { <p class="history error">{ message }</p> },
+        Some(Ok(events)) => events,
+    };
+
+    html! {
+        <ol class="history">
+            { for events.iter().map(event) }
+        </ol>
+    }
+}
+
+fn event(event: &TaskEvent) -> Html {
+    let action = action_name(event.action);
+
+    html! {
+        <li>
+            <time datetime={event.at.to_rfc3339()}>
+                { event.at.format("%Y-%m-%d %H:%M").to_string() }
+            </time>
+            { format!(" {} {action}", event.actor) }
+            if !event.changes.is_empty() {
+                <ul>
+                    { for event.changes.iter().map(change) }
+                </ul>
+            }
+        </li>
+    }
+}
+
+fn change(change: &FieldChange) -> Html {
+    let from = change.from.as_deref().unwrap_or("none");
+    let to = change.to.as_deref().unwrap_or("none");
+    html! {
+        <li>{ format!("{}: {from} → {to}", change.field) }</li>
+    }
+}
+
+/// How a change is named in the history and in webhook settings.
+pub fn action_name(action: EventAction) -> &'static str {
+    match action {
+        EventAction::Created => "created",
+        EventAction::Updated => "updated",
+        EventAction::Toggled => "toggled",
+        EventAction::Moved => "moved",
+        EventAction::Deleted => "deleted",
+    }
+}
@@ -1,12 +1,27 @@
-use yew::{classes, function_component, html, Html, Callback, Properties};
+use wasm_bindgen::JsCast;
+use web_sys::{DragEvent, Element};
+use yew::{classes, function_component, html, use_state, Callback, Html, Properties};
+use yew_router::prelude::Link;
 
-use crate::models::Task;
+use super::{AssigneeSelect, Attachments, TaskHistory};
+
+use crate::{
+    models::{MoveTask, Task},
+    routes::Route,
+};
 
 #[derive(Properties, PartialEq)]
 pub struct TaskItemProps {
     pub task: Task,
     pub on_toggle_task: Callback<String>,
     pub on_delete_task: Callback<String>,
+    pub on_assign_task: Callback<(String, Option<String>)>,
+    /// Whether this task is the one being dragged.
+    pub dragging: bool,
+    pub on_drag_start: Callback<String>,
+    pub on_drag_end: Callback<()>,
+    /// A dragged task was dropped here, to go before or after this task.
+    pub on_drop: Callback<MoveTask>,
 }
 
 #[function_component(TaskItem)]
@@ -15,6 +30,11 @@ pub fn task(
         task,
         on_toggle_task,
         on_delete_task,
+        on_assign_task,
+        dragging,
+        on_drag_start,
+        on_drag_end,
+        on_drop,
     }: &TaskItemProps,
 ) -> Html {
     let list_item_class = match task.completed {
@@ -34,15 +54,83 @@ pub fn task(
         move |_| on_toggle_task.emit(task.id.clone())
     };
 
+    let on_assign = {
+        let id = task.id.clone();
+        let on_assign_task = on_assign_task.clone();
+        Callback::from(move |assignee| on_assign_task.emit((id.clone(), assignee)))
+    };
+
+    let on_drag_start = {
+        let id = task.id.clone();
+        let on_drag_start = on_drag_start.clone();
+        move |event: DragEvent| {
+            // Firefox only starts a drag that carries data.
+            if let Some(data) = event.data_transfer() {
+                let _ = data.set_data("text/plain", &id);
+            }
+            on_drag_start.emit(id.clone())
+        }
+    };
+
+    let on_drag_end = {
+        let on_drag_end = on_drag_end.clone();
+        move |_| on_drag_end.emit(())
+    };
+
+    // Dropping is only allowed where `dragover` is cancelled.
+    let on_drag_over = |event: DragEvent| event.prevent_default();
+
+    let on_drop = {
+        let id = task.id.clone();
+        let on_drop = on_drop.clone();
+        move |event: DragEvent| {
+            event.prevent_default();
+            let item = event
+                .current_target()
+                .and_then(|target| target.dyn_into::<Element>().ok());
+            let lower_half = item.is_some_and(|item| {
+                let rect = item.get_bounding_client_rect();
+                f64::from(event.client_y()) > rect.top() + rect.height() / 2.0
+            });
+            on_drop.emit(match lower_half {
+                true => MoveTask::After(id.clone()),
+                false => MoveTask::Before(id.clone()),
+            })
+        }
+    };
+
+    let show_history = use_state(|| false);
+    let on_history_click = {
+        let show_history = show_history.clone();
+        move |_| show_history.set(!*show_history)
+    };
+
     html! {
-        <li class={classes!(list_item_class, "center")}>
+        <li
+            class={classes!(list_item_class, dragging.then_some("dragging"), "center")}
+            draggable="true"
+            ondragstart={on_drag_start}
+            ondragend={on_drag_end}
+            ondragover={on_drag_over}
+            ondrop={on_drop}
+        >
                 <input
                     type="checkbox"
                     checked={task.completed}
                     onclick={on_toggle}
                 />
-                <label>{ &task.title }</label>
+                <label>
+                    <Link<Route> to={Route::Task { id: task.id.clone() }}>{ &task.title }</Link<Route>>
+                </label>
+                <AssigneeSelect task={task.clone()} {on_assign} />
+                <button onclick={on_history_click} aria-expanded={show_history.to_string()}>
+                    {"History"}
+                </button>
                 <button onclick={on_delete_click}>{"Delete"}</button>
+                <Attachments task_id={task.id.clone()} />
+                if *show_history {
+                    <TaskHistory task_id={task.id.clone()} />
+                }
         </li>
 
     }
@@ -1,13 +1,17 @@
-use yew::{function_component, html, Callback, Html, Properties};
+use yew::{function_component, html, use_state, Callback, Html, Properties};
 
 use super::TaskItem;
-use crate::models::Task;
+use crate::models::{MoveTask, Task};
 
 #[derive(Properties, PartialEq)]
 pub struct TaskListProps {
     pub tasks: Vec<Task>,
     pub on_toggle_task: Callback<String>,
     pub on_delete_task: Callback<String>,
+    /// A task was assigned to a user, or unassigned with `None`.
+    pub on_assign_task: Callback<(String, Option<String>)>,
+    /// A task was dragged to a new place.
+    pub on_move_task: Callback<(String, MoveTask)>,
 }
 
 #[function_component(TaskList)]
@@ -16,8 +20,32 @@ pub fn task_list(
         tasks,
         on_toggle_task,
         on_delete_task,
+        on_assign_task,
+        on_move_task,
     }: &TaskListProps,
 ) -> Html {
+    let dragged = use_state(|| None::<String>);
+
+    let on_drag_start = {
+        let dragged = dragged.clone();
+        Callback::from(move |id: String| dragged.set(Some(id)))
+    };
+
+    // `dragend` follows `drop`, and also fires when the drag is cancelled.
+    let on_drag_end = {
+        let dragged = dragged.clone();
+        Callback::from(move |_| dragged.set(None))
+    };
+
+    let on_drop = {
+        let dragged = dragged.clone();
+        let on_move_task = on_move_task.clone();
+        Callback::from(move |to: MoveTask| match &*dragged {
+            Some(id) if id != to.target() => on_move_task.emit((id.clone(), to)),
+            _ => {}
+        })
+    };
+
     let tasks: Html = tasks
         .iter()
         .map(|task| {
@@ -26,6 +54,11 @@ pub fn task_list(
                     task={task.clone()}
                     on_toggle_task={on_toggle_task.clone()}
                     on_delete_task={on_delete_task.clone()}
+                    on_assign_task={on_assign_task.clone()}
+                    dragging={dragged.as_deref() == Some(task.id.as_str())}
+                    on_drag_start={on_drag_start.clone()}
+                    on_drag_end={on_drag_end.clone()}
+                    on_drop={on_drop.clone()}
                 />
             )
         })
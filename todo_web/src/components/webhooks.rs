@@ -0,0 +1,219 @@
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use crate::{
+    components::action_name,
+    models::{DeliveryStatus, EventAction, NewWebhook, Webhook, WebhookDelivery},
+    todo_api,
+};
+
+/// Every change a webhook can subscribe to, in the order they are offered.
+const EVENTS: [EventAction; 5] = [
+    EventAction::Created,
+    EventAction::Updated,
+    EventAction::Toggled,
+    EventAction::Moved,
+    EventAction::Deleted,
+];
+
+/// Lists the webhooks, with a form to add one and the delivery log of the
+/// one picked.
+#[function_component(Webhooks)]
+pub fn webhooks() -> Html {
+    let webhooks = use_state(Vec::<Webhook>::new);
+    let events = use_state(|| vec![EventAction::Created]);
+    let log = use_state(|| None::<(String, Vec<WebhookDelivery>)>);
+    let error = use_state(|| None::<String>);
+    let url_ref = use_node_ref();
+    let secret_ref = use_node_ref();
+
+    {
+        let webhooks = webhooks.clone();
+        let error = error.clone();
+        use_effect_with_deps(
+            move |_| {
+                wasm_bindgen_futures::spawn_local(async move {
+                    match todo_api::fetch_webhooks().await {
+                        Ok(fetched) => webhooks.set(fetched),
+                        Err(e) => error.set(Some(e.to_string())),
+                    }
+                });
+                || ()
+            },
+            (),
+        );
+    }
+
+    let on_event = |event: EventAction| {
+        let events = events.clone();
+        Callback::from(move |_| {
+            let mut list = (*events).clone();
+            match list.iter().position(|e| *e == event) {
+                Some(i) => {
+                    list.remove(i);
+                }
+                None => list.push(event),
+            }
+            events.set(list);
+        })
+    };
+
+    let on_add = {
+        let webhooks = webhooks.clone();
+        let events = events.clone();
+        let error = error.clone();
+        let (url_ref, secret_ref) = (url_ref.clone(), secret_ref.clone());
+        Callback::from(move |_| {
+            let (Some(url), Some(secret)) = (
+                url_ref.cast::<HtmlInputElement>(),
+                secret_ref.cast::<HtmlInputElement>(),
+            ) else {
+                return;
+            };
+            let webhook = NewWebhook {
+                url: url.value(),
+                secret: secret.value(),
+                events: (*events).clone(),
+            };
+            // Same rules as the API, so only valid webhooks are sent.
+            let webhook = match webhook.sanitize() {
+                Ok(webhook) => webhook,
+                Err(errors) => return error.set(Some(errors.to_string())),
+            };
+            let webhooks = webhooks.clone();
+            let error = error.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match todo_api::add_webhook(webhook).await {
+                    Ok(added) => {
+                        let mut list = (*webhooks).clone();
+                        list.push(added);
+                        webhooks.set(list);
+                        url.set_value("");
+                        secret.set_value("");
+                        error.set(None);
+                    }
+                    Err(e) => error.set(Some(e.to_string())),
+                }
+            });
+        })
+    };
+
+    let on_delete = |id: String| {
+        let webhooks = webhooks.clone();
+        let log = log.clone();
+        let error = error.clone();
+        Callback::from(move |_| {
+            let (id, webhooks, log, error) =
+                (id.clone(), webhooks.clone(), log.clone(), error.clone());
+            wasm_bindgen_futures::spawn_local(async move {
+                match todo_api::delete_webhook(id.clone()).await {
+                    Ok(_) => {
+                        webhooks.set(webhooks.iter().filter(|w| w.id != id).cloned().collect());
+                        if matches!(&*log, Some((webhook, _)) if *webhook == id) {
+                            log.set(None);
+                        }
+                    }
+                    Err(e) => error.set(Some(e.to_string())),
+                }
+            });
+        })
+    };
+
+    let on_log = |id: String| {
+        let log = log.clone();
+        let error = error.clone();
+        Callback::from(move |_| {
+            let (id, log, error) = (id.clone(), log.clone(), error.clone());
+            wasm_bindgen_futures::spawn_local(async move {
+                match todo_api::fetch_webhook_deliveries(id.clone()).await {
+                    Ok(deliveries) => log.set(Some((id, deliveries))),
+                    Err(e) => error.set(Some(e.to_string())),
+                }
+            });
+        })
+    };
+
+    let row = |webhook: &Webhook| {
+        let events: Vec<&str> = webhook.events.iter().map(|e| action_name(*e)).collect();
+        html! {
+            <tr key={webhook.id.clone()}>
+                <td>{ &webhook.url }</td>
+                <td>{ events.join(", ") }</td>
+                <td>
+                    <button onclick={on_log(webhook.id.clone())}>{ "Deliveries" }</button>
+                    <button onclick={on_delete(webhook.id.clone())}>{ "Delete" }</button>
+                </td>
+            </tr>
+        }
+    };
+
+    html! {
+        <section class="settings webhooks">
+            <h3>{ "Webhooks" }</h3>
+            <p>
+                { "Task changes are posted as JSON to each URL, signed with its secret in the \
+                   X-Todo-Signature header. Failed deliveries are retried with growing delays." }
+            </p>
+            <table>
+                <tr><th>{ "URL" }</th><th>{ "Events" }</th><th></th></tr>
+                { for webhooks.iter().map(row) }
+            </table>
+            <div class="webhook-form">
+                <input ref={url_ref} placeholder="https://example.com/hook" />
+                <input ref={secret_ref} type="password" placeholder="secret" />
+                { for EVENTS.iter().map(|event| html! {
+                    <label>
+                        <input
+                            type="checkbox"
+                            checked={events.contains(event)}
+                            onclick={on_event(*event)}
+                        />
+                        { action_name(*event) }
+                    </label>
+                }) }
+                <button onclick={on_add}>{ "Add webhook" }</button>
+            </div>
+            if let Some(message) = &*error {
+                <p class="error">{ message }</p>
+            }
+            if let Some((_, deliveries)) = &*log {
+                <table class="deliveries">
+                    <tr>
+                        <th>{ "Queued" }</th><th>{ "Event" }</th><th>{ "Status" }</th>
+                        <th>{ "Attempts" }</th><th>{ "Last response" }</th>
+                    </tr>
+                    { for deliveries.iter().map(delivery) }
+                </table>
+                if deliveries.is_empty() {
+                    <p class="empty">{ "No deliveries yet." }</p>
+                }
+            }
+        </section>
+    }
+}
+
+fn delivery(delivery: &WebhookDelivery) -> Html {
+    let status = match delivery.status {
+        DeliveryStatus::Pending => "pending",
+        DeliveryStatus::Delivered => "delivered",
+        DeliveryStatus::Failed => "failed",
+    };
+    let response = match (&delivery.error, delivery.response_status) {
+        (Some(error), _) => error.clone(),
+        (None, Some(status)) => status.to_string(),
+        (None, None) => String::new(),
+    };
+    html! {
+        <tr key={delivery.id.clone()} class={status}>
+            <td>
+                <time datetime={delivery.created_at.to_rfc3339()}>
+                    { delivery.created_at.format("%Y-%m-%d %H:%M").to_string() }
+                </time>
+            </td>
+            <td>{ action_name(delivery.event) }</td>
+            <td>{ status }</td>
+            <td>{ delivery.attempts }</td>
+            <td>{ response }</td>
+        </tr>
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
-    state::{TaskAction, TaskState},
+    models::{MoveTask, UpdateTask},
+    state::{Change, TaskAction, TaskState},
     todo_api,
 };
 
@@ -17,6 +18,8 @@ impl TaskController {
     pub fn init_tasks(&self) {
         let tasks = self.state.clone();
         wasm_bindgen_futures::spawn_local(async move {
+            let workflow = todo_api::fetch_statuses().await.unwrap();
+            tasks.dispatch(TaskAction::SetWorkflow(workflow));
             let fetched_tasks = todo_api::fetch_tasks().await.unwrap();
             tasks.dispatch(TaskAction::Set(fetched_tasks));
         });
@@ -49,4 +52,92 @@ impl TaskController {
             }
         });
     }
-}
\ No newline at end of file
+
+    /// Moves the task on screen at once, then puts it back if the server
+    /// refuses the move.
+    pub fn move_task(&self, id: String, to: MoveTask) {
+        let tasks = self.state.clone();
+        let order: Vec<String> = self.state.tasks.iter().map(|t| t.id.clone()).collect();
+        tasks.dispatch(TaskAction::Move(id.clone(), to.clone()));
+        wasm_bindgen_futures::spawn_local(async move {
+            if todo_api::move_task(id, to).await.is_err() {
+                tasks.dispatch(TaskAction::Reorder(order));
+            }
+        });
+    }
+
+    /// Moves the task to the status's column at once, then takes the
+    /// server's copy, or puts the task back if the change is refused.
+    pub fn set_task_status(&self, id: String, status: String) {
+        let before = match self.state.tasks.iter().find(|task| task.id == id) {
+            Some(task) if self.state.workflow.status_of(task).id != status => task.clone(),
+            _ => return,
+        };
+        let tasks = self.state.clone();
+        tasks.dispatch(TaskAction::SetStatus(id.clone(), status.clone()));
+        wasm_bindgen_futures::spawn_local(async move {
+            match todo_api::set_task_status(id, status).await {
+                Ok(task) => tasks.dispatch(TaskAction::Replace(task)),
+                Err(_) => tasks.dispatch(TaskAction::Replace(before)),
+            }
+        });
+    }
+
+    /// Assigns the task, or unassigns it with `None`, and takes the
+    /// server's copy.
+    pub fn assign_task(&self, id: String, assignee: Option<String>) {
+        let tasks = self.state.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Ok(task) = todo_api::assign_task(id, assignee).await {
+                tasks.dispatch(TaskAction::Replace(task));
+            }
+        });
+    }
+
+    pub fn undo(&self) {
+        let tasks = self.state.clone();
+        if let Some(change) = self.state.undo.last().cloned() {
+            wasm_bindgen_futures::spawn_local(async move {
+                tasks.dispatch(TaskAction::Undone(revert(change).await));
+            });
+        }
+    }
+
+    pub fn redo(&self) {
+        let tasks = self.state.clone();
+        if let Some(change) = self.state.redo.last().cloned() {
+            wasm_bindgen_futures::spawn_local(async move {
+                tasks.dispatch(TaskAction::Redone(revert(change).await));
+            });
+        }
+    }
+}
+
+/// Performs the inverse of `change` against the API and returns it, or
+/// `None` if it could not be performed, such as when the task has since
+/// been deleted elsewhere.
+async fn revert(change: Change) -> Option<Change> {
+    match change {
+        Change::Created(task) => {
+            let response = todo_api::delete_task(task.id.clone()).await.ok()?;
+            (response.affected_rows == 1).then_some(Change::Deleted(task))
+        }
+        Change::Deleted(task) => {
+            let mut created = todo_api::create_task(&task.title).await.ok()?;
+            if task.completed || task.due.is_some() || task.priority.is_some() {
+                let update = UpdateTask {
+                    completed: Some(task.completed),
+                    due: Some(task.due),
+                    priority: Some(task.priority),
+                    ..Default::default()
+                };
+                created = todo_api::update_task(created.id, update).await.ok()?;
+            }
+            Some(Change::Created(created))
+        }
+        Change::Toggled(id) => {
+            let response = todo_api::toggle_task(id.clone()).await.ok()?;
+            (response.affected_rows == 1).then_some(Change::Toggled(id))
+        }
+    }
+}
@@ -1,14 +1,23 @@
 use std::rc::Rc;
+
+use gloo_events::EventListener;
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlInputElement, KeyboardEvent};
 use yew::prelude::*;
+use yew_router::prelude::*;
 
 mod components;
 mod controllers;
 mod models;
+mod pages;
+mod routes;
 mod state;
 mod todo_api;
 
-use components::*;
+use components::Notifications;
 use controllers::*;
+use pages::*;
+use routes::{Filter, Route};
 use state::*;
 
 #[function_component(App)]
@@ -27,39 +36,64 @@ fn app() -> Html {
         );
     }
 
-    let on_create_task = {
-        let task_controller = task_controller.clone();
-        Callback::from(move |title: String| {
-            task_controller.create_task(title);
-        })
-    };
-
-    let on_delete_task = {
-        let task_controller = task_controller.clone();
-        Callback::from(move |id: String| {
-            task_controller.delete_task(id);
-        })
-    };
-
-    let on_toggle_task = {
-        let task_controller = task_controller;
-        Callback::from(move |id: String| {
-            task_controller.toggle_task(id);
-        })
-    };
+    // Re-registered after every render, so the shortcuts see the current
+    // undo and redo stacks.
+    use_effect(move || {
+        let window = web_sys::window().expect("running in a browser");
+        let listener = EventListener::new(&window, "keydown", move |event| {
+            if let Some(event) = event.dyn_ref::<KeyboardEvent>() {
+                on_shortcut(event, &task_controller);
+            }
+        });
+        move || drop(listener)
+    });
 
     html! {
+        <BrowserRouter>
+        <ContextProvider<UseReducerHandle<TaskState>> context={tasks}>
         <div class="container">
-        <TaskForm on_create_task={on_create_task} />
-        <h3>{"Todo"}</h3>
-        <div>
-            <TaskList
-                tasks={tasks.tasks.clone()}
-                on_delete_task={on_delete_task}
-                on_toggle_task={on_toggle_task}
-            />
-        </div>
+            <header class="nav">
+                <Link<Route> to={Route::All}>{ "Todo" }</Link<Route>>
+                <span class="nav-right">
+                    <Notifications />
+                    <Link<Route> to={Route::Settings}>{ "Settings" }</Link<Route>>
+                </span>
+            </header>
+            <Switch<Route> render={switch} />
         </div>
+        </ContextProvider<UseReducerHandle<TaskState>>>
+        </BrowserRouter>
+    }
+}
+
+fn switch(route: Route) -> Html {
+    match route {
+        Route::All => html! { <TasksPage filter={Filter::All} /> },
+        Route::Active => html! { <TasksPage filter={Filter::Active} /> },
+        Route::Completed => html! { <TasksPage filter={Filter::Completed} /> },
+        Route::Task { id } => html! { <TaskDetail {id} /> },
+        Route::Settings => html! { <Settings /> },
+        Route::NotFound => html! { <p>{ "Page not found." }</p> },
+    }
+}
+
+/// Ctrl+Z undoes and Ctrl+Shift+Z redoes, with Cmd in place of Ctrl on
+/// macOS. Text inputs keep their own undo.
+fn on_shortcut(event: &KeyboardEvent, task_controller: &TaskController) {
+    let in_input = event
+        .target()
+        .and_then(|target| target.dyn_into::<HtmlInputElement>().ok())
+        .is_some();
+    if in_input || !(event.ctrl_key() || event.meta_key()) || !event.key().eq_ignore_ascii_case("z")
+    {
+        return;
+    }
+
+    event.prevent_default();
+    if event.shift_key() {
+        task_controller.redo();
+    } else {
+        task_controller.undo();
     }
 }
 
@@ -0,0 +1,7 @@
+mod settings;
+mod task_detail;
+mod tasks;
+
+pub use settings::*;
+pub use task_detail::*;
+pub use tasks::*;
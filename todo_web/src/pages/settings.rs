@@ -0,0 +1,138 @@
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use crate::{
+    components::Webhooks,
+    models::{Status, Workflow},
+    state::{TaskAction, TaskState},
+    todo_api,
+};
+
+/// Edits the workflow's statuses. Changes are kept as a draft until saved.
+#[function_component(Settings)]
+pub fn settings() -> Html {
+    let tasks = use_context::<UseReducerHandle<TaskState>>().expect("App provides the tasks");
+    let draft = use_state(|| tasks.workflow.statuses.clone());
+    let message = use_state(|| None::<String>);
+
+    // The workflow may arrive after the page opens.
+    {
+        let draft = draft.clone();
+        use_effect_with_deps(
+            move |workflow: &Workflow| {
+                draft.set(workflow.statuses.clone());
+                || ()
+            },
+            tasks.workflow.clone(),
+        );
+    }
+
+    let edit = |index: usize, change: fn(&mut Status, &HtmlInputElement)| {
+        let draft = draft.clone();
+        Callback::from(move |event: Event| {
+            let input: HtmlInputElement = event.target_unchecked_into();
+            let mut statuses = (*draft).clone();
+            change(&mut statuses[index], &input);
+            draft.set(statuses);
+        })
+    };
+
+    let remove = |index: usize| {
+        let draft = draft.clone();
+        Callback::from(move |_| {
+            let mut statuses = (*draft).clone();
+            statuses.remove(index);
+            draft.set(statuses);
+        })
+    };
+
+    let on_add = {
+        let draft = draft.clone();
+        Callback::from(move |_| {
+            let mut statuses = (*draft).clone();
+            statuses.push(Status {
+                id: String::new(),
+                name: String::new(),
+                done: false,
+            });
+            draft.set(statuses);
+        })
+    };
+
+    let on_save = {
+        let draft = draft.clone();
+        let message = message.clone();
+        let tasks = tasks.clone();
+        Callback::from(move |_| {
+            // Same rules as the API, so most mistakes never leave the page.
+            let workflow = Workflow {
+                statuses: (*draft).clone(),
+            };
+            let workflow = match workflow.sanitize() {
+                Ok(workflow) => workflow,
+                Err(errors) => return message.set(Some(errors.to_string())),
+            };
+            let message = message.clone();
+            let tasks = tasks.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match todo_api::set_statuses(workflow).await {
+                    Ok(workflow) => {
+                        tasks.dispatch(TaskAction::SetWorkflow(workflow));
+                        message.set(Some("Saved.".to_string()));
+                    }
+                    Err(e) => message.set(Some(e.to_string())),
+                }
+            });
+        })
+    };
+
+    let row = |(index, status): (usize, &Status)| {
+        html! {
+            <tr>
+                <td>
+                    <input
+                        value={status.name.clone()}
+                        onchange={edit(index, |s, input| s.name = input.value())}
+                    />
+                </td>
+                <td>
+                    <input
+                        value={status.id.clone()}
+                        onchange={edit(index, |s, input| s.id = input.value())}
+                    />
+                </td>
+                <td>
+                    <input
+                        type="checkbox"
+                        checked={status.done}
+                        onchange={edit(index, |s, input| s.done = input.checked())}
+                    />
+                </td>
+                <td><button onclick={remove(index)}>{ "Remove" }</button></td>
+            </tr>
+        }
+    };
+
+    html! {
+        <>
+            <section class="settings">
+                <h3>{ "Statuses" }</h3>
+                <p>
+                    { "Board columns, in order. New and reopened tasks go to the first open status, \
+                       and completed tasks to the first done one. Tasks keep their status id when \
+                       it is renamed." }
+                </p>
+                <table>
+                    <tr><th>{ "Name" }</th><th>{ "Id" }</th><th>{ "Done" }</th><th></th></tr>
+                    { for draft.iter().enumerate().map(row) }
+                </table>
+                <button onclick={on_add}>{ "Add status" }</button>
+                <button onclick={on_save}>{ "Save" }</button>
+                if let Some(message) = &*message {
+                    <p class="message">{ message }</p>
+                }
+            </section>
+            <Webhooks />
+        </>
+    }
+}
@@ -0,0 +1,79 @@
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::{
+    components::{Comments, ReminderForm, TaskHistory},
+    models::Task,
+    routes::Route,
+    state::TaskState,
+    todo_api,
+};
+
+#[derive(Properties, PartialEq)]
+pub struct TaskDetailProps {
+    pub id: String,
+}
+
+/// One task, loaded from the API so a shared link works before the list
+/// has loaded.
+#[function_component(TaskDetail)]
+pub fn task_detail(TaskDetailProps { id }: &TaskDetailProps) -> Html {
+    let tasks = use_context::<UseReducerHandle<TaskState>>().expect("App provides the tasks");
+    let task = use_state(|| None::<Result<Task, String>>);
+
+    {
+        let task = task.clone();
+        use_effect_with_deps(
+            move |id: &String| {
+                let id = id.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let fetched = todo_api::fetch_task(id).await.map_err(|e| match e {
+                        e if e.is_not_found() => "No such task.".to_string(),
+                        e => e.to_string(),
+                    });
+                    task.set(Some(fetched));
+                });
+                || ()
+            },
+            id.clone(),
+        );
+    }
+
+    let back = html! { <Link<Route> to={Route::All}>{ "← All tasks" }</Link<Route>> };
+    let task_state = task.clone();
+    let task = match &*task {
+        None => return html! { <p>{ "Loading…" }</p> },
+        Some(Err(message)) => return html! { <><p class="error">{ message }</p>{ back }</> },
+        Some(Ok(task)) => task,
+    };
+
+    let on_change = Callback::from(move |changed: Task| task_state.set(Some(Ok(changed))));
+
+    const FORMAT: &str = "%Y-%m-%d %H:%M";
+    html! {
+        <article class="task-detail">
+            { back }
+            <h3>{ &task.title }</h3>
+            <dl>
+                <dt>{ "Status" }</dt>
+                <dd>{ &tasks.workflow.status_of(task).name }</dd>
+                <dt>{ "Created" }</dt>
+                <dd>{ task.created_at.format(FORMAT).to_string() }</dd>
+                if let Some(due) = &task.due {
+                    <dt>{ "Due" }</dt>
+                    <dd>{ due.format(FORMAT).to_string() }</dd>
+                }
+                if let Some(priority) = task.priority {
+                    <dt>{ "Priority" }</dt>
+                    <dd>{ priority }</dd>
+                }
+                <dt>{ "Reminder (UTC)" }</dt>
+                <dd><ReminderForm task={task.clone()} {on_change} /></dd>
+            </dl>
+            <h4>{ "Comments" }</h4>
+            <Comments task_id={task.id.clone()} />
+            <h4>{ "History" }</h4>
+            <TaskHistory task_id={task.id.clone()} />
+        </article>
+    }
+}
@@ -0,0 +1,152 @@
+use std::rc::Rc;
+
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::{
+    components::*,
+    controllers::TaskController,
+    models::Task,
+    routes::{Filter, ListQuery, Route, View},
+    state::TaskState,
+};
+
+#[derive(Properties, PartialEq)]
+pub struct TasksPageProps {
+    pub filter: Filter,
+}
+
+/// The task list or board, filtered by the route and shown in the view named
+/// by the query string.
+#[function_component(TasksPage)]
+pub fn tasks_page(TasksPageProps { filter }: &TasksPageProps) -> Html {
+    let tasks = use_context::<UseReducerHandle<TaskState>>().expect("App provides the tasks");
+    let task_controller = Rc::new(TaskController::new(tasks.clone()));
+    let query = use_location()
+        .and_then(|location| location.query::<ListQuery>().ok())
+        .unwrap_or_default();
+
+    let visible: Vec<Task> = tasks
+        .tasks
+        .iter()
+        .filter(|task| filter.matches(task))
+        .cloned()
+        .collect();
+
+    let on_create_task = {
+        let task_controller = task_controller.clone();
+        Callback::from(move |title: String| {
+            task_controller.create_task(title);
+        })
+    };
+
+    let on_delete_task = {
+        let task_controller = task_controller.clone();
+        Callback::from(move |id: String| {
+            task_controller.delete_task(id);
+        })
+    };
+
+    let on_toggle_task = {
+        let task_controller = task_controller.clone();
+        Callback::from(move |id: String| {
+            task_controller.toggle_task(id);
+        })
+    };
+
+    let on_assign_task = {
+        let task_controller = task_controller.clone();
+        Callback::from(move |(id, assignee)| {
+            task_controller.assign_task(id, assignee);
+        })
+    };
+
+    let on_move_task = {
+        let task_controller = task_controller.clone();
+        Callback::from(move |(id, to)| {
+            task_controller.move_task(id, to);
+        })
+    };
+
+    let on_set_status = {
+        let task_controller = task_controller.clone();
+        Callback::from(move |(id, status)| {
+            task_controller.set_task_status(id, status);
+        })
+    };
+
+    let on_undo = {
+        let task_controller = task_controller.clone();
+        Callback::from(move |_| task_controller.undo())
+    };
+
+    let on_redo = {
+        let task_controller = task_controller.clone();
+        Callback::from(move |_| task_controller.redo())
+    };
+
+    // Links keep the other half of the state: filters keep the view, and
+    // views keep the filter.
+    let filter_link = |link: Filter, label: &'static str| {
+        html! {
+            <Link<Route, ListQuery>
+                to={link.route()}
+                query={Some(query)}
+                classes={classes!((link == *filter).then_some("active"))}
+            >
+                { label }
+            </Link<Route, ListQuery>>
+        }
+    };
+    let view_link = |view: View, label: &'static str| {
+        html! {
+            <Link<Route, ListQuery>
+                to={filter.route()}
+                query={Some(ListQuery { view })}
+                classes={classes!((view == query.view).then_some("active"))}
+            >
+                { label }
+            </Link<Route, ListQuery>>
+        }
+    };
+
+    html! {
+        <>
+        <TaskForm on_create_task={on_create_task} />
+        <div class="toolbar">
+            <span class="views">
+                { view_link(View::List, "List") }
+                { view_link(View::Board, "Board") }
+            </span>
+            <button onclick={on_undo} disabled={tasks.undo.is_empty()} title="Undo (Ctrl+Z)">
+                { "Undo" }
+            </button>
+            <button onclick={on_redo} disabled={tasks.redo.is_empty()} title="Redo (Ctrl+Shift+Z)">
+                { "Redo" }
+            </button>
+        </div>
+        <nav class="filters">
+            { filter_link(Filter::All, "All") }
+            { filter_link(Filter::Active, "Active") }
+            { filter_link(Filter::Completed, "Completed") }
+        </nav>
+        <div>
+            if query.view == View::List {
+                <TaskList
+                    tasks={visible}
+                    on_delete_task={on_delete_task}
+                    on_toggle_task={on_toggle_task}
+                    on_assign_task={on_assign_task}
+                    on_move_task={on_move_task}
+                />
+            } else {
+                <Board
+                    tasks={visible}
+                    workflow={tasks.workflow.clone()}
+                    on_set_status={on_set_status}
+                />
+            }
+        </div>
+        </>
+    }
+}
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+use yew_router::Routable;
+
+use crate::models::Task;
+
+#[derive(Clone, Routable, PartialEq)]
+pub enum Route {
+    #[at("/")]
+    All,
+    #[at("/active")]
+    Active,
+    #[at("/completed")]
+    Completed,
+    #[at("/task/:id")]
+    Task { id: String },
+    #[at("/settings")]
+    Settings,
+    #[not_found]
+    #[at("/404")]
+    NotFound,
+}
+
+/// Which tasks a list route shows.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Filter {
+    All,
+    Active,
+    Completed,
+}
+
+impl Filter {
+    pub fn matches(&self, task: &Task) -> bool {
+        match self {
+            Filter::All => true,
+            Filter::Active => !task.completed,
+            Filter::Completed => task.completed,
+        }
+    }
+
+    pub fn route(&self) -> Route {
+        match self {
+            Filter::All => Route::All,
+            Filter::Active => Route::Active,
+            Filter::Completed => Route::Completed,
+        }
+    }
+}
+
+/// How the tasks are shown.
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum View {
+    #[default]
+    List,
+    Board,
+}
+
+/// The query string of the list routes, as in `/active?view=board`, so a
+/// shared link opens the same view.
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ListQuery {
+    #[serde(default)]
+    pub view: View,
+}
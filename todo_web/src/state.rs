@@ -2,51 +2,229 @@ use std::rc::Rc;
 
 use yew::Reducible;
 
-use crate::models::Task;
+use crate::models::{MoveTask, Task, UpdateTask, Workflow};
+
+/// A change made through the API. Undoing one performs its inverse, which is
+/// itself a `Change` that redo performs the inverse of.
+#[derive(Clone, PartialEq)]
+pub enum Change {
+    Created(Task),
+    Deleted(Task),
+    Toggled(String),
+}
+
+impl Change {
+    /// Re-creating a deleted task gives it a new id, so changes recorded
+    /// against the old one are moved over.
+    fn rename(&mut self, from: &str, to: &str) {
+        match self {
+            Change::Created(task) | Change::Deleted(task) if task.id == from => {
+                task.id = to.to_string()
+            }
+            Change::Toggled(id) if id == from => *id = to.to_string(),
+            _ => {}
+        }
+    }
+}
 
 pub enum TaskAction {
     Set(Vec<Task>),
     Add(Task),
     Delete(String),
     Toggle(String),
+    /// Moves a task before the server confirms it, so dragging feels
+    /// instant. Moves are not undoable.
+    Move(String, MoveTask),
+    /// Puts the tasks back in the order of these ids, rolling back a move
+    /// the server refused. Tasks added since keep their place at the end.
+    Reorder(Vec<String>),
+    SetWorkflow(Workflow),
+    /// Moves a task to another status before the server confirms it. Like
+    /// moves, status changes are not undoable.
+    SetStatus(String, String),
+    /// The task as the server has it, replacing the local copy.
+    Replace(Task),
+    /// The last change was undone by performing this one. `None` means it
+    /// could not be undone, and is dropped.
+    Undone(Option<Change>),
+    /// The last undone change was redone by performing this one, or
+    /// dropped as for `Undone`.
+    Redone(Option<Change>),
 }
 
+#[derive(Default)]
 pub struct TaskState {
     pub tasks: Vec<Task>,
+    pub undo: Vec<Change>,
+    pub redo: Vec<Change>,
+    pub workflow: Workflow,
 }
 
-impl Default for TaskState {
-    fn default() -> Self {
-        Self { tasks: vec![] }
+impl TaskState {
+    fn with_tasks(&self, tasks: Vec<Task>) -> Self {
+        Self {
+            tasks,
+            undo: self.undo.clone(),
+            redo: self.redo.clone(),
+            workflow: self.workflow.clone(),
+        }
+    }
+
+    /// Applies `update` to the task with this id as the API would.
+    fn updated(&self, id: &str, update: UpdateTask) -> Vec<Task> {
+        let mut tasks = self.tasks.clone();
+        for task in tasks.iter_mut().filter(|task| task.id == id) {
+            if let Ok(update) = self.workflow.settle(task, update.clone()) {
+                *task = update.apply(task);
+            }
+        }
+        tasks
+    }
+
+    fn apply(&self, change: &Change) -> Vec<Task> {
+        let mut tasks = self.tasks.clone();
+        match change {
+            Change::Created(task) => tasks.push(task.clone()),
+            Change::Deleted(task) => tasks.retain(|t| t.id != task.id),
+            Change::Toggled(id) => {
+                let completed = tasks
+                    .iter()
+                    .find(|task| &task.id == id)
+                    .map(|t| t.completed);
+                let update = UpdateTask {
+                    completed: completed.map(|completed| !completed),
+                    ..Default::default()
+                };
+                return self.updated(id, update);
+            }
+        }
+        tasks
+    }
+
+    fn moved(&self, id: &str, to: &MoveTask) -> Vec<Task> {
+        let mut tasks = self.tasks.clone();
+        let from = match tasks.iter().position(|task| task.id == id) {
+            Some(from) => from,
+            None => return tasks,
+        };
+        let task = tasks.remove(from);
+        let index = match (tasks.iter().position(|t| t.id == to.target()), to) {
+            (Some(target), MoveTask::Before(_)) => target,
+            (Some(target), MoveTask::After(_)) => target + 1,
+            (None, _) => from,
+        };
+        tasks.insert(index, task);
+        tasks
+    }
+
+    fn reordered(&self, order: &[String]) -> Vec<Task> {
+        let mut tasks = self.tasks.clone();
+        tasks.sort_by_key(|task| {
+            order
+                .iter()
+                .position(|id| id == &task.id)
+                .unwrap_or(order.len())
+        });
+        tasks
+    }
+
+    /// A new change clears the redo stack, as in any editor.
+    fn record(&self, change: Change) -> Self {
+        let mut undo = self.undo.clone();
+        undo.push(change.clone());
+        Self {
+            tasks: self.apply(&change),
+            undo,
+            redo: vec![],
+            workflow: self.workflow.clone(),
+        }
+    }
+
+    /// Pops the top of the `from` stack and pushes `change`, the inverse
+    /// just performed, onto `to`. Returns the tasks and both stacks.
+    fn replay(
+        &self,
+        change: Option<Change>,
+        mut from: Vec<Change>,
+        mut to: Vec<Change>,
+    ) -> (Vec<Task>, Vec<Change>, Vec<Change>) {
+        let replaced = from.pop();
+        let change = match change {
+            Some(change) => change,
+            None => return (self.tasks.clone(), from, to),
+        };
+
+        if let (Some(Change::Deleted(old)), Change::Created(new)) = (&replaced, &change) {
+            for entry in from.iter_mut().chain(to.iter_mut()) {
+                entry.rename(&old.id, &new.id);
+            }
+        }
+        let tasks = self.apply(&change);
+        to.push(change);
+        (tasks, from, to)
     }
 }
 
 impl Reducible for TaskState {
     type Action = TaskAction;
 
-    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self>{
-        let next_tasks = match action {
-            TaskAction::Set(tasks) => tasks,
-            TaskAction::Add(task) => {
-                let mut tasks = self.tasks.clone();
-                tasks.push(task);
-                tasks
-            }
-            TaskAction::Delete(id) => {
-                let mut tasks = self.tasks.clone();
-                tasks.retain(|task| task.id != id);
-                tasks
-            }
-            TaskAction::Toggle(id) => {
-                let mut tasks = self.tasks.clone();
-                for task in tasks.iter_mut() {
-                    if task.id == id {
-                        task.completed = !task.completed;
-                    }
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        let next = match action {
+            TaskAction::Set(tasks) => Self {
+                tasks,
+                undo: vec![],
+                redo: vec![],
+                workflow: self.workflow.clone(),
+            },
+            TaskAction::Add(task) => self.record(Change::Created(task)),
+            TaskAction::Delete(id) => match self.tasks.iter().find(|task| task.id == id) {
+                Some(task) => self.record(Change::Deleted(task.clone())),
+                None => return self,
+            },
+            TaskAction::Toggle(id) => self.record(Change::Toggled(id)),
+            TaskAction::Move(id, to) => self.with_tasks(self.moved(&id, &to)),
+            TaskAction::Reorder(order) => self.with_tasks(self.reordered(&order)),
+            TaskAction::SetWorkflow(workflow) => Self {
+                workflow,
+                ..self.with_tasks(self.tasks.clone())
+            },
+            TaskAction::SetStatus(id, status) => {
+                let update = UpdateTask {
+                    status: Some(status),
+                    ..Default::default()
+                };
+                self.with_tasks(self.updated(&id, update))
+            }
+            TaskAction::Replace(task) => {
+                let tasks = self
+                    .tasks
+                    .iter()
+                    .map(|t| match t.id == task.id {
+                        true => task.clone(),
+                        false => t.clone(),
+                    })
+                    .collect();
+                self.with_tasks(tasks)
+            }
+            TaskAction::Undone(change) => {
+                let (tasks, undo, redo) = self.replay(change, self.undo.clone(), self.redo.clone());
+                Self {
+                    tasks,
+                    undo,
+                    redo,
+                    workflow: self.workflow.clone(),
+                }
+            }
+            TaskAction::Redone(change) => {
+                let (tasks, redo, undo) = self.replay(change, self.redo.clone(), self.undo.clone());
+                Self {
+                    tasks,
+                    undo,
+                    redo,
+                    workflow: self.workflow.clone(),
                 }
-                tasks
             }
         };
-        Self { tasks: next_tasks }.into()
+        next.into()
     }
-}
\ No newline at end of file
+}
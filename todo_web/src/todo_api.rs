@@ -1,40 +1,126 @@
 use crate::models::*;
-use reqwasm::{http::Request, Error};
+use todo_client::{Client, Error};
 
 const BASE_URL: &str = "http://localhost:8080";
 
+fn client() -> Client {
+    Client::new(BASE_URL).expect("BASE_URL is a valid url")
+}
+
 pub async fn fetch_tasks() -> Result<Vec<Task>, Error> {
-    Request::get(&format!("{BASE_URL}/tasks"))
-        .send()
-        .await
-        .unwrap()
-        .json()
-        .await
+    client().get_tasks().await
+}
+
+pub async fn fetch_task(id: String) -> Result<Task, Error> {
+    client().get_task(&id).await
 }
 
 pub async fn create_task(title: &str) -> Result<Task, Error> {
-    Request::post(&format!("{BASE_URL}/task/{title}"))
-        .send()
-        .await
-        .unwrap()
-        .json()
-        .await
+    client().create_task(title).await
+}
+
+pub async fn update_task(id: String, update: UpdateTask) -> Result<Task, Error> {
+    client().update_task(&id, &update).await
+}
+
+pub async fn move_task(id: String, to: MoveTask) -> Result<Task, Error> {
+    client().move_task(&id, &to).await
+}
+
+pub async fn set_task_status(id: String, status: String) -> Result<Task, Error> {
+    client().set_task_status(&id, &status).await
+}
+
+pub async fn assign_task(id: String, assignee: Option<String>) -> Result<Task, Error> {
+    match assignee {
+        Some(assignee) => client().assign_task(&id, &assignee).await,
+        None => client().unassign_task(&id).await,
+    }
+}
+
+pub async fn fetch_assignees(id: String) -> Result<Vec<String>, Error> {
+    client().get_assignees(&id).await
+}
+
+pub async fn fetch_statuses() -> Result<Workflow, Error> {
+    client().get_statuses().await
+}
+
+pub async fn set_statuses(workflow: Workflow) -> Result<Workflow, Error> {
+    client().set_statuses(&workflow).await
 }
 
 pub async fn delete_task(id: String) -> Result<AffectedRows, Error> {
-    Request::delete(&format!("{BASE_URL}/task/{id}"))
-        .send()
-        .await
-        .unwrap()
-        .json()
-        .await
+    client().delete_task(&id).await
 }
 
 pub async fn toggle_task(id: String) -> Result<AffectedRows, Error> {
-    Request::put(&format!("{BASE_URL}/task/{id}"))
-        .send()
-        .await
-        .unwrap()
-        .json()
+    client().toggle_task(&id).await
+}
+
+pub async fn fetch_history(id: String) -> Result<Vec<TaskEvent>, Error> {
+    client().get_task_history(&id).await
+}
+
+pub async fn fetch_comments(task: String) -> Result<Vec<Comment>, Error> {
+    client().get_comments(&task).await
+}
+
+pub async fn add_comment(task: String, comment: NewComment) -> Result<Comment, Error> {
+    client().add_comment(&task, comment).await
+}
+
+pub async fn edit_comment(id: String, edit: EditComment) -> Result<Comment, Error> {
+    client().edit_comment(&id, edit).await
+}
+
+pub async fn delete_comment(id: String) -> Result<AffectedRows, Error> {
+    client().delete_comment(&id).await
+}
+
+pub async fn fetch_attachments(task: String) -> Result<Vec<Attachment>, Error> {
+    client().get_attachments(&task).await
+}
+
+pub async fn add_attachment(
+    task: String,
+    name: String,
+    content_type: String,
+    bytes: Vec<u8>,
+) -> Result<Attachment, Error> {
+    client()
+        .add_attachment(&task, &name, &content_type, bytes)
         .await
 }
+
+pub fn attachment_url(id: &str) -> String {
+    client().attachment_url(id).to_string()
+}
+
+pub async fn delete_attachment(id: String) -> Result<AffectedRows, Error> {
+    client().delete_attachment(&id).await
+}
+
+pub async fn fetch_notifications() -> Result<Vec<Notification>, Error> {
+    client().get_notifications().await
+}
+
+pub async fn read_notification(id: String) -> Result<Notification, Error> {
+    client().read_notification(&id).await
+}
+
+pub async fn fetch_webhooks() -> Result<Vec<Webhook>, Error> {
+    client().get_webhooks().await
+}
+
+pub async fn add_webhook(webhook: NewWebhook) -> Result<Webhook, Error> {
+    client().add_webhook(webhook).await
+}
+
+pub async fn delete_webhook(id: String) -> Result<AffectedRows, Error> {
+    client().delete_webhook(&id).await
+}
+
+pub async fn fetch_webhook_deliveries(id: String) -> Result<Vec<WebhookDelivery>, Error> {
+    client().get_webhook_deliveries(&id).await
+}